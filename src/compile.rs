@@ -0,0 +1,1972 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::parse::parser::*;
+use crate::parse::Span;
+use crate::sb3::*;
+
+mod builtins;
+mod decl;
+mod heap;
+mod loops;
+mod stack;
+
+use stack::{Binding, FramePointer, FRAME_ARG, reporter, simple, variable};
+
+pub struct CompileError {
+    pub msg: String,
+    pub span: Span,
+
+    // suggestion on how to fix the error
+    pub help: Option<String>
+}
+
+impl CompileError {
+    pub fn new(msg: String, span: Span) -> Self {
+        CompileError {
+            msg,
+            span,
+            help: None
+        }
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+}
+
+/// Settings which change the generated code
+#[derive(Clone, Copy, Default)]
+pub struct Options {
+    // trap when an array is indexed out of bounds
+    pub bounds_check: bool
+}
+
+// names of the blank costumes given to objects without any
+const BLANK_COSTUME: &str = "costume1";
+const BLANK_BACKDROP: &str = "backdrop1";
+
+/// Compiles source files into the sprites and stage of a project
+pub struct ProjectCompiler<'a> {
+    project: &'a mut Project,
+    options: Options,
+    stage_declared: bool,
+
+    // number of files compiled so far
+    files: usize,
+
+    // backdrops named in each file, which are checked once every file declared its objects
+    backdrops: Vec<(usize, String, Span)>
+}
+
+// compile the functions and event handlers of an object, keeping the backdrops they name
+fn compile_object(obj: &mut Object, options: Options, funcs: &[AstFunc], events: &[AstEvent],
+    file: usize, backdrops: &mut Vec<(usize, String, Span)>) -> Vec<CompileError>
+{
+    let mut compiler = Compiler::new(obj, options);
+    let errors = compiler.compile(funcs, events);
+    backdrops.extend(compiler.backdrops.drain(..).map(|(name, span)| (file, name, span)));
+    errors
+}
+
+impl<'a> ProjectCompiler<'a> {
+    pub fn new(project: &'a mut Project, options: Options) -> Self {
+        Self {
+            project,
+            options,
+            stage_declared: false,
+            files: 0,
+            backdrops: Vec::new()
+        }
+    }
+
+    /// Compiles the declarations and functions of a source file.
+    ///
+    /// Functions outside of a declaration go into a sprite named `default_name`.
+    /// Asset paths are relative to `asset_dir`.
+    pub fn file(&mut self, program: &AstProgram, default_name: &str, asset_dir: &Path) -> Result<(), Vec<CompileError>> {
+        let mut errors = Vec::new();
+        let file = self.files;
+        self.files += 1;
+
+        // a file without any declarations is a sprite by itself
+        if !program.functions.is_empty() || !program.events.is_empty() || program.objects.is_empty() {
+            let span = program.functions.first().map(|func| func.span)
+                .or(program.events.first().map(|event| event.span));
+            self.project.create_sprite(default_name);
+            self.check_sprite_name(span, &mut errors);
+
+            let sprite = self.project.sprites.last_mut().unwrap();
+            errors.append(&mut compile_object(&mut sprite.obj, self.options, &program.functions, &program.events, file, &mut self.backdrops));
+        }
+
+        for obj in program.objects.iter() {
+            match &obj.kind {
+                AstObjectKind::Sprite(id) => {
+                    let sprite = self.project.create_sprite(id);
+                    errors.append(&mut decl::sprite(sprite, &obj.properties, asset_dir));
+                    errors.append(&mut compile_object(&mut sprite.obj, self.options, &obj.functions, &obj.events, file, &mut self.backdrops));
+                    self.check_sprite_name(Some(obj.span), &mut errors);
+                },
+
+                AstObjectKind::Stage => {
+                    if self.stage_declared {
+                        errors.push(CompileError::new("the stage is already declared".to_string(), obj.span)
+                            .with_help("a project can only have one stage declaration"));
+                        continue;
+                    }
+
+                    self.stage_declared = true;
+                    errors.append(&mut decl::stage(self.project, &obj.properties, asset_dir));
+                    errors.append(&mut compile_object(&mut self.project.stage, self.options, &obj.functions, &obj.events, file, &mut self.backdrops));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // check that the last created sprite doesn't share its name with another sprite
+    fn check_sprite_name(&self, span: Option<Span>, errors: &mut Vec<CompileError>) {
+        let (last, others) = self.project.sprites.split_last().unwrap();
+
+        if others.iter().any(|sprite| sprite.obj.name == last.obj.name) {
+            let msg = format!("sprite \"{}\" is already declared", last.obj.name);
+
+            // an implicit sprite without functions has nothing to point at
+            errors.push(CompileError::new(msg, span.unwrap_or(Span { start: 0, end: 0, line: 1, col: 1 })));
+        }
+    }
+
+    /// Gives a blank costume to the stage and every sprite that didn't declare one, then checks
+    /// the backdrops named by every file.
+    ///
+    /// Each error comes with the number of the file it is in, counting the calls to [`Self::file`] from 0.
+    pub fn finish(mut self) -> Vec<(usize, CompileError)> {
+        if self.project.stage.costumes.is_empty() {
+            self.project.stage.add_costume(Costume::empty(BLANK_BACKDROP));
+        }
+
+        for sprite in self.project.sprites.iter_mut() {
+            if sprite.obj.costumes.is_empty() {
+                sprite.obj.add_costume(Costume::empty(BLANK_COSTUME));
+            }
+        }
+
+        let stage = &self.project.stage;
+        self.backdrops.sort_by_key(|(file, _, span)| (*file, span.start));
+
+        self.backdrops.into_iter()
+            .filter(|(_, name, _)| !stage.costumes.iter().any(|costume| costume.name == *name))
+            .map(|(file, name, span)| (file, CompileError::new(format!("unknown backdrop \"{}\"", name), span)
+                .with_help("add a backdrop with this name to the backdrops property of the stage")))
+            .collect()
+    }
+}
+
+// the custom block a function is compiled to
+struct Procedure {
+    prototype: Prototype,
+
+    // whether the function returns a value
+    returns: bool
+}
+
+/// Lowers a parsed program into the scripts of a Scratch object
+pub struct Compiler<'a> {
+    obj: &'a mut Object,
+    options: Options,
+    procedures: HashMap<String, Procedure>,
+
+    // parameters of the function being compiled
+    params: Vec<Argument>,
+
+    // blocks which must run before the statement being compiled,
+    // such as calls to functions used in its expressions
+    pre: Vec<Block>,
+
+    // local variables of each scope, with their slot in the frame
+    scopes: Vec<Vec<(String, usize)>>,
+
+    // the first slot which isn't used by a local variable in scope
+    next_slot: usize,
+
+    // number of slots needed by the script being compiled
+    max_slots: usize,
+
+    // where the script being compiled keeps its frame, if it has one
+    frame: Option<FramePointer>,
+
+    // number of event handlers with a frame compiled so far
+    handler_frames: usize,
+
+    // number of temporaries used by the statement being compiled
+    temps: usize,
+
+    // slots of the flags of the loops around the statement being compiled,
+    // for loops which use break or continue
+    loops: Vec<Option<usize>>,
+
+    // backdrops named by literal strings, with their spans
+    backdrops: Vec<(String, Span)>
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(obj: &'a mut Object, options: Options) -> Self {
+        Self {
+            obj,
+            options,
+            procedures: HashMap::new(),
+            params: Vec::new(),
+            pre: Vec::new(),
+            scopes: Vec::new(),
+            next_slot: 0,
+            max_slots: 0,
+            frame: None,
+            handler_frames: 0,
+            temps: 0,
+            loops: Vec::new(),
+            backdrops: Vec::new()
+        }
+    }
+
+    /// Compiles every given function and event handler, returning the errors of all that failed
+    pub fn compile(&mut self, funcs: &[AstFunc], events: &[AstEvent]) -> Vec<CompileError> {
+        let mut errors = Vec::new();
+
+        // declare every function first so they can call each other
+        for func in funcs.iter() {
+            if let Err(err) = self.declare(func) {
+                errors.push(err);
+            }
+        }
+
+        // so that a message sent by a reporter can show one the object receives
+        for event in events.iter() {
+            if let AstEventKind::Broadcast(name) = &event.kind {
+                self.obj.add_broadcast(name);
+            }
+        }
+
+        for func in funcs.iter() {
+            if let Err(err) = self.func(func) {
+                errors.push(err);
+            }
+        }
+
+        for event in events.iter() {
+            if let Err(err) = self.event(event) {
+                errors.push(err);
+            }
+        }
+
+        errors
+    }
+
+    fn declare(&mut self, func: &AstFunc) -> Result<(), CompileError> {
+        if self.procedures.contains_key(&func.id) {
+            return Err(CompileError::new(format!("function {} is already defined", func.id), func.span));
+        }
+
+        let mut args: Vec<(String, ArgumentType)> = Vec::new();
+
+        for param in func.params.iter() {
+            if args.iter().any(|(name, _)| *name == param.name) {
+                return Err(CompileError::new(format!("parameter {} is already defined", param.name), param.span));
+            }
+
+            args.push((param.name.clone(), match param.param_type {
+                AstType::Boolean => ArgumentType::Boolean,
+                _ => ArgumentType::StringNumber
+            }));
+        }
+
+        // main is the entry point, it runs when the green flag is clicked
+        if func.id == "main" && !args.is_empty() {
+            return Err(CompileError::new("main can't have parameters".to_string(), func.span));
+        }
+
+        self.procedures.insert(func.id.clone(), Procedure {
+            prototype: Prototype::new(&func.id, args, func.warp),
+            returns: returns_value(&func.block)
+        });
+
+        Ok(())
+    }
+
+    fn func(&mut self, func: &AstFunc) -> Result<(), CompileError> {
+        let proc = match self.procedures.get(&func.id) {
+            Some(v) => v,
+            None => return Ok(()) // the declaration failed
+        };
+
+        let prototype = proc.prototype.clone();
+        let returns = proc.returns;
+
+        let mut script = definition(&prototype);
+        self.frame = self.needs_frame(&func.block).then_some(FramePointer::Argument);
+
+        self.params = prototype.args.clone();
+        let res = self.body(&func.block, returns);
+        self.params.clear();
+        let body = res?;
+
+        if self.frame.is_some() {
+            // the body goes in a second custom block, which gets the frame as an argument
+            let mut args: Vec<(String, ArgumentType)> = prototype.args.iter()
+                .map(|arg| (arg.name.clone(), arg.arg_type))
+                .collect();
+            args.push((FRAME_ARG.to_string(), ArgumentType::StringNumber));
+
+            let inner = Prototype::new(&format!("{}.body", func.id), args, prototype.warp);
+            let mut inner_script = definition(&inner);
+            inner_script.append(body);
+            self.obj.scripts.push(inner_script);
+
+            // pass on the arguments of the call along with the new frame
+            let mut args: Vec<ArgumentInput> = prototype.args.iter().map(|arg| match arg.arg_type {
+                ArgumentType::StringNumber => ArgumentInput::StringNumber(reporter(Opcode::ArgumentReporterStringNumber(arg.name.clone()))),
+                ArgumentType::Boolean => ArgumentInput::Boolean(ReporterInput {
+                    block: Some(Box::new(Block::new(Opcode::ArgumentReporterBoolean(arg.name.clone()))))
+                })
+            }).collect();
+
+            let ret = self.hidden_var("return");
+            args.push(ArgumentInput::StringNumber(variable(ret)));
+
+            self.alloc_frame(self.max_slots, &mut script);
+            script.push(Block::new(Opcode::ProceduresCall(inner, args)));
+        } else {
+            script.append(body);
+        }
+
+        self.obj.scripts.push(script);
+
+        if func.id == "main" {
+            let mut script = Script::new();
+            script.push(Block::new(Opcode::WhenGreenFlagClicked()));
+            script.push(Block::new(Opcode::ProceduresCall(prototype, Vec::new())));
+            self.obj.scripts.push(script);
+        }
+
+        Ok(())
+    }
+
+    // each event handler is its own script under a hat block
+    fn event(&mut self, event: &AstEvent) -> Result<(), CompileError> {
+        let hat = match &event.kind {
+            AstEventKind::Start => Opcode::WhenGreenFlagClicked(),
+            AstEventKind::Clicked if self.obj.is_stage => Opcode::WhenStageClicked(),
+            AstEventKind::Clicked => Opcode::WhenThisSpriteClicked(),
+
+            AstEventKind::Clone if self.obj.is_stage => {
+                return Err(CompileError::new("the stage can't be cloned".to_string(), event.span));
+            },
+
+            AstEventKind::Clone => Opcode::StartAsClone(),
+
+            AstEventKind::Key(name) => match KeyOption::from_name(name) {
+                Some(key) => Opcode::WhenKeyPressed(key),
+                None => return Err(CompileError::new(format!("unknown key \"{}\"", name), event.span)
+                    .with_help("expected a letter, a digit, space, any, up_arrow, down_arrow, left_arrow or right_arrow"))
+            },
+
+            AstEventKind::Broadcast(name) => {
+                self.obj.add_broadcast(name);
+                Opcode::WhenBroadcastReceived(name.clone())
+            },
+
+            AstEventKind::Backdrop(name, span) => {
+                self.backdrops.push((name.clone(), *span));
+                Opcode::WhenBackdropSwitchesTo(name.clone())
+            },
+            // there is nowhere to put blocks that have to run before the hat
+            AstEventKind::Loudness(value) | AstEventKind::Timer(value) if self.needs_temp(value) => {
+                return Err(CompileError::new("functions can't be called in an event condition".to_string(), event.span));
+            },
+
+            AstEventKind::Loudness(value) => Opcode::WhenGreaterThan(SensingType::Loudness, self.value(value)?),
+            AstEventKind::Timer(value) => Opcode::WhenGreaterThan(SensingType::Timer, self.value(value)?)
+        };
+
+        let mut script = Script::new();
+        script.push(Block::new(hat));
+
+        if !self.needs_frame(&event.block) {
+            self.frame = None;
+            script.append(self.body(&event.block, false)?);
+            self.obj.scripts.push(script);
+            return Ok(());
+        }
+
+        // clones have their own copy of the variable, so a handler can run in several at once
+        self.handler_frames += 1;
+        let var = self.hidden_var(&format!("frame{}", self.handler_frames));
+        self.frame = Some(FramePointer::Variable(var.clone()));
+        let body = self.body(&event.block, false)?;
+
+        let ret = self.hidden_var("return");
+        self.alloc_frame(self.max_slots, &mut script);
+        script.push(Block::new(Opcode::SetVariableTo(var, variable(ret))));
+        script.append(body);
+
+        self.obj.scripts.push(script);
+        Ok(())
+    }
+
+    // lower the body of a function or event handler, freeing its frame at the end if it has one
+    fn body(&mut self, block: &AstBlock, returns: bool) -> Result<Script, CompileError> {
+        self.next_slot = 0;
+        self.max_slots = 0;
+        self.loops.clear();
+
+        let mut body = Script::new();
+        self.block(block, &mut body)?;
+
+        // a return at the end has already freed the frame, and nothing can come after it
+        if matches!(block.statements.last(), Some(AstStatement { kind: AstStatementKind::Return(_), .. })) {
+            return Ok(body);
+        }
+
+        // falling off the end of a function returns null
+        if returns {
+            let var = self.hidden_var("return");
+            body.push(Block::new(Opcode::SetVariableTo(var, UserInput::new(Value::String(String::new()), None))));
+        }
+
+        if self.frame.is_some() {
+            self.free_frame(&mut body);
+        }
+
+        Ok(body)
+    }
+
+    fn block(&mut self, block: &AstBlock, script: &mut Script) -> Result<(), CompileError> {
+        let first_slot = self.next_slot;
+        self.scopes.push(Vec::new());
+
+        let mut res = Ok(());
+
+        // statements after a break or continue go in a new part, which only runs if it wasn't taken
+        let mut parts = vec![Script::new()];
+
+        for (i, statement) in block.statements.iter().enumerate() {
+            self.temps = 0;
+            res = self.statement(statement, parts.last_mut().unwrap());
+
+            // don't let blocks from a failed statement leak into the next one
+            self.pre.clear();
+
+            if res.is_err() {
+                break;
+            }
+
+            // the script stops at a return, so nothing after it can run
+            if let (AstStatementKind::Return(_), Some(next)) = (&statement.kind, block.statements.get(i + 1)) {
+                res = Err(CompileError::new("unreachable code after return".to_string(), next.span)
+                    .with_help("remove it, or move the return to the end of the block"));
+                break;
+            }
+
+            if loops::jumps(statement) {
+                parts.push(Script::new());
+            }
+        }
+
+        while let Some(part) = parts.pop() {
+            match parts.last_mut() {
+                Some(outer) if !part.is_empty() => {
+                    outer.push(Block::new(Opcode::If(self.not_jumped(), ScriptInput { script: Some(part) })));
+                },
+                Some(_) => (),
+                None => script.append(part)
+            }
+        }
+
+        // locals of this block go out of scope
+        self.scopes.pop();
+        self.next_slot = first_slot;
+
+        res
+    }
+
+    // lower a block into a substack input
+    fn substack(&mut self, block: &AstBlock) -> Result<ScriptInput, CompileError> {
+        let mut script = Script::new();
+        self.block(block, &mut script)?;
+
+        Ok(ScriptInput {
+            script: if script.is_empty() { None } else { Some(script) }
+        })
+    }
+
+    fn statement(&mut self, statement: &AstStatement, script: &mut Script) -> Result<(), CompileError> {
+        match &statement.kind {
+            AstStatementKind::Call(call) => {
+                match self.call(call)? {
+                    (builtins::Kind::Statement, opcode) => self.emit(script, opcode),
+                    _ => return Err(CompileError::new(format!("result of {} is unused", call_name(call)), call.span)
+                        .with_help("use \"drop\" to discard the result"))
+                }
+            },
+
+            // expressions without side effects can be dropped entirely
+            AstStatementKind::Drop(AstExpression { kind: AstExpressionKind::Call(call), .. }) => {
+                if let (builtins::Kind::Statement, opcode) = self.call(call)? {
+                    self.emit(script, opcode);
+                }
+            },
+
+            // calls to functions in the expression still have to run
+            AstStatementKind::Drop(expr) => {
+                self.value(expr)?;
+                self.flush(script);
+            },
+
+            // "stop this script" returns from a custom block
+            AstStatementKind::Return(expr) => {
+                if let Some(expr) = expr {
+                    let value = self.value(expr)?;
+                    let var = self.hidden_var("return");
+                    self.emit(script, Opcode::SetVariableTo(var, value));
+                }
+
+                if self.frame.is_some() {
+                    self.free_frame(script);
+                }
+
+                self.emit(script, Opcode::Stop(StopOption::Myself));
+            },
+
+            // the local isn't in scope in its own initializer
+            AstStatementKind::Local(name, expr) => {
+                let value = match expr {
+                    Some(expr) => self.value(expr)?,
+                    None => UserInput::new(Value::String(String::new()), None)
+                };
+
+                let slot = self.declare_local(name);
+                let opcode = self.write_slot(slot, value);
+                self.emit(script, opcode);
+            },
+
+            AstStatementKind::Assign(AstExpression { kind: AstExpressionKind::Variable(name), .. }, expr) => match self.lookup(name) {
+                Some(Binding::Local(slot)) => {
+                    let value = self.value(expr)?;
+                    let opcode = self.write_slot(slot, value);
+                    self.emit(script, opcode);
+                },
+
+                Some(Binding::Param(_)) => {
+                    return Err(CompileError::new(format!("can't assign to parameter {}", name), statement.span)
+                        .with_help(&format!("declare a local copy with \"local {} = {}\"", name, name)));
+                },
+
+                None => return Err(CompileError::new(format!("unknown variable {}", name), statement.span)
+                    .with_help(&format!("declare it with \"local {}\"", name)))
+            },
+
+            AstStatementKind::Assign(AstExpression { kind: AstExpressionKind::Index(array, index), .. }, expr) => {
+                let (list, position) = self.index(array, index)?;
+                let value = self.value(expr)?;
+                self.emit(script, Opcode::ReplaceItemOfList(position, list, value));
+            },
+
+            AstStatementKind::Assign(target, _) => {
+                return Err(CompileError::new("can't assign to this expression".to_string(), target.span)
+                    .with_help("only variables and array items can be assigned to"));
+            },
+
+            AstStatementKind::Do(block) => {
+                self.block(block, script)?;
+            },
+
+            AstStatementKind::If(cond, block, else_block) => {
+                let cond = self.condition(cond)?;
+                self.flush(script);
+                let substack = self.substack(block)?;
+
+                match else_block {
+                    Some(else_block) => {
+                        let else_substack = self.substack(else_block)?;
+                        script.push(Block::new(Opcode::IfElse(cond, substack, else_substack)));
+                    },
+                    None => script.push(Block::new(Opcode::If(cond, substack)))
+                }
+            },
+
+            AstStatementKind::While(cond, block) => self.while_loop(cond, block, script)?,
+            AstStatementKind::Repeat(count, block) => self.repeat_loop(count, block, script)?,
+            AstStatementKind::RepeatUntil(block, cond) => self.repeat_until_loop(block, cond, script)?,
+
+            AstStatementKind::For(name, start, end, step, block) => {
+                self.for_loop(name, start, end, step.as_ref(), block, script)?;
+            },
+
+            AstStatementKind::Break | AstStatementKind::Continue => self.jump(statement, script)?
+        }
+
+        Ok(())
+    }
+
+    // push a block after the blocks which have to run before it
+    fn emit(&mut self, script: &mut Script, opcode: Opcode) {
+        self.flush(script);
+        script.push(Block::new(opcode));
+    }
+
+    fn flush(&mut self, script: &mut Script) {
+        for block in self.pre.drain(..) {
+            script.push(block);
+        }
+    }
+
+    // name of a variable or list used by the compiled code.
+    // data of the stage is global, so it is named differently to not clash with sprites
+    fn hidden_name(&self, name: &str) -> String {
+        if self.obj.is_stage {
+            format!("_stage_{}", name)
+        } else {
+            format!("_{}", name)
+        }
+    }
+
+    // get a variable used by the compiled code, creating it if needed
+    fn hidden_var(&mut self, name: &str) -> String {
+        let name = self.hidden_name(name);
+
+        if self.obj.variable_id(&name).is_none() {
+            self.obj.add_variable(Variable::new(&name, Value::String(String::new())));
+        }
+
+        name
+    }
+
+    fn procedure(&self, call: &AstCall) -> Option<&Procedure> {
+        match call.module {
+            Some(_) => None,
+            None => self.procedures.get(&call.name)
+        }
+    }
+
+    // lower a call to a function in this object into a procedures_call block
+    fn procedure_call(&mut self, call: &AstCall) -> Result<Option<Opcode>, CompileError> {
+        let prototype = match self.procedure(call) {
+            Some(proc) => proc.prototype.clone(),
+            None => return Ok(None)
+        };
+
+        if call.args.len() != prototype.args.len() {
+            return Err(CompileError::new(format!(
+                "{} expects {} argument(s), got {}", call.name, prototype.args.len(), call.args.len()
+            ), call.span));
+        }
+
+        let mut args = Vec::new();
+
+        for (arg, expr) in prototype.args.iter().zip(call.args.iter()) {
+            args.push(match arg.arg_type {
+                ArgumentType::StringNumber => ArgumentInput::StringNumber(self.value(expr)?),
+                ArgumentType::Boolean => ArgumentInput::Boolean(self.condition(expr)?)
+            });
+        }
+
+        Ok(Some(Opcode::ProceduresCall(prototype, args)))
+    }
+
+    // call a function before the current statement, and get its return value
+    fn procedure_result(&mut self, call: &AstCall) -> Result<UserInput, CompileError> {
+        if !self.procedure(call).is_some_and(|proc| proc.returns) {
+            return Err(CompileError::new(format!("{} does not return a value", call.name), call.span));
+        }
+
+        if let Some(opcode) = self.procedure_call(call)? {
+            self.pre.push(Block::new(opcode));
+        }
+
+        // copy the return value to the frame, since it is overwritten by the next call
+        let ret = self.hidden_var("return");
+        let slot = self.temp_slot();
+        let opcode = self.write_slot(slot, variable(ret));
+
+        self.pre.push(Block::new(opcode));
+        Ok(self.read_slot(slot))
+    }
+
+    fn call(&mut self, call: &AstCall) -> Result<(builtins::Kind, Opcode), CompileError> {
+        if let Some(opcode) = self.procedure_call(call)? {
+            return Ok((builtins::Kind::Statement, opcode));
+        }
+
+        match builtins::lower(self, call)? {
+            Some(v) => Ok(v),
+            None => Err(CompileError::new(format!("unknown function {}", call_name(call)), call.span))
+        }
+    }
+
+    /// Lowers an expression into a value slot
+    pub fn value(&mut self, expr: &AstExpression) -> Result<UserInput, CompileError> {
+        let opcode = match &expr.kind {
+            AstExpressionKind::Number(num) => return Ok(UserInput::new(Value::Number(*num), None)),
+            AstExpressionKind::String(string) => return Ok(UserInput::new(Value::String(string.clone()), None)),
+            AstExpressionKind::Boolean(b) => return Ok(UserInput::new(Value::String(b.to_string()), None)),
+            AstExpressionKind::Null => return Ok(UserInput::new(Value::String(String::new()), None)),
+
+            // fold negation of number literals
+            AstExpressionKind::Neg(v) => match v.kind {
+                AstExpressionKind::Number(num) => return Ok(UserInput::new(Value::Number(-num), None)),
+                _ => Opcode::Subtract(UserInput::new(Value::Number(0.0), None), self.value(v)?)
+            },
+
+            AstExpressionKind::Add(a, b) => Opcode::Add(self.value(a)?, self.value(b)?),
+            AstExpressionKind::Sub(a, b) => Opcode::Subtract(self.value(a)?, self.value(b)?),
+            AstExpressionKind::Mul(a, b) => Opcode::Multiply(self.value(a)?, self.value(b)?),
+            AstExpressionKind::Div(a, b) => Opcode::Divide(self.value(a)?, self.value(b)?),
+            AstExpressionKind::Mod(a, b) => Opcode::Mod(self.value(a)?, self.value(b)?),
+            AstExpressionKind::Pow(a, b) => return self.power(a, b),
+            AstExpressionKind::Join(a, b) => Opcode::Join(self.value(a)?, self.value(b)?),
+
+            // boolean blocks can be placed in value slots
+            AstExpressionKind::Not(_) | AstExpressionKind::And(..) | AstExpressionKind::Or(..) |
+            AstExpressionKind::Eq(..) | AstExpressionKind::Neq(..) | AstExpressionKind::Lt(..) |
+            AstExpressionKind::Gt(..) | AstExpressionKind::Le(..) | AstExpressionKind::Ge(..) => {
+                return Ok(UserInput::new(Value::String(String::new()), self.condition(expr)?.block));
+            },
+
+            AstExpressionKind::Variable(name) => match self.lookup(name) {
+                Some(Binding::Local(slot)) => return Ok(self.read_slot(slot)),
+                Some(Binding::Param(ArgumentType::StringNumber)) => Opcode::ArgumentReporterStringNumber(name.clone()),
+                Some(Binding::Param(ArgumentType::Boolean)) => Opcode::ArgumentReporterBoolean(name.clone()),
+                None => return Err(CompileError::new(format!("unknown variable {}", name), expr.span))
+            },
+
+            AstExpressionKind::Index(array, index) => {
+                let (list, position) = self.index(array, index)?;
+                Opcode::ItemOfList(position, list)
+            },
+
+            AstExpressionKind::New(size_expr) => {
+                let size = self.value(size_expr)?;
+
+                if let (Value::Number(num), None) = (&size.value, &size.block) {
+                    if *num < 1.0 {
+                        return Err(CompileError::new(format!("can't allocate an array of size {}", num), size_expr.span)
+                            .with_help("an array needs at least one item"));
+                    }
+                }
+
+                return Ok(self.alloc(size));
+            },
+
+            AstExpressionKind::Array(items) if items.is_empty() => {
+                return Err(CompileError::new("can't allocate an empty array".to_string(), expr.span)
+                    .with_help("an array needs at least one item"));
+            },
+
+            AstExpressionKind::Array(items) => return self.array(items),
+
+            AstExpressionKind::Call(call) if self.procedure(call).is_some() => return self.procedure_result(call),
+
+            AstExpressionKind::Call(call) => match self.call(call)? {
+                (builtins::Kind::Statement, _) => return Err(CompileError::new(format!("{} does not return a value", call_name(call)), call.span)),
+                (_, opcode) => opcode
+            }
+        };
+
+        Ok(reporter(opcode))
+    }
+
+    /// Lowers an expression into a boolean slot
+    pub fn condition(&mut self, expr: &AstExpression) -> Result<ReporterInput, CompileError> {
+        let opcode = match &expr.kind {
+            // an empty boolean slot is false, so true is "not <>"
+            AstExpressionKind::Boolean(true) => Opcode::Not(ReporterInput { block: None }),
+            AstExpressionKind::Boolean(false) => return Ok(ReporterInput { block: None }),
+
+            AstExpressionKind::Not(v) => Opcode::Not(self.condition(v)?),
+            AstExpressionKind::And(a, b) | AstExpressionKind::Or(a, b) if self.needs_temp(b) => {
+                return self.short_circuit(a, b, matches!(expr.kind, AstExpressionKind::And(..)));
+            },
+
+            AstExpressionKind::And(a, b) => Opcode::And(self.condition(a)?, self.condition(b)?),
+            AstExpressionKind::Or(a, b) => Opcode::Or(self.condition(a)?, self.condition(b)?),
+
+            AstExpressionKind::Eq(a, b) => Opcode::Equals(self.value(a)?, self.value(b)?),
+            AstExpressionKind::Lt(a, b) => Opcode::Lt(self.value(a)?, self.value(b)?),
+            AstExpressionKind::Gt(a, b) => Opcode::Gt(self.value(a)?, self.value(b)?),
+
+            // the other comparisons are the opposite of one that scratch has
+            AstExpressionKind::Neq(a, b) => Opcode::Not(self.comparison(Opcode::Equals, a, b)?),
+            AstExpressionKind::Le(a, b) => Opcode::Not(self.comparison(Opcode::Gt, a, b)?),
+            AstExpressionKind::Ge(a, b) => Opcode::Not(self.comparison(Opcode::Lt, a, b)?),
+
+            // boolean parameters can be used directly
+            AstExpressionKind::Variable(name) if matches!(self.lookup(name), Some(Binding::Param(ArgumentType::Boolean))) => {
+                Opcode::ArgumentReporterBoolean(name.clone())
+            },
+
+            AstExpressionKind::Call(call) if self.procedure(call).is_none() => match self.call(call)? {
+                (builtins::Kind::Predicate, opcode) => opcode,
+                (builtins::Kind::Statement, _) => return Err(CompileError::new(format!("{} does not return a value", call_name(call)), call.span)),
+                (builtins::Kind::Reporter, opcode) => {
+                    let value = UserInput::new(Value::String(String::new()), Some(Box::new(Block::new(opcode))));
+                    Opcode::Equals(value, UserInput::new(Value::String("true".to_string()), None))
+                }
+            },
+
+            // any other value is only true if it is the string "true"
+            _ => Opcode::Equals(self.value(expr)?, UserInput::new(Value::String("true".to_string()), None))
+        };
+
+        Ok(ReporterInput {
+            block: Some(Box::new(Block::new(opcode)))
+        })
+    }
+
+    fn comparison(&mut self, op: fn(UserInput, UserInput) -> Opcode, a: &AstExpression, b: &AstExpression) -> Result<ReporterInput, CompileError> {
+        let opcode = op(self.value(a)?, self.value(b)?);
+
+        Ok(ReporterInput {
+            block: Some(Box::new(Block::new(opcode)))
+        })
+    }
+
+    // the right side of `and` or `or` runs its calls before the statement, so when it has any
+    // they go in an if which only runs when the left side doesn't decide the result. the
+    // result is kept in a temporary
+    fn short_circuit(&mut self, a: &AstExpression, b: &AstExpression, and: bool) -> Result<ReporterInput, CompileError> {
+        let slot = self.temp_slot();
+        let first = self.condition(a)?;
+        let opcode = self.write_slot(slot, UserInput::new(Value::String(String::new()), first.block));
+        self.pre.push(Block::new(opcode));
+
+        let outer = std::mem::take(&mut self.pre);
+        let second = self.condition(b);
+        let mut inner = std::mem::replace(&mut self.pre, outer);
+
+        let opcode = self.write_slot(slot, UserInput::new(Value::String(String::new()), second?.block));
+        inner.push(Block::new(opcode));
+
+        let mut script = Script::new();
+
+        for block in inner {
+            script.push(block);
+        }
+
+        let result = |compiler: &mut Self| {
+            let value = compiler.read_slot(slot);
+            let opcode = Opcode::Equals(value, UserInput::new(Value::String("true".to_string()), None));
+            ReporterInput { block: Some(Box::new(Block::new(opcode))) }
+        };
+
+        let decided = match and {
+            true => result(self),
+            false => ReporterInput { block: Some(Box::new(Block::new(Opcode::Not(result(self))))) }
+        };
+
+        self.pre.push(Block::new(Opcode::If(decided, ScriptInput { script: Some(script) })));
+        Ok(result(self))
+    }
+
+    // scratch has no exponent block, so a ^ b is e ^ (b * ln(abs(a))), negated when a is
+    // negative and b is odd. small whole powers of simple values are multiplied out instead,
+    // which keeps them exact
+    fn power(&mut self, a: &AstExpression, b: &AstExpression) -> Result<UserInput, CompileError> {
+        let number = |num: f64| UserInput::new(Value::Number(num), None);
+
+        if let (Some(x), Some(y)) = (literal(a), literal(b)) {
+            return Ok(number(x.powf(y)));
+        }
+
+        if let AstExpressionKind::Number(n) = b.kind {
+            if (1.0..=4.0).contains(&n) && n.fract() == 0.0 && simple(a) {
+                let mut product = self.value(a)?;
+
+                for _ in 1..(n as usize) {
+                    product = reporter(Opcode::Multiply(product, self.value(a)?));
+                }
+
+                return Ok(product);
+            }
+        }
+
+        let base = self.reuse(a)?;
+        let exponent = self.reuse(b)?;
+
+        let abs = reporter(Opcode::MathOp(MathOp::Abs, self.reused(&base)?));
+        let ln = reporter(Opcode::MathOp(MathOp::Ln, abs));
+        let product = reporter(Opcode::Multiply(self.reused(&exponent)?, ln));
+        let magnitude = reporter(Opcode::MathOp(MathOp::NaturalExponent, product));
+
+        // the sign can't change for a positive base or an even exponent
+        let positive = literal(a).is_some_and(|x| x >= 0.0);
+        let even = literal(b).is_some_and(|y| y.rem_euclid(2.0) == 0.0);
+
+        if positive || even {
+            return Ok(magnitude);
+        }
+
+        // 1 - 2 * (a < 0) * (b mod 2) is -1 for a negative base and an odd exponent
+        let negative = Opcode::Lt(self.reused(&base)?, number(0.0));
+        let negative = UserInput::new(Value::String(String::new()), Some(Box::new(Block::new(negative))));
+        let odd = reporter(Opcode::Mod(self.reused(&exponent)?, number(2.0)));
+        let twice = reporter(Opcode::Multiply(number(2.0), reporter(Opcode::Multiply(negative, odd))));
+        let sign = reporter(Opcode::Subtract(number(1.0), twice));
+
+        Ok(reporter(Opcode::Multiply(magnitude, sign)))
+    }
+}
+
+// the value of a number literal, which may be negated
+fn literal(expr: &AstExpression) -> Option<f64> {
+    match &expr.kind {
+        AstExpressionKind::Number(num) => Some(*num),
+        AstExpressionKind::Neg(v) => literal(v).map(|num| -num),
+        _ => None
+    }
+}
+
+// a script which starts with the definition of a custom block
+fn definition(prototype: &Prototype) -> Script {
+    let mut script = Script::new();
+    script.push(Block::new(Opcode::ProceduresDefinition(Box::new(Block::new(
+        Opcode::ProceduresPrototype(prototype.clone())
+    )))));
+
+    script
+}
+
+fn call_name(call: &AstCall) -> String {
+    match &call.module {
+        Some(module) => format!("{}.{}", module, call.name),
+        None => call.name.clone()
+    }
+}
+
+// does a return statement in the block give a value?
+fn returns_value(block: &AstBlock) -> bool {
+    block.statements.iter().any(|statement| match &statement.kind {
+        AstStatementKind::Return(expr) => expr.is_some(),
+        AstStatementKind::If(_, block, Some(else_block)) => returns_value(block) || returns_value(else_block),
+        AstStatementKind::Do(block) | AstStatementKind::If(_, block, None) | AstStatementKind::While(_, block) |
+        AstStatementKind::Repeat(_, block) | AstStatementKind::RepeatUntil(block, _) |
+        AstStatementKind::For(.., block) => returns_value(block),
+        _ => false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use json::JsonValue;
+
+    fn parse(source: &str) -> AstProgram {
+        match crate::parse::run(source) {
+            Ok(program) => program,
+            Err(errors) => panic!("{}", errors[0].msg)
+        }
+    }
+
+    fn compile_with(source: &str, options: Options) -> Project {
+        let program = parse(source);
+        let mut project = Project::new();
+        let mut compiler = ProjectCompiler::new(&mut project, options);
+
+        if let Err(errors) = compiler.file(&program, "Sprite1", Path::new(".")) {
+            panic!("{}", errors[0].msg);
+        }
+
+        compiler.finish();
+        project
+    }
+
+    fn compile(source: &str) -> Project {
+        compile_with(source, Options::default())
+    }
+
+    // the messages and lines of the errors in a source file which doesn't compile
+    fn errors(source: &str) -> Vec<(String, usize)> {
+        let program = parse(source);
+        let mut project = Project::new();
+        let mut compiler = ProjectCompiler::new(&mut project, Options::default());
+
+        match compiler.file(&program, "Sprite1", Path::new(".")) {
+            Ok(()) => panic!("expected a compile error"),
+            Err(errors) => errors.into_iter().map(|err| (err.msg, err.span.line)).collect()
+        }
+    }
+
+    // an opcode with the custom block it calls or defines or the name in its first field,
+    // followed by the values of its inputs which are literals
+    fn describe(blocks: &JsonValue, block: &JsonValue) -> String {
+        let mut res = block["opcode"].as_str().unwrap().to_string();
+        let prototype = &blocks[block["inputs"]["custom_block"][1].as_str().unwrap_or("")];
+
+        let name = block["mutation"]["proccode"].as_str()
+            .or(prototype["mutation"]["proccode"].as_str())
+            .or(block["fields"].entries().next().and_then(|(_, field)| field[0].as_str()));
+
+        if let Some(name) = name {
+            res = format!("{} {}", res, name);
+        }
+
+        for (_, input) in block["inputs"].entries() {
+            match input[1][1].as_str() {
+                Some("") if input[0] == 1 => res += " \"\"",
+                Some(value) if input[0] == 1 => res = format!("{} {}", res, value),
+                _ => ()
+            }
+        }
+
+        res
+    }
+
+    // the blocks of a stack, starting at a block. blocks in a substack come after
+    // the block they are in, indented by two spaces
+    fn stack(blocks: &JsonValue, first: &str, depth: usize, res: &mut Vec<String>) {
+        let mut uuid = Some(first);
+
+        while let Some(block) = uuid.map(|uuid| &blocks[uuid]) {
+            res.push(format!("{}{}", "  ".repeat(depth), describe(blocks, block)));
+
+            for input in ["SUBSTACK", "SUBSTACK2"] {
+                if let Some(first) = block["inputs"][input][1].as_str() {
+                    stack(blocks, first, depth + 1, res);
+                }
+            }
+
+            uuid = block["next"].as_str();
+        }
+    }
+
+    // the blocks of each script of an object
+    fn scripts(obj: &Object) -> Vec<Vec<String>> {
+        let mut blocks = JsonValue::new_object();
+        let mut res = Vec::new();
+
+        for script in obj.scripts.iter() {
+            let first = script.serialize(&mut blocks, obj, None).unwrap().clone();
+            let mut script = Vec::new();
+            stack(&blocks, &first, 0, &mut script);
+            res.push(script);
+        }
+
+        res
+    }
+
+    // the blocks of the first script of the first sprite which starts with a hat block
+    fn handler(source: &str) -> Vec<String> {
+        let project = compile(source);
+        scripts(&project.sprites[0].obj).into_iter().find(|script| script[0].starts_with("event_")).unwrap()
+    }
+
+    // an input written as an s-expression of the blocks in it. parameters are written as their name
+    fn expression(blocks: &JsonValue, input: &JsonValue) -> String {
+        let block = match input[1].as_str() {
+            Some(uuid) => &blocks[uuid],
+            None => return input[1][1].to_string()
+        };
+
+        let opcode = block["opcode"].as_str().unwrap();
+        let fields = block["fields"].entries().map(|(_, field)| field[0].to_string());
+
+        if opcode.starts_with("argument_reporter") {
+            return fields.collect();
+        }
+
+        let inputs = block["inputs"].entries().map(|(_, input)| expression(blocks, input));
+        let parts: Vec<String> = std::iter::once(opcode.to_string()).chain(fields).chain(inputs).collect();
+        format!("({})", parts.join(" "))
+    }
+
+    // the value the function f returns, and the blocks of its script
+    fn returned(source: &str) -> (String, Vec<String>) {
+        let project = compile(source);
+        let obj = &project.sprites[0].obj;
+        let mut blocks = JsonValue::new_object();
+
+        for script in obj.scripts.iter() {
+            let first = script.serialize(&mut blocks, obj, None).unwrap().clone();
+            let mut lines = Vec::new();
+            stack(&blocks, &first, 0, &mut lines);
+
+            if !["f", "f.body"].contains(&lines[0].split(' ').nth(1).unwrap_or("")) {
+                continue;
+            }
+
+            let mut uuid = blocks[first.as_str()]["next"].as_str().map(str::to_string);
+
+            while let Some(block) = uuid.map(|uuid| blocks[uuid.as_str()].clone()) {
+                if block["opcode"] == "data_setvariableto" {
+                    return (expression(&blocks, &block["inputs"]["VALUE"]), lines);
+                }
+
+                uuid = block["next"].as_str().map(str::to_string);
+            }
+        }
+
+        panic!("f doesn't return a value");
+    }
+
+    #[test]
+    fn handlers_have_their_own_frames() {
+        let project = compile(concat!(
+            "on start do\n\tfor i = 1, 3 do sprite.say(i) end\nend\n",
+            "on start do\n\tfor i = 1, 3 do sprite.say(i) end\nend\n"
+        ));
+
+        let obj = &project.sprites[0].obj;
+        let scripts = scripts(obj);
+        let handlers: Vec<&Vec<String>> = scripts.iter().filter(|script| script[0] == "event_whenflagclicked").collect();
+
+        assert_eq!(handlers.len(), 2);
+        assert_eq!(handlers[0][1..3], ["procedures_call heap.alloc %s 3", "data_setvariableto _frame1"]);
+        assert_eq!(handlers[1][1..3], ["procedures_call heap.alloc %s 3", "data_setvariableto _frame2"]);
+
+        for handler in handlers {
+            assert_eq!(handler.last().unwrap(), "procedures_call heap.free %s");
+        }
+
+        assert!(obj.list_id("_stack").is_none());
+    }
+
+    #[test]
+    fn functions_with_locals_get_their_frame_as_an_argument() {
+        let project = compile("func sum(n: num)\n\tlocal total = n + 1\n\treturn total\nend\n");
+        let scripts = scripts(&project.sprites[0].obj);
+
+        assert!(scripts.contains(&vec![
+            "procedures_definition sum %s".to_string(),
+            "procedures_call heap.alloc %s 1".to_string(),
+            "procedures_call sum.body %s %s".to_string()
+        ]));
+
+        assert!(scripts.contains(&vec![
+            "procedures_definition sum.body %s %s".to_string(),
+            "data_replaceitemoflist _heap".to_string(),
+            "data_setvariableto _return".to_string(),
+            "procedures_call heap.free %s".to_string(),
+            "control_stop this script".to_string()
+        ]));
+    }
+
+    #[test]
+    fn while_loop_keeps_its_flag_in_the_frame() {
+        let script = handler(concat!(
+            "on start do\n",
+            "\tlocal x = 0\n",
+            "\twhile x < 10 do\n",
+            "\t\tx = x + 1\n",
+            "\t\tif x == 2: continue\n",
+            "\t\tif x == 5: break\n",
+            "\t\tsprite.say(x)\n",
+            "\tend\n",
+            "end\n"
+        ));
+
+        // the flag is cleared before the loop and at the start of each iteration, and set to 2
+        // by continue and 1 by break
+        assert_eq!(script, [
+            "event_whenflagclicked",
+            "procedures_call heap.alloc %s 2",
+            "data_setvariableto _frame1",
+            "data_replaceitemoflist _heap 0",
+            "data_replaceitemoflist _heap 0",
+            "control_repeat_until",
+            "  data_replaceitemoflist _heap 0",
+            "  data_replaceitemoflist _heap",
+            "  control_if",
+            "    data_replaceitemoflist _heap 2",
+            "  control_if",
+            "    control_if",
+            "      data_replaceitemoflist _heap 1",
+            "    control_if",
+            "      looks_say",
+            "procedures_call heap.free %s"
+        ]);
+    }
+
+    #[test]
+    fn repeat_until_loop_keeps_its_flag_in_the_frame() {
+        let script = handler(concat!(
+            "on start do\n",
+            "\tlocal x = 0\n",
+            "\trepeat\n",
+            "\t\tx = x + 1\n",
+            "\t\tif x == 2: continue\n",
+            "\t\tif x == 5: break\n",
+            "\t\tsprite.say(x)\n",
+            "\tuntil x > 10\n",
+            "end\n"
+        ));
+
+        // the flag starts at 3 so that the condition isn't checked before the first iteration
+        assert_eq!(script, [
+            "event_whenflagclicked",
+            "procedures_call heap.alloc %s 2",
+            "data_setvariableto _frame1",
+            "data_replaceitemoflist _heap 0",
+            "data_replaceitemoflist _heap 3",
+            "control_repeat_until",
+            "  data_replaceitemoflist _heap 0",
+            "  data_replaceitemoflist _heap",
+            "  control_if",
+            "    data_replaceitemoflist _heap 2",
+            "  control_if",
+            "    control_if",
+            "      data_replaceitemoflist _heap 1",
+            "    control_if",
+            "      looks_say",
+            "procedures_call heap.free %s"
+        ]);
+    }
+
+    #[test]
+    fn for_loop_keeps_its_flag_in_the_frame() {
+        let script = handler(concat!(
+            "on start do\n",
+            "\tfor i = 1, 10 do\n",
+            "\t\tif i == 2: continue\n",
+            "\t\tif i == 5: break\n",
+            "\t\tsprite.say(i)\n",
+            "\tend\n",
+            "end\n"
+        ));
+
+        // the counter starts at 1, and its end and step are literals which aren't kept
+        assert_eq!(script, [
+            "event_whenflagclicked",
+            "procedures_call heap.alloc %s 4",
+            "data_setvariableto _frame1",
+            "data_replaceitemoflist _heap 1",
+            "data_replaceitemoflist _heap 0",
+            "control_repeat_until",
+            "  data_replaceitemoflist _heap 0",
+            "  control_if",
+            "    data_replaceitemoflist _heap 2",
+            "  control_if",
+            "    control_if",
+            "      data_replaceitemoflist _heap 1",
+            "    control_if",
+            "      looks_say",
+            "  data_replaceitemoflist _heap",
+            "procedures_call heap.free %s"
+        ]);
+    }
+
+    #[test]
+    fn unreachable_code_after_return() {
+        let errors = errors("func f(x: num)\n\tif x > 1 then\n\t\treturn x\n\t\tsprite.say(x)\n\tend\n\treturn 0\nend\n");
+        assert_eq!(errors, [("unreachable code after return".to_string(), 4)]);
+    }
+
+    #[test]
+    fn power_of_a_negative_base() {
+        assert_eq!(returned("func f()\n\treturn (-2) ^ 3\nend\n").0, "-8");
+        assert_eq!(returned("func f(a: num)\n\treturn a ^ 2\nend\n").0, "(operator_multiply a a)");
+        assert_eq!(returned("func f(a: num)\n\treturn a ^ 6\nend\n").0,
+            "(operator_mathop e ^ (operator_multiply 6 (operator_mathop ln (operator_mathop abs a))))");
+
+        assert_eq!(returned("func f(a: num, b: num)\n\treturn a ^ b\nend\n").0, concat!(
+            "(operator_multiply (operator_mathop e ^ (operator_multiply b (operator_mathop ln (operator_mathop abs a)))) ",
+            "(operator_subtract 1 (operator_multiply 2 (operator_multiply (operator_lt a 0) (operator_mod b 2)))))"
+        ));
+    }
+
+    #[test]
+    fn power_keeps_a_reporter_base_in_a_temporary() {
+        let (value, lines) = returned("func f(b: num)\n\treturn sprite.x() ^ b\nend\n");
+
+        assert_eq!(lines[..3], ["procedures_definition f.body %s %s", "data_replaceitemoflist _heap", "data_setvariableto _return"]);
+        assert_eq!(value.matches("motion_xposition").count(), 0);
+        assert_eq!(value.matches("(data_itemoflist _heap frame pointer)").count(), 2);
+    }
+
+    #[test]
+    fn and_with_a_call_on_the_right_short_circuits() {
+        let (value, lines) = returned(concat!(
+            "func g()\n\treturn true\nend\n",
+            "func f(a: bool)\n\treturn a and g()\nend\n"
+        ));
+
+        assert_eq!(lines[..7], [
+            "procedures_definition f.body %b %s",
+            "data_replaceitemoflist _heap",
+            "control_if",
+            "  procedures_call g",
+            "  data_replaceitemoflist _heap",
+            "  data_replaceitemoflist _heap",
+            "data_setvariableto _return"
+        ]);
+
+        assert_eq!(value, "(operator_equals (data_itemoflist _heap frame pointer) true)");
+    }
+
+    #[test]
+    fn or_with_a_call_on_the_right_short_circuits() {
+        let (_, lines) = returned(concat!(
+            "func g()\n\treturn true\nend\n",
+            "func f(a: bool)\n\treturn a or g()\nend\n"
+        ));
+
+        assert_eq!(lines[2..5], ["control_if", "  procedures_call g", "  data_replaceitemoflist _heap"]);
+    }
+
+    #[test]
+    fn arrays_are_allocated_and_freed_by_runtime_blocks() {
+        let project = compile("on start do\n\tlocal a = new(3)\n\ta[2] = 5\n\theap.free(a)\nend\n");
+        let scripts = scripts(&project.sprites[0].obj);
+
+        assert!(scripts.contains(&vec![
+            "event_whenflagclicked".to_string(),
+            "procedures_call heap.alloc %s 1".to_string(),
+            "data_setvariableto _frame1".to_string(),
+            "procedures_call heap.alloc %s 3".to_string(),
+            "data_replaceitemoflist _heap".to_string(),
+            "data_replaceitemoflist _heap".to_string(),
+            "data_replaceitemoflist _heap 5".to_string(),
+            "procedures_call heap.free %s".to_string(),
+            "procedures_call heap.free %s".to_string()
+        ]));
+
+        // freeing null or a free block does nothing
+        assert!(scripts.contains(&vec![
+            "procedures_definition heap.free %s".to_string(),
+            "control_if".to_string(),
+            "  data_replaceitemoflist _heap".to_string(),
+            "  data_replaceitemoflist _heap".to_string(),
+            "  data_setvariableto _heap_free".to_string()
+        ]));
+    }
+
+    #[test]
+    fn bounds_check_stops_the_script() {
+        let project = compile_with("on start do\n\tlocal a = new(3)\n\tsprite.say(a[4])\nend\n", Options { bounds_check: true });
+
+        let scripts = scripts(&project.sprites[0].obj);
+        let handler = scripts.iter().find(|script| script[0] == "event_whenflagclicked").unwrap();
+
+        assert_eq!(handler[6..12], [
+            "data_replaceitemoflist _heap",
+            "data_replaceitemoflist _heap 4",
+            "control_if",
+            "  looks_say",
+            "  control_stop this script",
+            "looks_say"
+        ]);
+
+        assert!(!scripts.iter().any(|script| script[0] == "procedures_definition heap.check %s %s"));
+    }
+
+    // the serialized blocks of an object
+    fn blocks(obj: &Object) -> JsonValue {
+        let mut blocks = JsonValue::new_object();
+
+        for script in obj.scripts.iter() {
+            script.serialize(&mut blocks, obj, None).unwrap();
+        }
+
+        blocks
+    }
+
+    // the first serialized block with an opcode
+    fn block<'b>(blocks: &'b JsonValue, opcode: &str) -> &'b JsonValue {
+        match blocks.entries().find(|(_, block)| block["opcode"] == opcode) {
+            Some((_, block)) => block,
+            None => panic!("no {} block", opcode)
+        }
+    }
+
+    // the broadcasts of a project, and the broadcast inputs of the blocks of its first sprite
+    fn broadcasts(project: &Project) -> (Vec<(String, String)>, Vec<JsonValue>) {
+        let obj = &project.sprites[0].obj;
+        let blocks = blocks(obj);
+
+        let inputs = blocks.entries()
+            .map(|(_, block)| block["inputs"]["BROADCAST_INPUT"].clone())
+            .filter(|input| !input.is_null())
+            .collect();
+
+        let broadcasts = obj.global_data.borrow().broadcasts.iter()
+            .map(|broadcast| (broadcast.id.clone(), broadcast.name.clone()))
+            .collect();
+
+        (broadcasts, inputs)
+    }
+
+    #[test]
+    fn broadcast_ids_are_made_from_the_name() {
+        let project = compile("on start do\n\tevent.broadcast(\"go\")\n\tevent.broadcast_and_wait(1)\nend\n");
+        let (broadcasts, inputs) = broadcasts(&project);
+
+        assert_eq!(broadcasts, [
+            ("broadcastMsgId-go".to_string(), "go".to_string()),
+            ("broadcastMsgId-1".to_string(), "1".to_string())
+        ]);
+        assert!(inputs.contains(&json::array![1, [11, "go", "broadcastMsgId-go"]]));
+        assert!(inputs.contains(&json::array![1, [11, "1", "broadcastMsgId-1"]]));
+    }
+
+    #[test]
+    fn broadcast_of_a_reporter_shows_a_received_message() {
+        let project = compile(concat!(
+            "func main()\n\tevent.broadcast(\"g\" .. \"o\")\nend\n",
+            "on broadcast \"go\" do\n\tsprite.say(1)\nend\n"
+        ));
+
+        let (broadcasts, inputs) = broadcasts(&project);
+
+        assert_eq!(broadcasts, [("broadcastMsgId-go".to_string(), "go".to_string())]);
+        assert_eq!(inputs[0][0], 3);
+        assert_eq!(inputs[0][2], json::array![11, "go", "broadcastMsgId-go"]);
+    }
+
+    #[test]
+    fn broadcast_of_a_reporter_adds_a_message_only_if_there_is_none() {
+        let project = compile("func main()\n\tevent.broadcast(\"g\" .. \"o\")\nend\n");
+        let (broadcasts, _) = broadcasts(&project);
+
+        assert_eq!(broadcasts, [("broadcastMsgId-message1".to_string(), "message1".to_string())]);
+    }
+
+    #[test]
+    fn clones() {
+        let project = compile("on clone do\n\tsprite.create_clone(\"_myself_\")\n\tsprite.delete_clone()\nend\n");
+        assert_eq!(scripts(&project.sprites[0].obj)[0], ["control_start_as_clone", "control_create_clone_of", "control_delete_this_clone"]);
+
+        let errors = errors("stage {\n\tfunc main()\n\t\tsprite.create_clone(\"_myself_\")\n\tend\n}\n");
+        assert_eq!(errors, [("the stage can't be cloned".to_string(), 3)]);
+    }
+
+    #[test]
+    fn sprite_and_stage_declarations() {
+        let project = compile(concat!(
+            "sprite Cat {\n\tname = \"Sprite2\"\n\tx = 10, y = -20\n\tsize = 50\n\tdirection = 45\n",
+            "\tvisible = false\n\tdraggable = true\n\trotation_style = \"left-right\"\n",
+            "\tfunc main()\n\t\tsprite.move(1)\n\tend\n}\n",
+            "stage {\n\ttempo = 90\n}\n"
+        ));
+
+        let sprite = &project.sprites[0];
+        assert_eq!(sprite.obj.name, "Sprite2");
+        assert_eq!((sprite.x, sprite.y, sprite.size, sprite.dir), (10.0, -20.0, 0.5, 45.0));
+        assert!(!sprite.visible && sprite.draggable);
+        assert!(matches!(sprite.rotation_style, RotationStyle::LeftRight));
+        assert_eq!(project.tempo, 90.0);
+
+        // a sprite without costumes gets a blank one
+        assert_eq!(sprite.obj.costumes.len(), 1);
+        assert_eq!(scripts(&sprite.obj), [
+            ["procedures_definition main", "motion_movesteps 1"],
+            ["event_whenflagclicked", "procedures_call main"]
+        ]);
+    }
+
+    #[test]
+    fn declaration_errors() {
+        let errors = errors(concat!(
+            "sprite Cat {\n\tcolour = 1\n\trotation_style = \"sideways\"\n}\n",
+            "stage {\n\ttempo = 5\n}\n"
+        ));
+
+        assert_eq!(errors, [
+            ("unknown sprite property \"colour\"".to_string(), 2),
+            ("unknown rotation style \"sideways\"".to_string(), 3),
+            ("tempo of 5 is out of range".to_string(), 6)
+        ]);
+    }
+
+    #[test]
+    fn event_handlers_are_hat_blocks() {
+        let project = compile(concat!(
+            "on start do sprite.say(1) end\n",
+            "on clicked do sprite.say(2) end\n",
+            "on key space do sprite.say(3) end\n",
+            "on broadcast \"go\" do sprite.say(4) end\n",
+            "on backdrop \"backdrop1\" do sprite.say(5) end\n",
+            "on timer > 5 do sprite.say(6) end\n"
+        ));
+
+        let obj = &project.sprites[0].obj;
+        let hats: Vec<String> = scripts(obj).into_iter().map(|script| script[0].clone()).collect();
+        assert_eq!(hats, [
+            "event_whenflagclicked",
+            "event_whenthisspriteclicked",
+            "event_whenkeypressed space",
+            "event_whenbroadcastreceived go",
+            "event_whenbackdropswitchesto backdrop1",
+            "event_whengreaterthan TIMER 5"
+        ]);
+
+        let blocks = blocks(obj);
+        let received = block(&blocks, "event_whenbroadcastreceived");
+        assert_eq!(received["fields"]["BROADCAST_OPTION"], json::array!["go", "broadcastMsgId-go"]);
+        assert_eq!(received["topLevel"], true);
+    }
+
+    #[test]
+    fn event_handler_errors() {
+        let errors = errors(concat!(
+            "on key f1 do sprite.say(1) end\n",
+            "stage {\n\ton clone do sprite.say(2) end\n}\n"
+        ));
+
+        assert_eq!(errors, [
+            ("unknown key \"f1\"".to_string(), 1),
+            ("the stage can't be cloned".to_string(), 3)
+        ]);
+    }
+    #[test]
+    fn functions_are_custom_blocks() {
+        let project = compile(concat!(
+            "warp func add(a: num, b: bool)\n\tif b then return a + 1 end\n\treturn a\nend\n",
+            "on start do\n\tsprite.say(add(2, true))\nend\n"
+        ));
+
+        let obj = &project.sprites[0].obj;
+        assert!(scripts(obj).contains(&vec![
+            "procedures_definition add %s %b".to_string(),
+            "control_if".to_string(),
+            "  data_setvariableto _return".to_string(),
+            "  control_stop this script".to_string(),
+            "data_setvariableto _return".to_string(),
+            "control_stop this script".to_string()
+        ]));
+
+        let blocks = blocks(obj);
+
+        let prototype = &block(&blocks, "procedures_prototype")["mutation"];
+        assert_eq!(prototype["proccode"], "add %s %b");
+        assert_eq!(prototype["warp"], "true");
+        assert_eq!(prototype["argumentnames"], "[\"a\",\"b\"]");
+        assert_eq!(prototype["argumentdefaults"], "[\"\",\"false\"]");
+
+        let (_, call) = blocks.entries()
+            .find(|(_, block)| block["opcode"] == "procedures_call" && block["mutation"]["proccode"] == "add %s %b")
+            .unwrap();
+        let ids = json::parse(call["mutation"]["argumentids"].as_str().unwrap()).unwrap();
+        assert_eq!(call["inputs"][ids[0].as_str().unwrap()], json::array![1, [10, "2"]]);
+        assert_eq!(call["inputs"][ids[1].as_str().unwrap()][0], 2);
+    }
+
+    #[test]
+    fn function_errors() {
+        let errors = errors(concat!(
+            "func f(a: num, a: num)\nend\n",
+            "func main(x: num)\nend\n",
+            "func g()\n\tsprite.move(1)\nend\n",
+            "func h()\n\tsprite.say(g())\nend\n"
+        ));
+
+        assert_eq!(errors, [
+            ("parameter a is already defined".to_string(), 1),
+            ("main can't have parameters".to_string(), 3),
+            ("g does not return a value".to_string(), 9)
+        ]);
+    }
+
+    #[test]
+    fn locals_are_scoped_to_their_block() {
+        let source = concat!(
+            "func f()\n\tlocal x = 1\n",
+            "\tif x > 0 then\n\t\tlocal y = 2\n\t\tx = y\n\tend\n",
+            "\tif x > 1 then\n\t\tlocal z = 3\n\t\tx = z\n\tend\n",
+            "\treturn x\nend\n"
+        );
+
+        let (value, _) = returned(source);
+        assert_eq!(value, "(data_itemoflist _heap frame pointer)");
+
+        // y and z are never in scope at the same time, so they share a slot
+        let project = compile(source);
+        assert!(scripts(&project.sprites[0].obj).contains(&vec![
+            "procedures_definition f".to_string(),
+            "procedures_call heap.alloc %s 2".to_string(),
+            "procedures_call f.body %s".to_string()
+        ]));
+
+        let errors = errors("func g()\n\tif true then\n\t\tlocal y = 2\n\tend\n\ty = 3\nend\n");
+        assert_eq!(errors, [("unknown variable y".to_string(), 5)]);
+    }
+
+    #[test]
+    fn operators() {
+        let value = |expr: &str| returned(&format!("func f(a: num, b: num, p: bool, q: bool)\n\treturn {}\nend\n", expr)).0;
+
+        assert_eq!(value("a + b * 2 - -b"), "(operator_subtract (operator_add a (operator_multiply b 2)) (operator_subtract 0 b))");
+        assert_eq!(value("a / b % 3"), "(operator_mod (operator_divide a b) 3)");
+        assert_eq!(value("a .. b + 1"), "(operator_join a (operator_add b 1))");
+        assert_eq!(value("a == b"), "(operator_equals a b)");
+        assert_eq!(value("a != b"), "(operator_not (operator_equals a b))");
+        assert_eq!(value("a <= b"), "(operator_not (operator_gt a b))");
+        assert_eq!(value("a >= b"), "(operator_not (operator_lt a b))");
+        assert_eq!(value("p or q and not p"), "(operator_or p (operator_and q (operator_not p)))");
+        assert_eq!(value("a < b == p"), "(operator_equals (operator_lt a b) p)");
+    }
+
+    #[test]
+    fn repeat_loops() {
+        // loops without break or continue are the blocks scratch has
+        let script = handler("on start do\n\trepeat 3 do sprite.say(1) end\n\twhile true do sprite.say(2) end\nend\n");
+        assert_eq!(script, [
+            "event_whenflagclicked",
+            "control_repeat 3",
+            "  looks_say 1",
+            "control_repeat_until",
+            "  looks_say 2"
+        ]);
+
+        // with a break, the count is kept in the frame next to the flag
+        let script = handler("on start do\n\trepeat 3 do\n\t\tsprite.say(1)\n\t\tbreak\n\tend\nend\n");
+        assert_eq!(script, [
+            "event_whenflagclicked",
+            "procedures_call heap.alloc %s 2",
+            "data_setvariableto _frame1",
+            "data_replaceitemoflist _heap 3",
+            "data_replaceitemoflist _heap 0",
+            "control_repeat_until",
+            "  data_replaceitemoflist _heap 0",
+            "  looks_say 1",
+            "  data_replaceitemoflist _heap 1",
+            "  data_replaceitemoflist _heap",
+            "procedures_call heap.free %s"
+        ]);
+    }
+
+    #[test]
+    fn if_elseif_else_chains() {
+        let script = handler(concat!(
+            "on start do\n",
+            "\tif sensing.mouse_down() then\n\t\tsprite.say(1)\n",
+            "\telseif sensing.key_pressed(\"a\") then\n\t\tsprite.say(2)\n",
+            "\telse\n\t\tsprite.say(3)\n\tend\n",
+            "\tif sensing.mouse_down(): sprite.say(4)\n",
+            "end\n"
+        ));
+
+        // an elseif is an if else in the else branch of the one before
+        assert_eq!(script, [
+            "event_whenflagclicked",
+            "control_if_else",
+            "  looks_say 1",
+            "  control_if_else",
+            "    looks_say 2",
+            "    looks_say 3",
+            "control_if",
+            "  looks_say 4"
+        ]);
+
+        let project = compile("on start do\n\tif sensing.mouse_down() then sprite.say(1) else sprite.say(2) end\nend\n");
+        let blocks = blocks(&project.sprites[0].obj);
+        let if_else = block(&blocks, "control_if_else");
+
+        assert_eq!(if_else["inputs"]["CONDITION"][0], 2);
+        assert_eq!(blocks[if_else["inputs"]["CONDITION"][1].as_str().unwrap()]["opcode"], "sensing_mousedown");
+        assert_eq!(blocks[if_else["inputs"]["SUBSTACK2"][1].as_str().unwrap()]["opcode"], "looks_say");
+    }
+
+    #[test]
+    fn data_blocks_refer_to_variables_and_lists_by_id() {
+        let project = compile("on start do\n\tlocal x = 1\n\tsprite.say(x)\nend\n");
+        let obj = &project.sprites[0].obj;
+
+        // the handler is added after the runtime blocks it uses
+        let mut blocks = JsonValue::new_object();
+        obj.scripts.last().unwrap().serialize(&mut blocks, obj, None).unwrap();
+
+        let heap = json::array!["_heap", obj.list_id("_heap").unwrap()];
+        let frame = json::array!["_frame1", obj.variable_id("_frame1").unwrap()];
+
+        assert_eq!(block(&blocks, "data_setvariableto")["fields"]["VARIABLE"], frame);
+        assert_eq!(block(&blocks, "data_replaceitemoflist")["fields"]["LIST"], heap);
+
+        // the variable in the index of the item is written into the input
+        let item = block(&blocks, "data_itemoflist");
+        assert_eq!(item["fields"]["LIST"], heap);
+        let index = json::array![3, [12, "_frame1", obj.variable_id("_frame1").unwrap()], [7, ""]];
+        assert_eq!(item["inputs"]["INDEX"], index);
+    }
+
+    // save a project, and read its project.json back
+    fn saved(project: &mut Project, name: &str) -> JsonValue {
+        let path = std::env::temp_dir().join(format!("solar-{}-{}.sb3", name, std::process::id()));
+        project.save(path.to_str().unwrap()).unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let json = std::io::read_to_string(zip.by_name("project.json").unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        json::parse(&json).unwrap()
+    }
+
+    #[test]
+    fn lists_are_saved_on_the_stage() {
+        let mut project = compile("on start do\n\tlocal a = [1, 2]\nend\n");
+        project.data.borrow_mut().lists.iter_mut().for_each(|list| list.visible = true);
+
+        let json = saved(&mut project, "lists");
+        let stage = &json["targets"][0];
+        let (id, heap) = stage["lists"].entries().find(|(_, list)| list[0] == "_heap").unwrap();
+
+        assert_eq!(stage["isStage"], true);
+        assert_eq!(*heap, json::array!["_heap", []]);
+        assert_eq!(json["targets"][1]["lists"], json::object! {});
+
+        let monitor = json["monitors"].members().find(|monitor| monitor["opcode"] == "data_listcontents").unwrap();
+        assert_eq!(monitor["id"], id);
+        assert_eq!(monitor["params"]["LIST"], "_heap");
+        assert_eq!(monitor["spriteName"], JsonValue::Null);
+        assert_eq!(monitor["visible"], true);
+    }
+
+    #[test]
+    fn broadcasts_are_saved_on_the_stage() {
+        let mut project = compile("on broadcast \"go\" do\n\tevent.broadcast_and_wait(\"stop\")\nend\n");
+        let json = saved(&mut project, "broadcasts");
+
+        assert_eq!(json["targets"][0]["broadcasts"], json::object! {
+            "broadcastMsgId-go": "go",
+            "broadcastMsgId-stop": "stop"
+        });
+
+        let blocks = &json["targets"][1]["blocks"];
+        let wait = block(blocks, "event_broadcastandwait");
+        assert_eq!(wait["inputs"]["BROADCAST_INPUT"], json::array![1, [11, "stop", "broadcastMsgId-stop"]]);
+    }
+
+    #[test]
+    fn sound_blocks() {
+        // the file is only read when the project is saved
+        let path = std::env::temp_dir().join(format!("solar-meow-{}.wav", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+
+        let project = compile(&format!(concat!(
+            "sprite Cat {{\n\tsounds = {{ \"meow\" = \"{}\" }}\n",
+            "\ton start do\n\t\tsprite.play_sound_until_done(\"meow\")\n\t\tsprite.play_sound(1 + 1)\n",
+            "\t\tsprite.set_sound_effect(\"pitch\", 10)\n\t\tsprite.change_volume(-10)\n\tend\n}}\n"
+        ), path.display()));
+
+        std::fs::remove_file(&path).unwrap();
+
+        let obj = &project.sprites[0].obj;
+        assert_eq!(scripts(obj)[0], [
+            "event_whenflagclicked",
+            "sound_playuntildone",
+            "sound_play",
+            "sound_seteffectto PITCH 10",
+            "sound_changevolumeby -10"
+        ]);
+
+        let blocks = blocks(obj);
+        let menu = &blocks[block(&blocks, "sound_playuntildone")["inputs"]["SOUND_MENU"][1].as_str().unwrap()];
+        assert_eq!(menu["opcode"], "sound_sounds_menu");
+        assert_eq!(menu["fields"]["SOUND_MENU"], json::array!["meow", null]);
+
+        // a reporter covers the menu, which shows the first sound
+        let play = &block(&blocks, "sound_play")["inputs"]["SOUND_MENU"];
+        assert_eq!(play[0], 3);
+        assert_eq!(blocks[play[2].as_str().unwrap()]["fields"]["SOUND_MENU"], json::array!["meow", null]);
+    }
+
+    #[test]
+    fn sound_errors() {
+        let errors = errors("on start do\n\tsprite.play_sound(\"meow\")\nend\non start do\n\tsprite.set_sound_effect(\"echo\", 1)\nend\n");
+        assert_eq!(errors, [
+            ("Sprite1 has no sounds".to_string(), 2),
+            ("unknown sound effect".to_string(), 5)
+        ]);
+    }
+
+    #[test]
+    fn looks_blocks() {
+        let project = compile(concat!(
+            "on start do\n\tsprite.switch_costume(\"costume1\")\n\tsprite.set_effect(\"ghost\", 50)\n",
+            "\tsprite.change_size(10)\n\tsprite.think(sprite.costume_name())\n\tsprite.go_to_front()\n",
+            "\tstage.switch_backdrop(\"backdrop1\")\nend\n"
+        ));
+
+        let obj = &project.sprites[0].obj;
+        assert_eq!(scripts(obj)[0], [
+            "event_whenflagclicked",
+            "looks_switchcostumeto",
+            "looks_seteffectto GHOST 50",
+            "looks_changesizeby 10",
+            "looks_think",
+            "looks_gotofrontback front",
+            "looks_switchbackdropto"
+        ]);
+
+        let blocks = blocks(obj);
+        let costume = &blocks[block(&blocks, "looks_switchcostumeto")["inputs"]["COSTUME"][1].as_str().unwrap()];
+        assert_eq!(costume["opcode"], "looks_costume");
+        assert_eq!(costume["fields"]["COSTUME"], json::array!["costume1", null]);
+        assert_eq!(costume["shadow"], true);
+
+        let backdrop = &blocks[block(&blocks, "looks_switchbackdropto")["inputs"]["BACKDROP"][1].as_str().unwrap()];
+        assert_eq!(backdrop["fields"]["BACKDROP"], json::array!["backdrop1", null]);
+
+        assert_eq!(block(&blocks, "looks_costumenumbername")["fields"]["NUMBER_NAME"], json::array!["name", null]);
+    }
+
+    #[test]
+    fn looks_errors() {
+        let errors = errors(concat!(
+            "on start do\n\tsprite.switch_costume(\"cat\")\nend\n",
+            "on start do\n\tsprite.set_effect(\"blur\", 1)\nend\n",
+            "stage {\n\ton start do\n\t\tsprite.say(1)\n\tend\n}\n"
+        ));
+
+        assert_eq!(errors, [
+            ("unknown costume \"cat\"".to_string(), 2),
+            ("unknown graphic effect".to_string(), 5),
+            ("sprite.say can't be used on the stage".to_string(), 9)
+        ]);
+    }
+
+    #[test]
+    fn motion_blocks() {
+        let project = compile(concat!(
+            "on start do\n\tsprite.goto(\"_mouse_\")\n\tsprite.glide_to(1, \"Cat\")\n",
+            "\tsprite.point_towards(\"_mouse_\")\n\tsprite.goto_xy(sprite.x(), -5)\n",
+            "\tsprite.set_rotation_style(\"don't rotate\")\nend\n"
+        ));
+
+        let obj = &project.sprites[0].obj;
+        assert_eq!(scripts(obj)[0], [
+            "event_whenflagclicked",
+            "motion_goto",
+            "motion_glideto 1",
+            "motion_pointtowards",
+            "motion_gotoxy -5",
+            "motion_setrotationstyle don't rotate"
+        ]);
+
+        let blocks = blocks(obj);
+        let menu = |opcode: &str, input: &str| {
+            let menu = &blocks[block(&blocks, opcode)["inputs"][input][1].as_str().unwrap()];
+            (menu["opcode"].to_string(), menu["fields"][input][0].to_string())
+        };
+
+        assert_eq!(menu("motion_goto", "TO"), ("motion_goto_menu".to_string(), "_mouse_".to_string()));
+        assert_eq!(menu("motion_glideto", "TO"), ("motion_glideto_menu".to_string(), "Cat".to_string()));
+        assert_eq!(menu("motion_pointtowards", "TOWARDS"), ("motion_pointtowards_menu".to_string(), "_mouse_".to_string()));
+
+        // the reporter covers the number in the x input
+        let x = &block(&blocks, "motion_gotoxy")["inputs"]["X"];
+        assert_eq!(x[0], 3);
+        assert_eq!(blocks[x[1].as_str().unwrap()]["opcode"], "motion_xposition");
+        assert_eq!(x[2], json::array![4, ""]);
+    }
+
+    #[test]
+    fn sensing_blocks() {
+        let project = compile(concat!(
+            "on start do\n\tsensing.ask(\"name?\")\n\tsprite.say(sensing.answer())\n",
+            "\tsprite.say(sensing.of(\"backdrop #\", \"_stage_\"))\n",
+            "\tsprite.say(sensing.current(\"day of week\"))\n",
+            "\tsprite.say(sensing.touching_color(\"#ff8000\"))\n",
+            "\tsensing.set_drag_mode(true)\nend\n"
+        ));
+
+        let obj = &project.sprites[0].obj;
+        let blocks = blocks(obj);
+
+        assert_eq!(block(&blocks, "sensing_askandwait")["inputs"]["QUESTION"], json::array![1, [10, "name?"]]);
+        assert_eq!(block(&blocks, "sensing_current")["fields"]["CURRENTMENU"], json::array!["DAYOFWEEK", null]);
+        assert_eq!(block(&blocks, "sensing_touchingcolor")["inputs"]["COLOR"], json::array![1, [9, "#ff8000"]]);
+        assert_eq!(block(&blocks, "sensing_setdragmode")["fields"]["DRAG_MODE"], json::array!["draggable", null]);
+
+        let of = block(&blocks, "sensing_of");
+        let object = &blocks[of["inputs"]["OBJECT"][1].as_str().unwrap()];
+        assert_eq!(of["fields"]["PROPERTY"], json::array!["backdrop #", null]);
+        assert_eq!(object["opcode"], "sensing_of_object_menu");
+        assert_eq!(object["fields"]["OBJECT"], json::array!["_stage_", null]);
+    }
+
+    #[test]
+    fn sensing_errors() {
+        let errors = errors(concat!(
+            "on start do\n\tsprite.say(sensing.of(\"x position\", \"_stage_\"))\nend\n",
+            "on start do\n\tsprite.say(sensing.current(\"week\"))\nend\n",
+            "on start do\n\tsprite.say(sensing.touching_color(\"orange\"))\nend\n",
+            "stage {\n\ton start do\n\t\tlocal d = sensing.distance_to(\"Cat\")\n\tend\n}\n"
+        ));
+
+        assert_eq!(errors, [
+            ("the stage has no property \"x position\"".to_string(), 2),
+            ("unknown unit of time".to_string(), 5),
+            ("invalid color".to_string(), 8),
+            ("sensing.distance_to can't be used on the stage".to_string(), 12)
+        ]);
+    }
+
+    #[test]
+    fn pen_blocks_add_the_extension() {
+        let mut project = compile(concat!(
+            "on start do\n\tpen.clear()\n\tpen.set_color(\"#ff8000\")\n",
+            "\tpen.set_color_param(\"transparency\", 50)\n\tpen.down()\n\tpen.set_size(4)\nend\n"
+        ));
+
+        assert_eq!(scripts(&project.sprites[0].obj)[0], [
+            "event_whenflagclicked",
+            "pen_clear",
+            "pen_setPenColorToColor #ff8000",
+            "pen_setPenColorParamTo 50",
+            "pen_penDown",
+            "pen_setPenSizeTo 4"
+        ]);
+
+        let json = saved(&mut project, "pen");
+        let blocks = &json["targets"][1]["blocks"];
+        let param = &blocks[block(blocks, "pen_setPenColorParamTo")["inputs"]["COLOR_PARAM"][1].as_str().unwrap()];
+
+        assert_eq!(json["extensions"], json::array!["pen"]);
+        assert_eq!(block(blocks, "pen_setPenColorToColor")["inputs"]["COLOR"], json::array![1, [9, "#ff8000"]]);
+        assert_eq!(param["opcode"], "pen_menu_colorParam");
+        assert_eq!(param["fields"]["colorParam"], json::array!["transparency", null]);
+    }
+
+    #[test]
+    fn projects_without_extension_blocks_list_none() {
+        let mut project = compile("on start do\n\tsprite.say(1)\nend\n");
+        assert_eq!(saved(&mut project, "no-extensions")["extensions"], json::array![]);
+
+        let errors = errors("stage {\n\ton start do\n\t\tpen.down()\n\tend\n}\n");
+        assert_eq!(errors, [("pen.down can't be used on the stage".to_string(), 3)]);
+    }
+
+    #[test]
+    fn music_blocks_add_the_extension() {
+        let mut project = compile(concat!(
+            "on start do\n\tmusic.set_instrument(\"electric guitar\")\n\tmusic.play_drum(\"open hi-hat\", 0.5)\n",
+            "\tmusic.play_note(60, 1)\n\tmusic.set_tempo(90)\n\tsprite.say(music.tempo())\n",
+            "\tpen.clear()\nend\n"
+        ));
+
+        let json = saved(&mut project, "music");
+        let blocks = &json["targets"][1]["blocks"];
+        let menu = |opcode: &str, input: &str, field: &str| {
+            let menu = &blocks[block(blocks, opcode)["inputs"][input][1].as_str().unwrap()];
+            (menu["opcode"].to_string(), menu["fields"][field][0].to_string())
+        };
+
+        // menus hold the number of the drum or instrument in scratch
+        assert_eq!(menu("music_setInstrument", "INSTRUMENT", "INSTRUMENT"), ("music_menu_INSTRUMENT".to_string(), "5".to_string()));
+        assert_eq!(menu("music_playDrumForBeats", "DRUM", "DRUM"), ("music_menu_DRUM".to_string(), "5".to_string()));
+        assert_eq!(block(blocks, "music_playNoteForBeats")["inputs"]["NOTE"][0], 1);
+        assert_eq!(block(blocks, "music_setTempo")["inputs"]["TEMPO"], json::array![1, [4, "90"]]);
+        assert!(blocks.entries().any(|(_, block)| block["opcode"] == "music_getTempo"));
+
+        assert_eq!(json["extensions"], json::array!["pen", "music"]);
+    }
+
+    #[test]
+    fn music_errors() {
+        let errors = errors("on start do\n\tmusic.set_instrument(\"kazoo\")\nend\non start do\n\tmusic.play_drum(99, 1)\nend\n");
+        assert_eq!(errors, [("unknown instrument".to_string(), 2), ("unknown drum".to_string(), 5)]);
+    }
+}
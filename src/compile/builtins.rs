@@ -0,0 +1,931 @@
+use super::*;
+
+// what kind of block a builtin function lowers to
+pub enum Kind {
+    Statement,
+    Reporter,
+    Predicate
+}
+
+// get the arguments of a call, checking that there are exactly N of them
+fn args<const N: usize>(call: &AstCall) -> Result<[&AstExpression; N], CompileError> {
+    let args: Vec<&AstExpression> = call.args.iter().collect();
+
+    args.try_into().or(Err(CompileError::new(format!(
+        "{} expects {} argument(s), got {}", call_name(call), N, call.args.len()
+    ), call.span)))
+}
+
+// functions of the sprite module which the stage doesn't have blocks for
+const SPRITE_ONLY: &[&str] = &[
+    "move", "turn_right", "turn_left", "point_in_direction", "point_towards",
+    "goto", "goto_xy", "glide_to", "glide_to_xy",
+    "change_x", "set_x", "change_y", "set_y", "bounce", "set_rotation_style",
+    "x", "y", "direction",
+
+    "say", "think", "show", "hide",
+    "switch_costume", "next_costume", "costume_number", "costume_name",
+    "change_size", "set_size", "size",
+    "go_to_front", "go_to_back", "go_forward", "go_backward",
+    "delete_clone"
+];
+
+// functions of the sensing module which only work on a sprite
+const SENSING_SPRITE_ONLY: &[&str] = &[
+    "touching", "touching_color", "color_touching_color", "distance_to", "set_drag_mode"
+];
+
+// functions of the pen module which only work on a sprite. the stage can only erase
+const PEN_SPRITE_ONLY: &[&str] = &[
+    "stamp", "down", "up", "set_color", "change_color_param", "set_color_param", "change_size", "set_size"
+];
+
+// the message of a broadcast block. a message which isn't known until the script
+// runs is put over the menu, which shows the first message of the project. only a
+// project without any gets one for it
+fn message(c: &mut Compiler, expr: &AstExpression) -> Result<UserInput, CompileError> {
+    let input = c.value(expr)?;
+
+    if input.block.is_none() {
+        let name = match &input.value {
+            Value::String(name) => name.clone(),
+            Value::Number(num) => num.to_string(),
+            Value::Boolean(b) => b.to_string()
+        };
+
+        c.obj.add_broadcast(&name);
+        return Ok(UserInput::new(Value::String(name), None));
+    }
+
+    let first = c.obj.global_data.borrow().broadcasts.first().map(|broadcast| broadcast.name.clone());
+
+    let name = match first {
+        Some(name) => name,
+        None => {
+            c.obj.add_broadcast("message1");
+            "message1".to_string()
+        }
+    };
+
+    Ok(UserInput::new(Value::String(name), input.block))
+}
+
+// the sound menu of a play block. a sound which isn't known until the script runs is
+// put over the menu, which then shows the first sound
+fn sound(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let first = match c.obj.sounds.first() {
+        Some(sound) => sound.name.clone(),
+        None => return Err(CompileError::new(format!("{} has no sounds", c.obj.name), expr.span)
+            .with_help("add a sound to the sounds property of the declaration"))
+    };
+
+    let input = c.value(expr)?;
+
+    match (input.value, input.block) {
+        // scratch plays a sound by its position when given a number
+        (Value::Number(num), None) => Ok(MenuInput::new(Opcode::SoundsMenu(num.to_string()), None)),
+
+        (Value::String(name), None) => {
+            if c.obj.sounds.iter().any(|sound| sound.name == name) {
+                Ok(MenuInput::new(Opcode::SoundsMenu(name), None))
+            } else {
+                Err(CompileError::new(format!("unknown sound \"{}\"", name), expr.span))
+            }
+        },
+
+        (_, block) => Ok(MenuInput::new(Opcode::SoundsMenu(first), block))
+    }
+}
+
+// the costume menu of a switch costume block, which shows the first costume under a reporter
+fn costume(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    // a sprite without costumes gets a blank one
+    let names: Vec<String> = match c.obj.costumes.is_empty() {
+        true => vec![BLANK_COSTUME.to_string()],
+        false => c.obj.costumes.iter().map(|costume| costume.name.clone()).collect()
+    };
+
+    let input = c.value(expr)?;
+
+    match (input.value, input.block) {
+        (Value::Number(num), None) => Ok(MenuInput::new(Opcode::Costume(num.to_string()), None)),
+
+        (Value::String(name), None) => {
+            if names.contains(&name) {
+                Ok(MenuInput::new(Opcode::Costume(name), None))
+            } else {
+                Err(CompileError::new(format!("unknown costume \"{}\"", name), expr.span))
+            }
+        },
+
+        (_, block) => Ok(MenuInput::new(Opcode::Costume(names[0].clone()), block))
+    }
+}
+
+// the backdrop menu of a switch backdrop block. the stage might not be declared yet,
+// so backdrop names are checked once every file is compiled
+fn backdrop(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let name = match (&input.value, &input.block) {
+        (Value::Number(num), None) => num.to_string(),
+
+        // the menu can also pick the next, previous or a random backdrop
+        (Value::String(name), None) => {
+            if !["next backdrop", "previous backdrop", "random backdrop"].contains(&name.as_str()) {
+                c.backdrops.push((name.clone(), expr.span));
+            }
+
+            name.clone()
+        },
+
+        _ => "next backdrop".to_string()
+    };
+
+    Ok(MenuInput::new(Opcode::Backdrops(name), input.block))
+}
+
+// the menu of a go to or glide block. a target which isn't known until the script
+// runs is put over the menu, which shows a random position
+fn position_target(c: &mut Compiler, expr: &AstExpression, glide: bool) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let target = match (&input.value, &input.block) {
+        (Value::String(name), None) => PositionTarget::from_name(name),
+        _ => PositionTarget::RandomPosition
+    };
+
+    let menu = if glide { Opcode::GlideToMenu(target) } else { Opcode::GoToMenu(target) };
+    Ok(MenuInput::new(menu, input.block))
+}
+
+// the menu of a create clone block, which shows the sprite itself under a reporter.
+// the stage can only clone sprites
+fn clone_target(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let option = match (&input.value, &input.block) {
+        (Value::String(name), None) => SpriteOption::from_name(name),
+        _ => SpriteOption::Myself
+    };
+
+    if c.obj.is_stage && input.block.is_none() && matches!(option, SpriteOption::Myself) {
+        return Err(CompileError::new("the stage can't be cloned".to_string(), expr.span)
+            .with_help("give the name of a sprite to clone"));
+    }
+
+    Ok(MenuInput::new(Opcode::CreateCloneOfMenu(option), input.block))
+}
+
+// the menu of a point towards block, which shows the mouse pointer under a reporter
+fn point_target(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let target = match (&input.value, &input.block) {
+        (Value::String(name), None) => PointTarget::from_name(name),
+        _ => PointTarget::MousePointer
+    };
+
+    Ok(MenuInput::new(Opcode::PointTowardsMenu(target), input.block))
+}
+
+// the menu of a touching block, which shows the mouse pointer under a reporter
+fn touching_target(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let target = match (&input.value, &input.block) {
+        (Value::String(name), None) => TouchingOption::from_name(name),
+        _ => TouchingOption::MousePointer
+    };
+
+    Ok(MenuInput::new(Opcode::TouchingObjectMenu(target), input.block))
+}
+
+// the menu of a distance to block, which shows the mouse pointer under a reporter
+fn distance_target(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let target = match (&input.value, &input.block) {
+        (Value::String(name), None) => name.clone(),
+        _ => "_mouse_".to_string()
+    };
+
+    Ok(MenuInput::new(Opcode::DistanceToMenu(target), input.block))
+}
+
+// the menu of a key pressed block, which shows the space key under a reporter
+fn key(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let key = match (&input.value, &input.block) {
+        (Value::String(name), None) => match KeyOption::from_name(name) {
+            Some(key) => key,
+            None => return Err(CompileError::new(format!("unknown key \"{}\"", name), expr.span)
+                .with_help("expected \"any\", \"space\", an arrow like \"up arrow\", a letter or a digit"))
+        },
+
+        _ => KeyOption::Space
+    };
+
+    Ok(MenuInput::new(Opcode::KeyOptions(key), input.block))
+}
+
+// the menu of an of block. a reporter is put over the stage if the stage has the
+// property, and over the sprite itself otherwise
+fn of_object(c: &mut Compiler, expr: &AstExpression, property: &ObjectProperty) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let object = match (&input.value, &input.block) {
+        (Value::String(name), None) => ObjectMenu::from_name(name),
+        _ if property.name(true).is_none() && !c.obj.is_stage => ObjectMenu::Sprite(c.obj.name.clone()),
+        _ => ObjectMenu::Stage
+    };
+
+    let stage = matches!(object, ObjectMenu::Stage);
+
+    if property.name(stage).is_none() {
+        // the property of a sprite is in the name the other way around
+        let name = property.name(!stage).unwrap_or_default();
+
+        let err = match (stage, input.block.is_some()) {
+            (true, false) => CompileError::new(format!("the stage has no property \"{}\"", name), expr.span),
+            (true, true) => CompileError::new(format!("\"{}\" can't be read from a sprite given by a reporter on the stage", name), expr.span)
+                .with_help("give the name of the sprite"),
+            (false, _) => CompileError::new(format!("a sprite has no property \"{}\"", name), expr.span)
+                .with_help("use \"_stage_\" to read the property of the stage")
+        };
+
+        return Err(err);
+    }
+
+    Ok(MenuInput::new(Opcode::OfObjectMenu(object), input.block))
+}
+
+// a property of a sprite or the stage given by name, or the name of one of its variables
+fn object_property(expr: &AstExpression) -> Result<ObjectProperty, CompileError> {
+    match &expr.kind {
+        AstExpressionKind::String(name) => Ok(ObjectProperty::from_name(name)),
+        _ => Err(CompileError::new("expected the name of a property".to_string(), expr.span)
+            .with_help("use a property like \"x position\" or \"backdrop #\", or the name of a variable"))
+    }
+}
+
+// a color given as #rrggbb
+fn color(expr: &AstExpression) -> Result<Color, CompileError> {
+    let color = match &expr.kind {
+        AstExpressionKind::String(hex) => Color::from_hex(hex),
+        _ => None
+    };
+
+    color.ok_or(CompileError::new("invalid color".to_string(), expr.span)
+        .with_help("expected a color like \"#ff0000\""))
+}
+
+// the color of a set pen color block. a color which isn't known until the script runs
+// is put over a black color picker
+fn pen_color(c: &mut Compiler, expr: &AstExpression) -> Result<UserInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let color = match (&input.value, &input.block) {
+        (Value::String(hex), None) => Color::from_hex(hex),
+        (_, None) => None,
+        (_, Some(_)) => return Ok(UserInput::new(Value::String("#000000".to_string()), input.block))
+    };
+
+    match color {
+        Some(color) => Ok(UserInput::new(Value::String(color.hex()), None)),
+        None => Err(CompileError::new("invalid color".to_string(), expr.span)
+            .with_help("expected a color like \"#ff0000\""))
+    }
+}
+
+// the menu of a pen color parameter block, which shows the color under a reporter
+fn color_param(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let param = match (&input.value, &input.block) {
+        (Value::String(name), None) => match PenColorParam::from_name(name) {
+            Some(param) => param,
+            None => return Err(CompileError::new(format!("unknown color parameter \"{}\"", name), expr.span)
+                .with_help("expected \"color\", \"saturation\", \"brightness\" or \"transparency\""))
+        },
+
+        _ => PenColorParam::Color
+    };
+
+    Ok(MenuInput::new(Opcode::PenColorParamMenu(param), input.block))
+}
+
+// the drum menu of a play drum block, given by name or number. a reporter is put over
+// the snare drum
+fn drum(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let drum = match (&input.value, &input.block) {
+        (Value::String(name), None) => Drum::from_name(name),
+        (Value::Number(num), None) if num.fract() == 0.0 && *num >= 1.0 => Drum::from_number(*num as usize),
+        (_, None) => None,
+        (_, Some(_)) => Some(Drum::SnareDrum)
+    };
+
+    match drum {
+        Some(drum) => Ok(MenuInput::new(Opcode::DrumMenu(drum), input.block)),
+        None => Err(CompileError::new("unknown drum".to_string(), expr.span)
+            .with_help("expected a drum like \"snare drum\" or \"open hi-hat\", or its number from 1 to 18"))
+    }
+}
+
+// the instrument menu of a set instrument block, given by name or number. a reporter is
+// put over the piano
+fn instrument(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let instrument = match (&input.value, &input.block) {
+        (Value::String(name), None) => Instrument::from_name(name),
+        (Value::Number(num), None) if num.fract() == 0.0 && *num >= 1.0 => Instrument::from_number(*num as usize),
+        (_, None) => None,
+        (_, Some(_)) => Some(Instrument::Piano)
+    };
+
+    match instrument {
+        Some(instrument) => Ok(MenuInput::new(Opcode::InstrumentMenu(instrument), input.block)),
+        None => Err(CompileError::new("unknown instrument".to_string(), expr.span)
+            .with_help("expected an instrument like \"piano\" or \"electric guitar\", or its number from 1 to 21"))
+    }
+}
+
+// the note picker of a play note block, which shows middle C under a reporter
+fn note(c: &mut Compiler, expr: &AstExpression) -> Result<MenuInput, CompileError> {
+    let input = c.value(expr)?;
+
+    let note = match (&input.value, &input.block) {
+        (Value::Number(num), None) => *num,
+        (_, None) => return Err(CompileError::new("expected a note number".to_string(), expr.span)
+            .with_help("notes are MIDI note numbers, where 60 is middle C")),
+        (_, Some(_)) => 60.0
+    };
+
+    Ok(MenuInput::new(Opcode::Note(note), input.block))
+}
+
+// a unit of time given by name
+fn time_option(expr: &AstExpression) -> Result<TimeOption, CompileError> {
+    let option = match &expr.kind {
+        AstExpressionKind::String(name) => TimeOption::from_name(name),
+        _ => None
+    };
+
+    option.ok_or(CompileError::new("unknown unit of time".to_string(), expr.span)
+        .with_help("expected \"year\", \"month\", \"date\", \"day of week\", \"hour\", \"minute\" or \"second\""))
+}
+
+// a rotation style given by name
+fn rotation_style(expr: &AstExpression) -> Result<RotationStyle, CompileError> {
+    let style = match &expr.kind {
+        AstExpressionKind::String(name) => RotationStyle::from_name(name),
+        _ => None
+    };
+
+    style.ok_or(CompileError::new("unknown rotation style".to_string(), expr.span)
+        .with_help("expected \"all around\", \"left-right\" or \"don't rotate\""))
+}
+
+// a graphic effect given by name
+fn graphic_effect(expr: &AstExpression) -> Result<GraphicEffect, CompileError> {
+    let effect = match &expr.kind {
+        AstExpressionKind::String(name) => GraphicEffect::from_name(name),
+        _ => None
+    };
+
+    effect.ok_or(CompileError::new("unknown graphic effect".to_string(), expr.span)
+        .with_help("expected \"color\", \"fisheye\", \"whirl\", \"pixelate\", \"mosaic\", \"brightness\" or \"ghost\""))
+}
+
+// a sound effect given by name
+fn sound_effect(expr: &AstExpression) -> Result<SoundEffect, CompileError> {
+    let effect = match &expr.kind {
+        AstExpressionKind::String(name) => SoundEffect::from_name(name),
+        _ => None
+    };
+
+    effect.ok_or(CompileError::new("unknown sound effect".to_string(), expr.span)
+        .with_help("expected \"pitch\" or \"pan\""))
+}
+
+/// Lowers a call to a builtin function into its opcode.
+///
+/// Returns `None` if the call does not refer to a builtin.
+pub fn lower(c: &mut Compiler, call: &AstCall) -> Result<Option<(Kind, Opcode)>, CompileError> {
+    let module = match &call.module {
+        Some(v) => v.as_str(),
+        None => return Ok(None)
+    };
+
+    let sprite_only = match module {
+        "sprite" => SPRITE_ONLY.contains(&call.name.as_str()),
+        "sensing" => SENSING_SPRITE_ONLY.contains(&call.name.as_str()),
+        "pen" => PEN_SPRITE_ONLY.contains(&call.name.as_str()),
+        _ => false
+    };
+
+    if sprite_only && c.obj.is_stage {
+        return Err(CompileError::new(format!("{} can't be used on the stage", call_name(call)), call.span));
+    }
+
+    Ok(Some(match (module, call.name.as_str()) {
+        // MOTION //
+        ("sprite", "move") => {
+            let [steps] = args(call)?;
+            (Kind::Statement, Opcode::MoveSteps(c.value(steps)?))
+        },
+
+        ("sprite", "turn_right") => {
+            let [degrees] = args(call)?;
+            (Kind::Statement, Opcode::TurnRight(c.value(degrees)?))
+        },
+
+        ("sprite", "turn_left") => {
+            let [degrees] = args(call)?;
+            (Kind::Statement, Opcode::TurnLeft(c.value(degrees)?))
+        },
+
+        ("sprite", "point_in_direction") => {
+            let [direction] = args(call)?;
+            (Kind::Statement, Opcode::PointInDirection(c.value(direction)?))
+        },
+
+        ("sprite", "point_towards") => {
+            let [target] = args(call)?;
+            (Kind::Statement, Opcode::PointTowards(point_target(c, target)?))
+        },
+
+        ("sprite", "goto") => {
+            let [target] = args(call)?;
+            (Kind::Statement, Opcode::GoTo(position_target(c, target, false)?))
+        },
+
+        ("sprite", "goto_xy") => {
+            let [x, y] = args(call)?;
+            (Kind::Statement, Opcode::GoToXY(c.value(x)?, c.value(y)?))
+        },
+
+        ("sprite", "glide_to") => {
+            let [secs, target] = args(call)?;
+            let secs = c.value(secs)?;
+            (Kind::Statement, Opcode::GlideTo(secs, position_target(c, target, true)?))
+        },
+
+        ("sprite", "glide_to_xy") => {
+            let [secs, x, y] = args(call)?;
+            (Kind::Statement, Opcode::GlideSecsToXY(c.value(secs)?, c.value(x)?, c.value(y)?))
+        },
+
+        ("sprite", "change_x") => {
+            let [dx] = args(call)?;
+            (Kind::Statement, Opcode::ChangeXBy(c.value(dx)?))
+        },
+
+        ("sprite", "set_x") => {
+            let [x] = args(call)?;
+            (Kind::Statement, Opcode::SetX(c.value(x)?))
+        },
+
+        ("sprite", "change_y") => {
+            let [dy] = args(call)?;
+            (Kind::Statement, Opcode::ChangeYBy(c.value(dy)?))
+        },
+
+        ("sprite", "set_y") => {
+            let [y] = args(call)?;
+            (Kind::Statement, Opcode::SetY(c.value(y)?))
+        },
+
+        ("sprite", "bounce") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::IfOnEdgeBounce())
+        },
+
+        ("sprite", "set_rotation_style") => {
+            let [style] = args(call)?;
+            (Kind::Statement, Opcode::SetRotationStyle(rotation_style(style)?))
+        },
+
+        ("sprite", "x") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::XPosition())
+        },
+
+        ("sprite", "y") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::YPosition())
+        },
+
+        ("sprite", "direction") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::Direction())
+        },
+
+        // LOOKS //
+        ("sprite", "say") => {
+            let [message] = args(call)?;
+            (Kind::Statement, Opcode::Say(c.value(message)?))
+        },
+
+        ("sprite", "think") => {
+            let [message] = args(call)?;
+            (Kind::Statement, Opcode::Think(c.value(message)?))
+        },
+
+        ("sprite", "show") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::Show())
+        },
+
+        ("sprite", "hide") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::Hide())
+        },
+
+        ("sprite", "switch_costume") => {
+            let [name] = args(call)?;
+            (Kind::Statement, Opcode::SwitchCostumeTo(costume(c, name)?))
+        },
+
+        ("sprite", "next_costume") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::NextCostume())
+        },
+
+        ("sprite", "costume_number") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::CostumeNumberName(NumberName::Number))
+        },
+
+        ("sprite", "costume_name") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::CostumeNumberName(NumberName::Name))
+        },
+
+        ("sprite", "set_effect") => {
+            let [effect, value] = args(call)?;
+            (Kind::Statement, Opcode::SetGraphicEffectTo(graphic_effect(effect)?, c.value(value)?))
+        },
+
+        ("sprite", "change_effect") => {
+            let [effect, change] = args(call)?;
+            (Kind::Statement, Opcode::ChangeGraphicEffectBy(graphic_effect(effect)?, c.value(change)?))
+        },
+
+        ("sprite", "clear_effects") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::ClearGraphicEffects())
+        },
+
+        ("sprite", "change_size") => {
+            let [change] = args(call)?;
+            (Kind::Statement, Opcode::ChangeSizeBy(c.value(change)?))
+        },
+
+        ("sprite", "set_size") => {
+            let [size] = args(call)?;
+            (Kind::Statement, Opcode::SetSizeTo(c.value(size)?))
+        },
+
+        ("sprite", "size") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::Size())
+        },
+
+        ("sprite", "go_to_front") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::GoToFrontBack(FrontBack::Front))
+        },
+
+        ("sprite", "go_to_back") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::GoToFrontBack(FrontBack::Back))
+        },
+
+        ("sprite", "go_forward") => {
+            let [layers] = args(call)?;
+            (Kind::Statement, Opcode::GoForwardBackwardLayers(ForwardBack::Forward, c.value(layers)?))
+        },
+
+        ("sprite", "go_backward") => {
+            let [layers] = args(call)?;
+            (Kind::Statement, Opcode::GoForwardBackwardLayers(ForwardBack::Back, c.value(layers)?))
+        },
+
+        ("stage", "switch_backdrop") => {
+            let [name] = args(call)?;
+            (Kind::Statement, Opcode::SwitchBackdropTo(backdrop(c, name)?))
+        },
+
+        // scratch only has this block on the stage
+        ("stage", "switch_backdrop_and_wait") => {
+            if !c.obj.is_stage {
+                return Err(CompileError::new(format!("{} can only be used on the stage", call_name(call)), call.span));
+            }
+
+            let [name] = args(call)?;
+            (Kind::Statement, Opcode::SwitchBackdropToAndWait(backdrop(c, name)?))
+        },
+
+        ("stage", "next_backdrop") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::NextBackdrop())
+        },
+
+        ("stage", "backdrop_number") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::BackdropNumberName(NumberName::Number))
+        },
+
+        ("stage", "backdrop_name") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::BackdropNumberName(NumberName::Name))
+        },
+
+        // SOUND //
+        ("sprite", "play_sound") => {
+            let [sound_name] = args(call)?;
+            (Kind::Statement, Opcode::Play(sound(c, sound_name)?))
+        },
+
+        ("sprite", "play_sound_until_done") => {
+            let [sound_name] = args(call)?;
+            (Kind::Statement, Opcode::PlayUntilDone(sound(c, sound_name)?))
+        },
+
+        ("sprite", "stop_all_sounds") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::StopAllSounds())
+        },
+
+        ("sprite", "clear_sound_effects") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::ClearSoundEffects())
+        },
+
+        ("sprite", "set_sound_effect") => {
+            let [effect, value] = args(call)?;
+            (Kind::Statement, Opcode::SetSoundEffectTo(sound_effect(effect)?, c.value(value)?))
+        },
+
+        ("sprite", "change_sound_effect") => {
+            let [effect, value] = args(call)?;
+            (Kind::Statement, Opcode::ChangeSoundEffectBy(sound_effect(effect)?, c.value(value)?))
+        },
+
+        ("sprite", "change_volume") => {
+            let [volume] = args(call)?;
+            (Kind::Statement, Opcode::ChangeVolumeBy(c.value(volume)?))
+        },
+
+        ("sprite", "set_volume") => {
+            let [volume] = args(call)?;
+            (Kind::Statement, Opcode::SetVolumeTo(c.value(volume)?))
+        },
+
+        ("sprite", "volume") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::Volume())
+        },
+
+        // SENSING //
+        ("sensing", "ask") => {
+            let [question] = args(call)?;
+            (Kind::Statement, Opcode::AskAndWait(c.value(question)?))
+        },
+
+        ("sensing", "answer") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::Answer())
+        },
+
+        ("sensing", "touching") => {
+            let [target] = args(call)?;
+            (Kind::Predicate, Opcode::TouchingObject(touching_target(c, target)?))
+        },
+
+        ("sensing", "touching_color") => {
+            let [touching] = args(call)?;
+            (Kind::Predicate, Opcode::TouchingColor(color(touching)?))
+        },
+
+        ("sensing", "color_touching_color") => {
+            let [sprite_color, touching] = args(call)?;
+            (Kind::Predicate, Opcode::ColorIsTouchingColor(color(sprite_color)?, color(touching)?))
+        },
+
+        ("sensing", "distance_to") => {
+            let [target] = args(call)?;
+            (Kind::Reporter, Opcode::DistanceTo(distance_target(c, target)?))
+        },
+
+        ("sensing", "key_pressed") => {
+            let [key_name] = args(call)?;
+            (Kind::Predicate, Opcode::KeyPressed(key(c, key_name)?))
+        },
+
+        ("sensing", "set_drag_mode") => {
+            let [draggable] = args(call)?;
+            match draggable.kind {
+                AstExpressionKind::Boolean(draggable) => (Kind::Statement, Opcode::SetDragMode(draggable)),
+                _ => return Err(CompileError::new("expected true or false".to_string(), draggable.span))
+            }
+        },
+
+        ("sensing", "of") => {
+            let [property, object] = args(call)?;
+            let property = object_property(property)?;
+            let object = of_object(c, object, &property)?;
+            (Kind::Reporter, Opcode::Of(property, object))
+        },
+
+        ("sensing", "current") => {
+            let [unit] = args(call)?;
+            (Kind::Reporter, Opcode::Current(time_option(unit)?))
+        },
+
+        ("sensing", "mouse_down") => {
+            let [] = args(call)?;
+            (Kind::Predicate, Opcode::MouseDown())
+        },
+
+        ("sensing", "mouse_x") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::MouseX())
+        },
+
+        ("sensing", "mouse_y") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::MouseY())
+        },
+
+        ("sensing", "loudness") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::Loudness())
+        },
+
+        ("sensing", "timer") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::Timer())
+        },
+
+        ("sensing", "reset_timer") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::ResetTimer())
+        },
+
+        ("sensing", "days_since_2000") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::DaysSince2000())
+        },
+
+        ("sensing", "username") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::Username())
+        },
+
+        // EVENTS //
+        ("event", "broadcast") => {
+            let [name] = args(call)?;
+            (Kind::Statement, Opcode::Broadcast(message(c, name)?))
+        },
+
+        ("event", "broadcast_and_wait") => {
+            let [name] = args(call)?;
+            (Kind::Statement, Opcode::BroadcastAndWait(message(c, name)?))
+        },
+
+        // CONTROL //
+        ("sprite", "create_clone") => {
+            let [target] = args(call)?;
+            (Kind::Statement, Opcode::CreateCloneOf(clone_target(c, target)?))
+        },
+
+        ("sprite", "delete_clone") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::DeleteThisClone())
+        },
+
+        // PEN //
+        ("pen", "clear") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::PenClear())
+        },
+
+        ("pen", "stamp") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::PenStamp())
+        },
+
+        ("pen", "down") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::PenDown())
+        },
+
+        ("pen", "up") => {
+            let [] = args(call)?;
+            (Kind::Statement, Opcode::PenUp())
+        },
+
+        ("pen", "set_color") => {
+            let [color] = args(call)?;
+            (Kind::Statement, Opcode::SetPenColorToColor(pen_color(c, color)?))
+        },
+
+        ("pen", "change_color_param") => {
+            let [param, value] = args(call)?;
+            (Kind::Statement, Opcode::ChangePenColorParamBy(color_param(c, param)?, c.value(value)?))
+        },
+
+        ("pen", "set_color_param") => {
+            let [param, value] = args(call)?;
+            (Kind::Statement, Opcode::SetPenColorParamTo(color_param(c, param)?, c.value(value)?))
+        },
+
+        ("pen", "change_size") => {
+            let [size] = args(call)?;
+            (Kind::Statement, Opcode::ChangePenSizeBy(c.value(size)?))
+        },
+
+        ("pen", "set_size") => {
+            let [size] = args(call)?;
+            (Kind::Statement, Opcode::SetPenSizeTo(c.value(size)?))
+        },
+
+        // MUSIC //
+        ("music", "play_drum") => {
+            let [drum_name, beats] = args(call)?;
+            (Kind::Statement, Opcode::PlayDrumForBeats(drum(c, drum_name)?, c.value(beats)?))
+        },
+
+        ("music", "rest") => {
+            let [beats] = args(call)?;
+            (Kind::Statement, Opcode::RestForBeats(c.value(beats)?))
+        },
+
+        ("music", "play_note") => {
+            let [note_number, beats] = args(call)?;
+            (Kind::Statement, Opcode::PlayNoteForBeats(note(c, note_number)?, c.value(beats)?))
+        },
+
+        ("music", "set_instrument") => {
+            let [instrument_name] = args(call)?;
+            (Kind::Statement, Opcode::SetInstrument(instrument(c, instrument_name)?))
+        },
+
+        ("music", "set_tempo") => {
+            let [tempo] = args(call)?;
+            (Kind::Statement, Opcode::SetTempo(c.value(tempo)?))
+        },
+
+        ("music", "change_tempo") => {
+            let [tempo] = args(call)?;
+            (Kind::Statement, Opcode::ChangeTempo(c.value(tempo)?))
+        },
+
+        ("music", "tempo") => {
+            let [] = args(call)?;
+            (Kind::Reporter, Opcode::GetTempo())
+        },
+
+        // OPERATORS //
+        ("math", "random") => {
+            let [from, to] = args(call)?;
+            (Kind::Reporter, Opcode::Random(c.value(from)?, c.value(to)?))
+        },
+
+        ("math", "round") => {
+            let [num] = args(call)?;
+            (Kind::Reporter, Opcode::Round(c.value(num)?))
+        },
+
+        ("string", "length") => {
+            let [string] = args(call)?;
+            (Kind::Reporter, Opcode::Length(c.value(string)?))
+        },
+
+        ("string", "contains") => {
+            let [string, substring] = args(call)?;
+            (Kind::Predicate, Opcode::Contains(c.value(string)?, c.value(substring)?))
+        },
+
+        // HEAP //
+        ("heap", "free") => {
+            let [pointer] = args(call)?;
+            let pointer = c.value(pointer)?;
+            (Kind::Statement, c.free(pointer))
+        },
+
+        ("heap", "len") => {
+            let [pointer] = args(call)?;
+            let pointer = c.value(pointer)?;
+            (Kind::Reporter, c.length(pointer))
+        },
+
+        _ => return Ok(None)
+    }))
+}
@@ -0,0 +1,210 @@
+use std::path::Path;
+use super::*;
+
+// get the constant number of a property value
+fn number(value: &AstValue) -> Result<f64, CompileError> {
+    match value {
+        AstValue::Expr(AstExpression { kind: AstExpressionKind::Number(num), .. }) => Ok(*num),
+        AstValue::Expr(AstExpression { kind: AstExpressionKind::Neg(v), .. }) => match v.kind {
+            AstExpressionKind::Number(num) => Ok(-num),
+            _ => Err(CompileError::new("expected a number".to_string(), value.span()))
+        },
+        _ => Err(CompileError::new("expected a number".to_string(), value.span()))
+    }
+}
+
+// get the constant string of a property value
+fn string(value: &AstValue) -> Result<&str, CompileError> {
+    match value {
+        AstValue::Expr(AstExpression { kind: AstExpressionKind::String(v), .. }) => Ok(v),
+        _ => Err(CompileError::new("expected a string".to_string(), value.span()))
+    }
+}
+
+// get the constant boolean of a property value
+fn boolean(value: &AstValue) -> Result<bool, CompileError> {
+    match value {
+        AstValue::Expr(AstExpression { kind: AstExpressionKind::Boolean(v), .. }) => Ok(*v),
+        _ => Err(CompileError::new("expected true or false".to_string(), value.span()))
+    }
+}
+
+// get the entries of a table property value
+fn table(value: &AstValue) -> Result<&Vec<AstProperty>, CompileError> {
+    match value {
+        AstValue::Table(entries, _) => Ok(entries),
+        _ => Err(CompileError::new("expected a table".to_string(), value.span()))
+    }
+}
+
+// find an asset file, relative to the asset directory
+fn asset_path(asset_dir: &Path, value: &AstValue) -> Result<String, CompileError> {
+    let path = asset_dir.join(string(value)?);
+
+    if !path.is_file() {
+        return Err(CompileError::new(format!("could not find asset file \"{}\"", path.display()), value.span()));
+    }
+
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// name of an asset which was declared without a key, taken from its file name
+fn asset_name(entry: &AstProperty, path: &str) -> String {
+    match &entry.key {
+        Some(key) => key.clone(),
+        None => match Path::new(path).file_stem().and_then(std::ffi::OsStr::to_str) {
+            Some(v) => v.to_string(),
+            None => path.to_string()
+        }
+    }
+}
+
+// { [<name> =] <path> }
+// { [<name> =] { path = <path>, center = { <x>, <y> } | <anchor>, offset = { <x>, <y> }, resolution = <1 or 2> } }
+fn costumes(obj: &mut Object, value: &AstValue, asset_dir: &Path) -> Result<(), CompileError> {
+    for entry in table(value)?.iter() {
+        let mut path = None;
+        let mut center = None;
+        let mut anchor = Anchor::Center;
+        let mut offset = (0.0, 0.0);
+        let mut resolution = 2;
+
+        match &entry.value {
+            AstValue::Table(props, _) => {
+                for prop in props.iter() {
+                    match prop.key.as_deref() {
+                        Some("path") => path = Some(asset_path(asset_dir, &prop.value)?),
+                        Some("center") => match &prop.value {
+                            AstValue::Table(..) => center = Some(point(&prop.value)?),
+                            v => anchor = match Anchor::from_name(string(v)?) {
+                                Some(anchor) => anchor,
+                                None => return Err(CompileError::new("unknown anchor".to_string(), v.span())
+                                    .with_help("expected center, top, bottom, left, right, top-left, top-right, bottom-left or bottom-right"))
+                            }
+                        },
+                        Some("offset") => offset = point(&prop.value)?,
+                        Some("resolution") => match number(&prop.value)? {
+                            v if v == 1.0 || v == 2.0 => resolution = v as u32,
+                            _ => return Err(CompileError::new("the resolution of a bitmap must be 1 or 2".to_string(), prop.value.span()))
+                        },
+                        _ => return Err(unknown_property(prop, "costume"))
+                    }
+                }
+
+                if path.is_none() {
+                    return Err(CompileError::new("costume is missing a path".to_string(), entry.value.span())
+                        .with_help("add path = \"<file>\""));
+                }
+            },
+
+            v => path = Some(asset_path(asset_dir, v)?)
+        }
+
+        let path = path.unwrap();
+        let name = asset_name(entry, &path);
+
+        // costumes rotate around the middle of their image unless given a center
+        let mut costume = match center {
+            Some((x, y)) => Costume::new(&name, &path, x + offset.0, y + offset.1),
+            None => Costume::anchored(&name, &path, anchor, offset)
+        };
+
+        costume.bitmap_resolution = resolution;
+        obj.add_costume(costume);
+    }
+
+    Ok(())
+}
+
+// { <x>, <y> }
+fn point(value: &AstValue) -> Result<(f64, f64), CompileError> {
+    match table(value)?.as_slice() {
+        [x, y] => Ok((number(&x.value)?, number(&y.value)?)),
+        _ => Err(CompileError::new("expected { x, y }".to_string(), value.span()))
+    }
+}
+
+// { [<name> =] <path> }
+fn sounds(obj: &mut Object, value: &AstValue, asset_dir: &Path) -> Result<(), CompileError> {
+    for entry in table(value)?.iter() {
+        let path = asset_path(asset_dir, &entry.value)?;
+        let name = asset_name(entry, &path);
+        obj.sounds.push(Sound::new(&name, &path));
+    }
+
+    Ok(())
+}
+
+fn unknown_property(prop: &AstProperty, owner: &str) -> CompileError {
+    CompileError::new(format!("unknown {} property \"{}\"", owner, prop.key.as_deref().unwrap_or("")), prop.span)
+}
+
+/// Applies the properties of a sprite declaration
+pub fn sprite(sprite: &mut Sprite, properties: &[AstProperty], asset_dir: &Path) -> Vec<CompileError> {
+    let mut errors = Vec::new();
+
+    for prop in properties.iter() {
+        let res = match prop.key.as_deref() {
+            Some("name") => string(&prop.value).map(|v| sprite.obj.name = v.to_string()),
+            Some("x") => number(&prop.value).map(|v| sprite.x = v),
+            Some("y") => number(&prop.value).map(|v| sprite.y = v),
+
+            // size is given as a percentage like in the editor
+            Some("size") => number(&prop.value).map(|v| sprite.size = v / 100.0),
+            Some("direction") => number(&prop.value).map(|v| sprite.dir = v),
+            Some("visible") => boolean(&prop.value).map(|v| sprite.visible = v),
+            Some("draggable") => boolean(&prop.value).map(|v| sprite.draggable = v),
+
+            Some("rotation_style") => string(&prop.value).and_then(|v| {
+                sprite.rotation_style = match RotationStyle::from_name(v) {
+                    Some(style) => style,
+                    None => return Err(CompileError::new(format!("unknown rotation style \"{}\"", v), prop.value.span())
+                        .with_help("expected \"all around\", \"left-right\" or \"don't rotate\""))
+                };
+
+                Ok(())
+            }),
+
+            Some("costumes") => costumes(&mut sprite.obj, &prop.value, asset_dir),
+            Some("sounds") => sounds(&mut sprite.obj, &prop.value, asset_dir),
+            _ => Err(unknown_property(prop, "sprite"))
+        };
+
+        if let Err(err) = res {
+            errors.push(err);
+        }
+    }
+
+    errors
+}
+
+/// Applies the properties of the stage declaration, some of which belong to the whole project
+pub fn stage(project: &mut Project, properties: &[AstProperty], asset_dir: &Path) -> Vec<CompileError> {
+    let mut errors = Vec::new();
+
+    for prop in properties.iter() {
+        let res = match prop.key.as_deref() {
+            Some("backdrops") => costumes(&mut project.stage, &prop.value, asset_dir),
+            Some("sounds") => sounds(&mut project.stage, &prop.value, asset_dir),
+
+            // scratch keeps the tempo between 20 and 500 beats per minute
+            Some("tempo") => number(&prop.value).and_then(|v| {
+                if !(20.0..=500.0).contains(&v) {
+                    return Err(CompileError::new(format!("tempo of {} is out of range", v), prop.value.span())
+                        .with_help("the tempo must be between 20 and 500 beats per minute"));
+                }
+
+                project.tempo = v;
+                Ok(())
+            }),
+
+            _ => Err(unknown_property(prop, "stage"))
+        };
+
+        if let Err(err) = res {
+            errors.push(err);
+        }
+    }
+
+    errors
+}
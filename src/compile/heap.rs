@@ -0,0 +1,306 @@
+// arrays are allocated from a list shared by every sprite. each block of
+// memory is preceded by an item holding its size:
+//
+//   _heap: ... | size | item 1 | item 2 | ... | item size | ...
+//                       ^ pointer
+//
+// freed blocks are kept in a linked list through their first item, starting
+// at _heap_free, and their size is negated so that they aren't freed twice.
+// a pointer of 0 is null.
+
+use super::*;
+
+const ALLOC: &str = "heap.alloc";
+const FREE: &str = "heap.free";
+
+fn number(num: f64) -> UserInput {
+    UserInput::new(Value::Number(num), None)
+}
+
+fn string(s: &str) -> UserInput {
+    UserInput::new(Value::String(s.to_string()), None)
+}
+
+fn add(a: UserInput, b: UserInput) -> UserInput {
+    reporter(Opcode::Add(a, b))
+}
+
+fn sub(a: UserInput, b: UserInput) -> UserInput {
+    reporter(Opcode::Subtract(a, b))
+}
+
+fn neg(a: UserInput) -> UserInput {
+    sub(number(0.0), a)
+}
+
+fn condition(opcode: Opcode) -> ReporterInput {
+    ReporterInput { block: Some(Box::new(Block::new(opcode))) }
+}
+
+fn substack(blocks: Vec<Opcode>) -> ScriptInput {
+    let mut script = Script::new();
+
+    for opcode in blocks {
+        script.push(Block::new(opcode));
+    }
+
+    ScriptInput { script: Some(script) }
+}
+
+fn argument(name: &str) -> UserInput {
+    reporter(Opcode::ArgumentReporterStringNumber(name.to_string()))
+}
+
+impl<'a> Compiler<'a> {
+    /// Gets the heap list, which lives on the stage so that every sprite can use it
+    pub fn heap(&mut self) -> String {
+        let name = "_heap".to_string();
+
+        if self.obj.list_id(&name).is_none() {
+            self.obj.global_data.borrow_mut().lists.push(List::new(&name, Vec::new()));
+        }
+
+        name
+    }
+
+    // head of the free list
+    fn heap_free_var(&mut self) -> String {
+        let name = "_heap_free".to_string();
+
+        if self.obj.variable_id(&name).is_none() {
+            self.obj.global_data.borrow_mut().vars.push(Variable::new(&name, Value::Number(0.0)));
+        }
+
+        name
+    }
+
+    // get the prototype of a runtime procedure, adding its script the first time it is used
+    fn runtime(&mut self, name: &str) -> Prototype {
+        if let Some(proc) = self.procedures.get(name) {
+            return proc.prototype.clone();
+        }
+
+        let (prototype, returns, body) = match name {
+            ALLOC => (Prototype::new(name, vec![("size".to_string(), ArgumentType::StringNumber)], true), true, self.alloc_body()),
+            _ => (Prototype::new(name, vec![("pointer".to_string(), ArgumentType::StringNumber)], true), false, self.free_body())
+        };
+
+        let mut script = definition(&prototype);
+
+        for opcode in body {
+            script.push(Block::new(opcode));
+        }
+
+        self.obj.scripts.push(script);
+        self.procedures.insert(name.to_string(), Procedure { prototype: prototype.clone(), returns });
+
+        prototype
+    }
+
+    // first fit: walk the free list for a block which is big enough, splitting off
+    // what is left of it. if there is none, the heap grows
+    fn alloc_body(&mut self) -> Vec<Opcode> {
+        let heap = self.heap();
+        let free = self.heap_free_var();
+        let ret = self.hidden_var("return");
+        let prev = self.hidden_var("heap_prev");
+        let block = self.hidden_var("heap_block");
+        let rest = self.hidden_var("heap_rest");
+        let block_size = self.hidden_var("heap_size");
+
+        let var = |name: &String| variable(name.clone());
+        let item = |index: UserInput| reporter(Opcode::ItemOfList(index, heap.clone()));
+        let size = || var(&block_size);
+
+        // a freed block holds the link to the next free block in its first item, so every
+        // block needs at least one
+        let clamp = Opcode::If(
+            condition(Opcode::Lt(size(), number(1.0))),
+            substack(vec![Opcode::SetVariableTo(block_size.clone(), number(1.0))])
+        );
+
+        let search = Opcode::RepeatUntil(
+            condition(Opcode::Or(
+                condition(Opcode::Equals(var(&block), number(0.0))),
+                condition(Opcode::Not(condition(Opcode::Lt(neg(item(sub(var(&block), number(1.0)))), size()))))
+            )),
+            substack(vec![
+                Opcode::SetVariableTo(prev.clone(), var(&block)),
+                Opcode::SetVariableTo(block.clone(), item(var(&block)))
+            ])
+        );
+
+        // the header goes at the end of the heap, followed by the new items
+        let grow = substack(vec![
+            Opcode::AddToList(size(), heap.clone()),
+            Opcode::SetVariableTo(block.clone(), add(reporter(Opcode::LengthOfList(heap.clone())), number(1.0))),
+            Opcode::Repeat(size(), substack(vec![Opcode::AddToList(string(""), heap.clone())]))
+        ]);
+
+        // a remainder needs at least one item to hold the link to the next free block
+        let split = Opcode::If(
+            condition(Opcode::Gt(var(&rest), number(0.0))),
+            substack(vec![
+                Opcode::ReplaceItemOfList(add(var(&block), size()), heap.clone(), neg(var(&rest))),
+                Opcode::ReplaceItemOfList(add(add(var(&block), size()), number(1.0)), heap.clone(), var(&free)),
+                Opcode::SetVariableTo(free.clone(), add(add(var(&block), size()), number(1.0)))
+            ])
+        );
+
+        let reuse = substack(vec![
+            Opcode::IfElse(
+                condition(Opcode::Equals(var(&prev), number(0.0))),
+                substack(vec![Opcode::SetVariableTo(free.clone(), item(var(&block)))]),
+                substack(vec![Opcode::ReplaceItemOfList(var(&prev), heap.clone(), item(var(&block)))])
+            ),
+            Opcode::SetVariableTo(rest.clone(), sub(sub(neg(item(sub(var(&block), number(1.0)))), size()), number(1.0))),
+            split,
+
+            // an item left over without a split is lost, so that the size of the block stays exact
+            Opcode::ReplaceItemOfList(sub(var(&block), number(1.0)), heap.clone(), size()),
+
+            // clear the old contents of the block
+            Opcode::SetVariableTo(rest.clone(), var(&block)),
+            Opcode::Repeat(size(), substack(vec![
+                Opcode::ReplaceItemOfList(var(&rest), heap.clone(), string("")),
+                Opcode::SetVariableTo(rest.clone(), add(var(&rest), number(1.0)))
+            ]))
+        ]);
+
+        vec![
+            Opcode::SetVariableTo(block_size.clone(), argument("size")),
+            clamp,
+            Opcode::SetVariableTo(prev.clone(), number(0.0)),
+            Opcode::SetVariableTo(block.clone(), var(&free)),
+            search,
+            Opcode::IfElse(condition(Opcode::Equals(var(&block), number(0.0))), grow, reuse),
+            Opcode::SetVariableTo(ret, var(&block))
+        ]
+    }
+
+    // freeing null or a block which is already free does nothing
+    fn free_body(&mut self) -> Vec<Opcode> {
+        let heap = self.heap();
+        let free = self.heap_free_var();
+        let header = || sub(argument("pointer"), number(1.0));
+        let size = reporter(Opcode::ItemOfList(header(), heap.clone()));
+
+        vec![
+            Opcode::If(
+                condition(Opcode::And(
+                    condition(Opcode::Not(condition(Opcode::Equals(argument("pointer"), number(0.0))))),
+                    condition(Opcode::Gt(size, number(0.0)))
+                )),
+                substack(vec![
+                    Opcode::ReplaceItemOfList(header(), heap.clone(), neg(reporter(Opcode::ItemOfList(header(), heap.clone())))),
+                    Opcode::ReplaceItemOfList(argument("pointer"), heap, variable(free.clone())),
+                    Opcode::SetVariableTo(free, argument("pointer"))
+                ])
+            )
+        ]
+    }
+
+    // call a runtime procedure before the current statement
+    fn runtime_call(&mut self, name: &str, args: Vec<UserInput>) {
+        let prototype = self.runtime(name);
+        let args = args.into_iter().map(ArgumentInput::StringNumber).collect();
+        self.pre.push(Block::new(Opcode::ProceduresCall(prototype, args)));
+    }
+
+    /// Allocates a block of items before the current statement, and gets a pointer to it
+    pub fn alloc(&mut self, size: UserInput) -> UserInput {
+        self.runtime_call(ALLOC, vec![size]);
+
+        let ret = self.hidden_var("return");
+        let slot = self.temp_slot();
+        let opcode = self.write_slot(slot, variable(ret));
+        self.pre.push(Block::new(opcode));
+
+        self.read_slot(slot)
+    }
+
+    /// Calls the allocator for a block of the given size, which leaves a pointer to it in the return variable
+    pub fn alloc_call(&mut self, size: usize) -> Opcode {
+        let prototype = self.runtime(ALLOC);
+        Opcode::ProceduresCall(prototype, vec![ArgumentInput::StringNumber(number(size as f64))])
+    }
+
+    /// Frees the block a pointer points to
+    pub fn free(&mut self, pointer: UserInput) -> Opcode {
+        let prototype = self.runtime(FREE);
+        Opcode::ProceduresCall(prototype, vec![ArgumentInput::StringNumber(pointer)])
+    }
+
+    // allocate an array and fill it with the values of the items
+    pub fn array(&mut self, items: &[AstExpression]) -> Result<UserInput, CompileError> {
+        let heap = self.heap();
+        let pointer = self.alloc(number(items.len() as f64));
+        let slot = self.temp_slot();
+        let opcode = self.write_slot(slot, pointer);
+        self.pre.push(Block::new(opcode));
+
+        for (i, item) in items.iter().enumerate() {
+            let value = self.value(item)?;
+            let index = add(self.read_slot(slot), number(i as f64));
+            self.pre.push(Block::new(Opcode::ReplaceItemOfList(index, heap.clone(), value)));
+        }
+
+        Ok(self.read_slot(slot))
+    }
+
+    // say an error and stop the script if the index is out of bounds. the check is made in the
+    // script itself, as stopping a custom block only returns from it
+    fn check(&mut self, pointer_slot: usize, index_slot: usize) {
+        let heap = self.heap();
+        let index = |compiler: &mut Self| compiler.read_slot(index_slot);
+
+        let size = reporter(Opcode::ItemOfList(sub(self.read_slot(pointer_slot), number(1.0)), heap));
+        let message = reporter(Opcode::Join(string("index "), reporter(Opcode::Join(index(self), string(" is out of bounds")))));
+        let out_of_bounds = Opcode::Or(
+            condition(Opcode::Lt(index(self), number(1.0))),
+            condition(Opcode::Gt(index(self), size))
+        );
+
+        self.pre.push(Block::new(Opcode::If(
+            condition(out_of_bounds),
+            substack(vec![Opcode::Say(message), Opcode::Stop(StopOption::Myself)])
+        )));
+    }
+
+    /// Gets the list and position in it of an array item, checking that the index is in bounds if enabled
+    pub fn index(&mut self, array: &AstExpression, index: &AstExpression) -> Result<(String, UserInput), CompileError> {
+        let mut pointer = self.value(array)?;
+        let mut index = self.value(index)?;
+
+        // both values are used twice, so they have to be kept until the check is done
+        if self.options.bounds_check {
+            let pointer_slot = self.temp_slot();
+            let index_slot = self.temp_slot();
+
+            let opcode = self.write_slot(pointer_slot, pointer);
+            self.pre.push(Block::new(opcode));
+            let opcode = self.write_slot(index_slot, index);
+            self.pre.push(Block::new(opcode));
+
+            self.check(pointer_slot, index_slot);
+
+            pointer = self.read_slot(pointer_slot);
+            index = self.read_slot(index_slot);
+        }
+
+        // arrays start at 1, so the first item is at the pointer itself
+        let position = match index.value {
+            Value::Number(num) if index.block.is_none() && num == 1.0 => pointer,
+            Value::Number(num) if index.block.is_none() => add(pointer, number(num - 1.0)),
+            _ => add(pointer, sub(index, number(1.0)))
+        };
+
+        Ok((self.heap(), position))
+    }
+
+    /// Gets the size of the block a pointer points to
+    pub fn length(&mut self, pointer: UserInput) -> Opcode {
+        let heap = self.heap();
+        Opcode::ItemOfList(sub(pointer, number(1.0)), heap)
+    }
+}
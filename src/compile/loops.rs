@@ -0,0 +1,319 @@
+// scratch loops can't be left early, so each loop which uses break or continue
+// gets a flag in the frame. statements after a jump only run if the flag is clear,
+// and the loop ends once the flag says to break.
+
+use super::*;
+
+// values of a loop's flag
+const RUNNING: f64 = 0.0;
+const BREAK: f64 = 1.0;
+const CONTINUE: f64 = 2.0;
+
+// a repeat-until loop which hasn't run its body yet
+const FIRST: f64 = 3.0;
+
+fn number(num: f64) -> UserInput {
+    UserInput::new(Value::Number(num), None)
+}
+
+fn condition(opcode: Opcode) -> ReporterInput {
+    ReporterInput { block: Some(Box::new(Block::new(opcode))) }
+}
+
+fn substack(script: Script) -> ScriptInput {
+    ScriptInput {
+        script: if script.is_empty() { None } else { Some(script) }
+    }
+}
+
+// the number of a literal value
+fn literal(input: &UserInput) -> Option<f64> {
+    match input.value {
+        Value::Number(num) if input.block.is_none() => Some(num),
+        _ => None
+    }
+}
+
+// a value used by every iteration of a for loop
+enum Kept {
+    Literal(UserInput),
+    Slot(usize)
+}
+
+impl<'a> Compiler<'a> {
+    fn flag_is(&mut self, flag: usize, value: f64) -> ReporterInput {
+        let flag = self.read_slot(flag);
+        condition(Opcode::Equals(flag, number(value)))
+    }
+
+    fn set_flag(&mut self, flag: usize, value: f64) -> Block {
+        Block::new(self.write_slot(flag, number(value)))
+    }
+
+    /// Condition for the statements after a break or continue in the innermost loop
+    pub fn not_jumped(&mut self) -> ReporterInput {
+        match self.loops.last() {
+            Some(Some(flag)) => self.flag_is(*flag, RUNNING),
+            _ => ReporterInput { block: None }
+        }
+    }
+
+    // lower the block of a loop, clearing its flag at the start of each iteration
+    fn loop_body(&mut self, block: &AstBlock, flag: Option<usize>) -> Result<Script, CompileError> {
+        let temps = self.temps;
+        let mut body = Script::new();
+
+        if let Some(flag) = flag {
+            body.push(self.set_flag(flag, RUNNING));
+        }
+
+        self.loops.push(flag);
+        let res = self.block(block, &mut body);
+        self.loops.pop();
+        self.temps = temps;
+
+        res.map(|_| body)
+    }
+
+    // a loop with a flag also ends when it is broken out of
+    fn exit_condition(&mut self, exit: ReporterInput, flag: Option<usize>) -> ReporterInput {
+        match flag {
+            Some(flag) => condition(Opcode::Or(self.flag_is(flag, BREAK), exit)),
+            None => exit
+        }
+    }
+
+    // blocks which evaluate the condition of a loop again at the end of its body.
+    // these are skipped when breaking, since they might call a function
+    fn recheck(&mut self, cond: &AstExpression, temps: usize, flag: Option<usize>, body: &mut Script) -> Result<(), CompileError> {
+        // the condition reads the same temporaries as when it was first lowered
+        self.temps = temps;
+        self.condition(cond)?;
+
+        let mut pre = Script::new();
+        self.flush(&mut pre);
+
+        match flag {
+            Some(flag) if !pre.is_empty() => {
+                let cond = condition(Opcode::Not(self.flag_is(flag, BREAK)));
+                body.push(Block::new(Opcode::If(cond, substack(pre))));
+            },
+            _ => body.append(pre)
+        }
+
+        Ok(())
+    }
+
+    pub fn while_loop(&mut self, cond: &AstExpression, block: &AstBlock, script: &mut Script) -> Result<(), CompileError> {
+        let jumps = block.statements.iter().any(jumps);
+
+        self.loop_scope(jumps as usize, |c, first_slot| {
+            let flag = jumps.then_some(first_slot);
+
+            let temps = c.temps;
+            let exit = condition(Opcode::Not(c.condition(cond)?));
+            let calls = !c.pre.is_empty();
+            c.flush(script);
+
+            let mut body = c.loop_body(block, flag)?;
+
+            if calls {
+                c.recheck(cond, temps, flag, &mut body)?;
+            }
+
+            if let Some(flag) = flag {
+                script.push(c.set_flag(flag, RUNNING));
+            }
+
+            let exit = c.exit_condition(exit, flag);
+            script.push(Block::new(Opcode::RepeatUntil(exit, substack(body))));
+            Ok(())
+        })
+    }
+
+    // the condition is checked after the body, so the flag also marks the first iteration
+    pub fn repeat_until_loop(&mut self, block: &AstBlock, cond: &AstExpression, script: &mut Script) -> Result<(), CompileError> {
+        self.loop_scope(1, |c, flag| {
+            let mut body = c.loop_body(block, Some(flag))?;
+
+            let temps = c.temps;
+            let done = c.condition(cond)?;
+            c.pre.clear();
+            c.recheck(cond, temps, Some(flag), &mut body)?;
+
+            let exit = condition(Opcode::And(condition(Opcode::Not(c.flag_is(flag, FIRST))), done));
+            let exit = c.exit_condition(exit, Some(flag));
+
+            script.push(c.set_flag(flag, FIRST));
+            script.push(Block::new(Opcode::RepeatUntil(exit, substack(body))));
+            Ok(())
+        })
+    }
+
+    // run a function in a new scope with hidden locals reserved for a loop, which are
+    // taken before any temporary of the loop's values so that keeping one can't overwrite another
+    fn loop_scope<T>(&mut self, slots: usize, f: impl FnOnce(&mut Self, usize) -> Result<T, CompileError>) -> Result<T, CompileError> {
+        let first_slot = self.next_slot;
+        self.scopes.push(Vec::new());
+
+        for _ in 0..slots {
+            self.declare_local("");
+        }
+
+        let res = f(self, first_slot);
+
+        self.scopes.pop();
+        self.next_slot = first_slot;
+        res
+    }
+
+    pub fn repeat_loop(&mut self, count: &AstExpression, block: &AstBlock, script: &mut Script) -> Result<(), CompileError> {
+        // control_repeat can't end early, so a loop with a break counts down in the frame
+        if block.statements.iter().any(breaks) {
+            return self.loop_scope(2, |c, counter| {
+                let count = c.value(count)?;
+                let opcode = c.write_slot(counter, count);
+                c.emit(script, opcode);
+
+                let flag = Some(counter + 1);
+                let mut body = c.loop_body(block, flag)?;
+
+                let decrement = reporter(Opcode::Subtract(c.read_slot(counter), number(1.0)));
+                body.push(Block::new(c.write_slot(counter, decrement)));
+
+                // scratch rounds the number of repeats
+                let exit = condition(Opcode::Lt(c.read_slot(counter), number(0.5)));
+                let exit = c.exit_condition(exit, flag);
+
+                script.push(c.set_flag(counter + 1, RUNNING));
+                script.push(Block::new(Opcode::RepeatUntil(exit, substack(body))));
+                Ok(())
+            });
+        }
+
+        let jumps = block.statements.iter().any(jumps);
+
+        self.loop_scope(jumps as usize, |c, first_slot| {
+            let flag = jumps.then_some(first_slot);
+            let count = c.value(count)?;
+            c.flush(script);
+
+            let body = c.loop_body(block, flag)?;
+            script.push(Block::new(Opcode::Repeat(count, substack(body))));
+            Ok(())
+        })
+    }
+
+    // the counter is a local of the loop. the end and step are evaluated once,
+    // and kept in the frame unless they are literals
+    pub fn for_loop(&mut self, name: &str, start: &AstExpression, end: &AstExpression, step: Option<&AstExpression>, block: &AstBlock, script: &mut Script) -> Result<(), CompileError> {
+        let jumps = block.statements.iter().any(jumps);
+
+        self.loop_scope(3 + jumps as usize, |c, counter| {
+            let start = c.value(start)?;
+            let end = c.value(end)?;
+            let step = match step {
+                Some(expr) => c.value(expr)?,
+                None => number(1.0)
+            };
+            c.flush(script);
+
+            let opcode = c.write_slot(counter, start);
+            script.push(Block::new(opcode));
+            let end = c.keep(end, counter + 1, script);
+            let step = c.keep(step, counter + 2, script);
+
+            // the counter comes into scope once the values of the loop are lowered
+            c.scopes.last_mut().unwrap()[0].0 = name.to_string();
+
+            let flag = jumps.then_some(counter + 3);
+            let mut body = c.loop_body(block, flag)?;
+
+            let step_value = match &step {
+                Kept::Literal(v) => number(literal(v).unwrap_or(0.0)),
+                Kept::Slot(slot) => c.read_slot(*slot)
+            };
+
+            let next = reporter(Opcode::Add(c.read_slot(counter), step_value));
+            body.push(Block::new(c.write_slot(counter, next)));
+
+            let end = match end {
+                Kept::Literal(v) => v,
+                Kept::Slot(slot) => c.read_slot(slot)
+            };
+
+            // the loop is done once the counter passes the end in the direction of the step
+            let exit = match step {
+                Kept::Literal(v) if literal(&v).unwrap_or(0.0) >= 0.0 => Opcode::Gt(c.read_slot(counter), end),
+                Kept::Literal(_) => Opcode::Lt(c.read_slot(counter), end),
+                Kept::Slot(slot) => {
+                    let distance = reporter(Opcode::Subtract(c.read_slot(counter), end));
+                    Opcode::Gt(reporter(Opcode::Multiply(distance, c.read_slot(slot))), number(0.0))
+                }
+            };
+
+            if let Some(flag) = flag {
+                script.push(c.set_flag(flag, RUNNING));
+            }
+
+            let exit = c.exit_condition(condition(exit), flag);
+            script.push(Block::new(Opcode::RepeatUntil(exit, substack(body))));
+            Ok(())
+        })
+    }
+
+    // keep a value of a for loop in its slot, unless it is a literal
+    fn keep(&mut self, value: UserInput, slot: usize, script: &mut Script) -> Kept {
+        if value.block.is_none() {
+            return Kept::Literal(value);
+        }
+
+        let opcode = self.write_slot(slot, value);
+        script.push(Block::new(opcode));
+        Kept::Slot(slot)
+    }
+
+    // lower a break or continue statement
+    pub fn jump(&mut self, statement: &AstStatement, script: &mut Script) -> Result<(), CompileError> {
+        let (keyword, value) = match statement.kind {
+            AstStatementKind::Break => ("break", BREAK),
+            _ => ("continue", CONTINUE)
+        };
+
+        match self.loops.last() {
+            Some(Some(flag)) => {
+                let block = self.set_flag(*flag, value);
+                script.push(block);
+                Ok(())
+            },
+
+            _ => Err(CompileError::new(format!("{} outside of a loop", keyword), statement.span))
+        }
+    }
+}
+
+/// Does the statement break out of or continue the loop it is in?
+pub fn jumps(statement: &AstStatement) -> bool {
+    jump(statement, true)
+}
+
+/// Does the statement break out of the loop it is in?
+pub fn breaks(statement: &AstStatement) -> bool {
+    jump(statement, false)
+}
+
+// jumps in nested loops belong to those loops
+fn jump(statement: &AstStatement, continues: bool) -> bool {
+    match &statement.kind {
+        AstStatementKind::Break => true,
+        AstStatementKind::Continue => continues,
+        AstStatementKind::Do(block) | AstStatementKind::If(_, block, None) => {
+            block.statements.iter().any(|statement| jump(statement, continues))
+        },
+
+        AstStatementKind::If(_, block, Some(else_block)) => {
+            block.statements.iter().chain(else_block.statements.iter()).any(|statement| jump(statement, continues))
+        },
+        _ => false
+    }
+}
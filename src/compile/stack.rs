@@ -0,0 +1,221 @@
+// local variables and temporaries live in a frame allocated on the heap, so that
+// every call and every event handler has its own, even when scripts which use them
+// are paused at the same time:
+//
+//   _heap: ... | size | slot 0 | slot 1 | ... | slot size - 1 | ...
+//                       ^ frame pointer
+//
+// a function with a frame is compiled to two custom blocks: the one that is called
+// allocates the frame, and passes a pointer to it to the one which runs the body.
+// event handlers keep the pointer in a variable of their own. frames are freed
+// when the function or handler returns.
+
+use super::*;
+
+/// Name of the argument which holds the frame pointer of a function. identifiers can't
+/// have spaces, so it can't be confused with a parameter
+pub const FRAME_ARG: &str = "frame pointer";
+
+// where a script keeps the pointer to its frame
+pub enum FramePointer {
+    // the custom block which runs the body of a function gets it as an argument
+    Argument,
+
+    // an event handler keeps it in a variable
+    Variable(String)
+}
+
+// a value which is read more than once
+pub enum Reused<'e> {
+    // lowered again each time it is read
+    Simple(&'e AstExpression),
+
+    // kept in a temporary
+    Slot(usize)
+}
+
+// what a name in an expression refers to
+pub enum Binding {
+    Local(usize),
+    Param(ArgumentType)
+}
+
+impl<'a> Compiler<'a> {
+    /// Finds the local variable or parameter a name refers to, innermost scope first
+    pub fn lookup(&self, name: &str) -> Option<Binding> {
+        for scope in self.scopes.iter().rev() {
+            if let Some((_, slot)) = scope.iter().rev().find(|(local, _)| local == name) {
+                return Some(Binding::Local(*slot));
+            }
+        }
+
+        self.params.iter()
+            .find(|arg| arg.name == name)
+            .map(|arg| Binding::Param(arg.arg_type))
+    }
+
+    // declare a local variable in the innermost scope
+    pub fn declare_local(&mut self, name: &str) -> usize {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        self.max_slots = self.max_slots.max(self.next_slot);
+
+        self.scopes.last_mut().unwrap().push((name.to_string(), slot));
+        slot
+    }
+
+    // get a slot for a value which is only needed until the end of the current statement
+    pub fn temp_slot(&mut self) -> usize {
+        let slot = self.next_slot + self.temps;
+        self.temps += 1;
+        self.max_slots = self.max_slots.max(slot + 1);
+        slot
+    }
+
+    // the pointer to the frame of the script being compiled
+    fn frame_pointer(&self) -> UserInput {
+        match self.frame.as_ref().expect("slot used by a script without a frame") {
+            FramePointer::Argument => reporter(Opcode::ArgumentReporterStringNumber(FRAME_ARG.to_string())),
+            FramePointer::Variable(name) => variable(name.clone())
+        }
+    }
+
+    // index of a slot in the heap: the frame pointer + slot
+    fn slot_index(&mut self, slot: usize) -> UserInput {
+        let fp = self.frame_pointer();
+
+        match slot {
+            0 => fp,
+            _ => reporter(Opcode::Add(fp, UserInput::new(Value::Number(slot as f64), None)))
+        }
+    }
+
+    pub fn read_slot(&mut self, slot: usize) -> UserInput {
+        let heap = self.heap();
+        let index = self.slot_index(slot);
+        reporter(Opcode::ItemOfList(index, heap))
+    }
+
+    pub fn write_slot(&mut self, slot: usize, value: UserInput) -> Opcode {
+        let heap = self.heap();
+        let index = self.slot_index(slot);
+        Opcode::ReplaceItemOfList(index, heap, value)
+    }
+
+    // keep the value of an expression in a temporary before the current statement,
+    // unless lowering it again gives the same value
+    pub fn reuse<'e>(&mut self, expr: &'e AstExpression) -> Result<Reused<'e>, CompileError> {
+        if simple(expr) {
+            return Ok(Reused::Simple(expr));
+        }
+
+        let value = self.value(expr)?;
+        let slot = self.temp_slot();
+        let opcode = self.write_slot(slot, value);
+        self.pre.push(Block::new(opcode));
+
+        Ok(Reused::Slot(slot))
+    }
+
+    pub fn reused(&mut self, value: &Reused) -> Result<UserInput, CompileError> {
+        match value {
+            Reused::Simple(expr) => self.value(expr),
+            Reused::Slot(slot) => Ok(self.read_slot(*slot))
+        }
+    }
+
+    // blocks which allocate a frame with the given number of slots,
+    // leaving the pointer to it in the return variable
+    pub fn alloc_frame(&mut self, slots: usize, script: &mut Script) {
+        let opcode = self.alloc_call(slots);
+        script.push(Block::new(opcode));
+    }
+
+    // blocks which free the frame of the script being compiled
+    pub fn free_frame(&mut self, script: &mut Script) {
+        let fp = self.frame_pointer();
+        let opcode = self.free(fp);
+        script.push(Block::new(opcode));
+    }
+
+    /// Does a script with this body need a frame?
+    pub fn needs_frame(&self, block: &AstBlock) -> bool {
+        block.statements.iter().any(|statement| match &statement.kind {
+            AstStatementKind::Local(..) => true,
+
+            // a call by itself doesn't need a temporary, but its arguments might
+            AstStatementKind::Call(call) |
+            AstStatementKind::Drop(AstExpression { kind: AstExpressionKind::Call(call), .. }) => {
+                call.args.iter().any(|arg| self.needs_temp(arg))
+            },
+
+            AstStatementKind::Assign(target, expr) => self.needs_temp(target) || self.needs_temp(expr),
+            AstStatementKind::Drop(expr) => self.needs_temp(expr),
+            AstStatementKind::Return(expr) => expr.as_ref().is_some_and(|expr| self.needs_temp(expr)),
+            AstStatementKind::Do(block) => self.needs_frame(block),
+
+            AstStatementKind::If(cond, block, Some(else_block)) => {
+                self.needs_temp(cond) || self.needs_frame(block) || self.needs_frame(else_block)
+            },
+
+            AstStatementKind::If(cond, block, None) => self.needs_temp(cond) || self.needs_frame(block),
+
+            // loops keep the flag for break and continue in the frame, and a repeat loop
+            // with a break keeps its counter there too
+            AstStatementKind::While(cond, block) | AstStatementKind::Repeat(cond, block) => {
+                self.needs_temp(cond) || block.statements.iter().any(loops::jumps) || self.needs_frame(block)
+            },
+
+            // the flag of a repeat-until loop also marks its first iteration
+            AstStatementKind::RepeatUntil(..) | AstStatementKind::For(..) => true,
+            AstStatementKind::Break | AstStatementKind::Continue => false
+        })
+    }
+
+    // does the expression call a function or allocate memory, so its result needs a temporary?
+    pub fn needs_temp(&self, expr: &AstExpression) -> bool {
+        match &expr.kind {
+            AstExpressionKind::Number(_) | AstExpressionKind::String(_) | AstExpressionKind::Boolean(_) |
+            AstExpressionKind::Null | AstExpressionKind::Variable(_) => false,
+
+            AstExpressionKind::Neg(v) | AstExpressionKind::Not(v) => self.needs_temp(v),
+
+            AstExpressionKind::Add(a, b) | AstExpressionKind::Sub(a, b) | AstExpressionKind::Mul(a, b) |
+            AstExpressionKind::Div(a, b) | AstExpressionKind::Mod(a, b) | AstExpressionKind::Join(a, b) | AstExpressionKind::And(a, b) | AstExpressionKind::Or(a, b) |
+            AstExpressionKind::Eq(a, b) | AstExpressionKind::Neq(a, b) | AstExpressionKind::Lt(a, b) |
+            AstExpressionKind::Gt(a, b) | AstExpressionKind::Le(a, b) | AstExpressionKind::Ge(a, b) => {
+                self.needs_temp(a) || self.needs_temp(b)
+            },
+
+            // operands of a power are read more than once, so any but a number or a variable is kept
+            AstExpressionKind::Pow(a, b) => !simple(a) || !simple(b) || self.needs_temp(a) || self.needs_temp(b),
+
+            AstExpressionKind::New(_) | AstExpressionKind::Array(_) => true,
+
+            // bounds checks keep the pointer and index in the frame
+            AstExpressionKind::Index(a, i) => self.options.bounds_check || self.needs_temp(a) || self.needs_temp(i),
+
+            AstExpressionKind::Call(call) => {
+                self.procedure(call).is_some() || call.args.iter().any(|arg| self.needs_temp(arg))
+            }
+        }
+    }
+}
+
+// can the expression be lowered more than once and give the same value each time?
+pub fn simple(expr: &AstExpression) -> bool {
+    match &expr.kind {
+        AstExpressionKind::Number(_) | AstExpressionKind::Variable(_) => true,
+        AstExpressionKind::Neg(v) => matches!(v.kind, AstExpressionKind::Number(_)),
+        _ => false
+    }
+}
+
+// a value slot holding a reporter block
+pub fn reporter(opcode: Opcode) -> UserInput {
+    UserInput::new(Value::String(String::new()), Some(Box::new(Block::new(opcode))))
+}
+
+pub fn variable(name: String) -> UserInput {
+    reporter(Opcode::Variable(name))
+}
@@ -0,0 +1,154 @@
+use std::io::IsTerminal;
+use crate::parse::Span;
+use crate::parse::parser::ParseError;
+use crate::compile::CompileError;
+
+// ANSI escape codes
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// An error to be reported to the user
+pub struct Diagnostic {
+    pub msg: String,
+    pub span: Option<Span>,
+    pub help: Option<String>
+}
+
+impl Diagnostic {
+    pub fn new(msg: String, span: Option<Span>) -> Self {
+        Self {
+            msg,
+            span,
+            help: None
+        }
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Self {
+        Self {
+            msg: err.msg,
+            span: err.span,
+            help: err.help
+        }
+    }
+}
+
+impl From<CompileError> for Diagnostic {
+    fn from(err: CompileError) -> Self {
+        Self {
+            msg: err.msg,
+            span: Some(err.span),
+            help: err.help
+        }
+    }
+}
+
+/// A source file that diagnostics can point into
+pub struct SourceFile<'a> {
+    pub path: &'a str,
+    pub text: &'a str
+}
+
+// the last character boundary of the string at or before the byte offset
+fn char_boundary(s: &str, offset: usize) -> usize {
+    let mut i = offset.min(s.len());
+
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+
+    i
+}
+
+/// Formats diagnostics in the style of rustc:
+///
+/// ```text
+/// error: unexpected )
+///  --> Sprite1.solar:2:18
+///   |
+/// 2 |     sprite.move(1 +)
+///   |                    ^
+/// ```
+pub struct Renderer {
+    color: bool
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    /// Creates a renderer which uses color if stdout is a terminal
+    pub fn new() -> Self {
+        Self {
+            color: std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+        }
+    }
+
+    // wrap text in an escape code if color is enabled
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn render(&self, diag: &Diagnostic, file: Option<&SourceFile>) -> String {
+        let mut out = format!("{}{}\n", self.paint(RED, "error"), self.paint(BOLD, &format!(": {}", diag.msg)));
+
+        // width of the line number column
+        let mut gutter = 0;
+
+        match (file, diag.span) {
+            (Some(file), Some(span)) => {
+                let line_str = span.line.to_string();
+                gutter = line_str.len();
+
+                // find the text of the line the span starts on
+                let start = char_boundary(file.text, span.start);
+                let line_start = file.text[..start].rfind('\n').map_or(0, |i| i + 1);
+                let line_text = file.text[line_start..].split('\n').next().unwrap_or("").trim_end_matches('\r');
+
+                // spans count bytes, so move their ends back onto the characters they fall in.
+                // the span is underlined up to the end of the line if it continues past it
+                let caret_start = char_boundary(line_text, start - line_start);
+                let caret_end = char_boundary(line_text, caret_start + (span.end - span.start));
+                let caret_len = line_text[caret_start..caret_end].chars().count().max(1);
+
+                out += &format!("{}{} {}:{}:{}\n", " ".repeat(gutter), self.paint(BLUE, "-->"), file.path, span.line, span.col);
+
+                // keep tabs so the carets line up with the source text
+                let padding: String = line_text[..caret_start].chars()
+                    .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+                    .collect();
+
+                let bar = self.paint(BLUE, "|");
+                out += &format!("{} {}\n", " ".repeat(gutter), bar);
+                out += &format!("{} {} {}\n", self.paint(BLUE, &line_str), bar, line_text);
+                out += &format!("{} {} {}{}\n", " ".repeat(gutter), bar, padding, self.paint(RED, &"^".repeat(caret_len)));
+            },
+
+            (Some(file), None) => {
+                out += &format!("{} {}\n", self.paint(BLUE, "-->"), file.path);
+            },
+
+            _ => ()
+        }
+
+        if let Some(help) = &diag.help {
+            out += &format!("{} {} {}\n", " ".repeat(gutter), self.paint(BLUE, "="), self.paint(BOLD, &format!("help: {}", help)));
+        }
+
+        out
+    }
+
+    pub fn print(&self, diag: &Diagnostic, file: Option<&SourceFile>) {
+        println!("{}", self.render(diag, file));
+    }
+}
@@ -1,76 +1,140 @@
-
-/*
 use std::env;
 use std::fs;
-pub mod app;
-
-fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    // get file path argument
-    let file_path = match args.get(1) {
-        None => {
-            println!("error: expected file path");
-            std::process::exit(1);
-        }
-        Some(v) => v,
-    };
-
-    // open file
-    let mut file = match fs::File::open(file_path) {
-        Ok(v) => v,
-        Err(e) => {
-            println!("error: could not open file: {}", e);
-            std::process::exit(1);
-        }
-    };
-
-    // run application
-    if let Err(e) = app::run(&mut file) {
-        println!("error: {}", e);
-        std::process::exit(1);
-    }
-}*/
-
+use std::path::{Path, PathBuf};
 use sb3::*;
-//use std::env;
+use diagnostic::{Diagnostic, SourceFile};
 
 pub mod sb3;
+pub mod parse;
+pub mod compile;
+pub mod diagnostic;
+
+const USAGE: &str = "usage: solar <file.solar>... [-o <output.sb3>] [-assets <dir>] [-bounds-check]";
 
 fn main() {
-    /*
-    for (i, arg) in env::args().enumerate() {
-        println!("arg {}: {}", i, arg);
+    let mut source_paths: Vec<String> = Vec::new();
+    let mut output_path = String::from("out.sb3");
+    let mut asset_dir: Option<PathBuf> = None;
+    let mut options = compile::Options::default();
+
+    // parse command-line arguments
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => match args.next() {
+                Some(v) => output_path = v,
+                None => {
+                    println!("error: expected output path after -o");
+                    std::process::exit(1);
+                }
+            },
+
+            "-assets" => match args.next() {
+                Some(v) => asset_dir = Some(PathBuf::from(v)),
+                None => {
+                    println!("error: expected asset directory after -assets");
+                    std::process::exit(1);
+                }
+            },
+
+            "-bounds-check" => options.bounds_check = true,
+
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+
+            _ => source_paths.push(arg)
+        }
     }
-    */
-    
-    let mut project = Project::new();
-    project.stage.add_costume(Costume::new("empty", "assets/empty_costume.svg", 240.0, 180.0));
-    let sprite = project.create_sprite("Sprite1");
-
-    let mut script = Script::new();
 
-    // when green flag clicked
-    script.push(Block::new(Opcode::WhenGreenFlagClicked()));
+    if source_paths.is_empty() {
+        println!("error: expected file path");
+        println!("{}", USAGE);
+        std::process::exit(1);
+    }
 
-    // move 10 steps 
-    script.push(Block::new(Opcode::MoveSteps(UserInput::new(Value::Number(10.0), None))));
+    let mut project = Project::new();
+    let mut compiler = compile::ProjectCompiler::new(&mut project, options);
+
+    let renderer = diagnostic::Renderer::new();
+    let mut error_count = 0;
+
+    // sources of the files given to the compiler, so that errors found at the end can be shown
+    let mut sources: Vec<(&String, String)> = Vec::new();
+
+    // each source file declares sprites, or is a sprite by itself
+    for path in source_paths.iter() {
+        // open file
+        let source = match fs::read_to_string(path) {
+            Ok(v) => v,
+            Err(e) => {
+                renderer.print(&Diagnostic::new(format!("could not open {}: {}", path, e), None), None);
+                error_count += 1;
+                continue;
+            }
+        };
+
+        let file = SourceFile {
+            path,
+            text: &source
+        };
+
+        let program = match parse::run(&source) {
+            Ok(v) => v,
+            Err(errors) => {
+                error_count += errors.len();
+
+                for err in errors {
+                    renderer.print(&err.into(), Some(&file));
+                }
+
+                continue;
+            }
+        };
+
+        // functions outside of a declaration go in a sprite named after the file
+        let name = match Path::new(path).file_stem().and_then(std::ffi::OsStr::to_str) {
+            Some(v) => v.to_string(),
+            None => path.clone()
+        };
+
+        // assets are found next to the source file unless a directory is given
+        let dir = match &asset_dir {
+            Some(v) => v.clone(),
+            None => Path::new(path).parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+
+        if let Err(errors) = compiler.file(&program, &name, &dir) {
+            error_count += errors.len();
+
+            for err in errors {
+                renderer.print(&err.into(), Some(&file));
+            }
+        }
 
-    // add script to sprite
-    sprite.obj.scripts.push(script);
+        sources.push((path, source));
+    }
 
-    // add costumes to sprite
-    sprite.obj.costumes.push(Costume::new("costume1", "assets/cat1.svg", 48.0, 50.0));
+    for (index, err) in compiler.finish() {
+        let (path, text) = &sources[index];
+        renderer.print(&err.into(), Some(&SourceFile { path, text }));
+        error_count += 1;
+    }
 
-    // add global "my variable" = 0
-    project.data.borrow_mut().vars.push(Variable::new("my variable", Value::Number(0.0)));
+    if error_count > 0 {
+        let msg = match error_count {
+            1 => "could not compile due to previous error".to_string(),
+            n => format!("could not compile due to {} previous errors", n)
+        };
 
-    project.save("out.sb3").expect("could not save");
+        renderer.print(&Diagnostic::new(msg, None), None);
+        std::process::exit(1);
+    }
 
-    match project.serialize() {
-        Ok(serialized) => {
-            println!("{}", json::stringify_pretty(serialized, 4));
-        },
-        Err(e) => println!("error: {}", e)
+    if let Err(e) = project.save(&output_path) {
+        renderer.print(&Diagnostic::new(format!("could not save {}: {}", output_path, e), None), None);
+        std::process::exit(1);
     }
 }
@@ -1,38 +1,216 @@
-use std::io::Read;
 use std::collections::VecDeque;
-use tokens::{Token, Keyword};
+pub use tokens::Span;
 
 pub mod tokens;
 pub mod parser;
 
-pub fn run(file: &mut std::fs::File) -> Result<(), std::io::Error> {
-    let mut file_contents = String::new();
-    file.read_to_string(&mut file_contents)?;
-
+/// Tokenizes and parses a source file into an AST, returning all syntax errors on failure
+pub fn run(source: &str) -> Result<parser::AstProgram, Vec<parser::ParseError>> {
     let mut tokens = Vec::new();
-    tokens::read_tokens(&file_contents, &mut tokens);
+    let lex_res = tokens::read_tokens(source, &mut tokens);
+    let parse_res = parser::parse_ast(&mut VecDeque::from(tokens));
 
-    for (_i, tok) in tokens.iter().enumerate() {
-        match tok {
-            Token::Symbol(v) => println!("SYMBOL {}", v),
-            Token::Keyword(v) => println!("KEYWORD {}", v),
-            Token::Identifier(v) => println!("IDENTIFIER {}", v),
-            Token::Number(v) => println!("NUMBER {}", v),
-            Token::String(v) => println!("STRING {}", v)
+    let mut errors = match (lex_res, parse_res) {
+        (Ok(()), Ok(program)) => return Ok(program),
+        (lex_res, parse_res) => {
+            let mut errors = lex_res.err().unwrap_or_default();
+            errors.extend(parse_res.err().unwrap_or_default());
+            errors
         }
+    };
+
+    // errors at the end of the file point to the last character
+    for err in errors.iter_mut() {
+        err.span.get_or_insert_with(|| Span::eof(source));
     }
 
-    let mut token_queue = VecDeque::from(tokens);
+    errors.sort_by_key(|err| err.span.map(|span| span.start));
+    Err(errors)
+}
 
-    match parser::parse_ast(&mut token_queue) {
-        Ok(ast) => {
-            println!("success");
-            dbg!(ast);
-        },
-        Err(err) => {
-            println!("error: {}", err.msg);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::{AstExpression, AstExpressionKind, AstStatementKind};
+
+    fn parse(source: &str) -> parser::AstProgram {
+        match run(source) {
+            Ok(program) => program,
+            Err(errors) => panic!("{}", errors[0].msg)
         }
-    };
+    }
+
+    // the messages and lines of the errors in a source file
+    fn errors(source: &str) -> Vec<(String, usize)> {
+        match run(source) {
+            Ok(_) => panic!("expected a syntax error"),
+            Err(errors) => errors.into_iter().map(|err| (err.msg, err.span.unwrap().line)).collect()
+        }
+    }
+
+    // write an expression with every operation in parentheses
+    fn show(expr: &AstExpression) -> String {
+        let binary = |a: &AstExpression, op: &str, b: &AstExpression| format!("({} {} {})", show(a), op, show(b));
+
+        match &expr.kind {
+            AstExpressionKind::Number(num) => num.to_string(),
+            AstExpressionKind::Variable(name) => name.clone(),
+            AstExpressionKind::Neg(v) => format!("(-{})", show(v)),
+            AstExpressionKind::Not(v) => format!("(not {})", show(v)),
+            AstExpressionKind::Add(a, b) => binary(a, "+", b),
+            AstExpressionKind::Sub(a, b) => binary(a, "-", b),
+            AstExpressionKind::Mul(a, b) => binary(a, "*", b),
+            AstExpressionKind::Div(a, b) => binary(a, "/", b),
+            AstExpressionKind::Mod(a, b) => binary(a, "%", b),
+            AstExpressionKind::Pow(a, b) => binary(a, "^", b),
+            AstExpressionKind::Join(a, b) => binary(a, "..", b),
+            AstExpressionKind::Eq(a, b) => binary(a, "==", b),
+            AstExpressionKind::Lt(a, b) => binary(a, "<", b),
+            AstExpressionKind::Gt(a, b) => binary(a, ">", b),
+            AstExpressionKind::And(a, b) => binary(a, "and", b),
+            AstExpressionKind::Or(a, b) => binary(a, "or", b),
+            kind => format!("{:?}", kind)
+        }
+    }
+
+    // parse the value of a local variable
+    fn expr(source: &str) -> String {
+        let program = parse(&format!("func main()\n\tlocal x = {}\nend", source));
+
+        match &program.functions[0].block.statements[0].kind {
+            AstStatementKind::Local(_, Some(value)) => show(value),
+            kind => panic!("expected a local, got {:?}", kind)
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(expr("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(expr("(1 + 2) * 3"), "((1 + 2) * 3)");
+        assert_eq!(expr("a or b and c == d"), "(a or (b and (c == d)))");
+        assert_eq!(expr("a .. b + c"), "(a .. (b + c))");
+        assert_eq!(expr("a < b .. c"), "(a < (b .. c))");
+        assert_eq!(expr("a * b % c"), "((a * b) % c)");
+        assert_eq!(expr("-a ^ 2"), "(-(a ^ 2))");
+        assert_eq!(expr("not a == b"), "((not a) == b)");
+    }
+
+    #[test]
+    fn associativity() {
+        assert_eq!(expr("1 - 2 - 3"), "((1 - 2) - 3)");
+        assert_eq!(expr("a / b / c"), "((a / b) / c)");
+        assert_eq!(expr("2 ^ 3 ^ 2"), "(2 ^ (3 ^ 2))");
+    }
+
+    #[test]
+    fn recovers_at_next_statement() {
+        let source = "func main()\n\tx = * 2\n\tsprite.say(1)\n\ty = )\n\tsprite.say(2)\nend";
+        let errors = errors(source);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].1, 2);
+        assert_eq!(errors[1].1, 4);
+    }
+
+    #[test]
+    fn recovers_inside_repeat_until() {
+        let source = "func main()\n\tx = * 2\n\trepeat\n\t\ty = )\n\t\tsprite.say(1)\n\tuntil x > 1\n\tz = )\nend";
+        let errors = errors(source);
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].1, 2);
+        assert_eq!(errors[1].1, 4);
+        assert_eq!(errors[2].1, 7);
+    }
+
+    #[test]
+    fn skips_repeat_until_while_recovering() {
+        let source = "func main()\n\tx = * repeat\n\t\tsprite.say(1)\n\tuntil x > 1\n\ty = )\nend";
+        let errors = errors(source);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].1, 2);
+        assert_eq!(errors[1].1, 5);
+    }
+
+    #[test]
+    fn recovers_in_later_functions() {
+        let source = "func a()\n\tx = = 1\nend\n\nfunc b()\n\tsprite.say(1)\nend\n\nfunc c()\n\ty = (\nend";
+        let errors = errors(source);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].1, 2);
+        assert_eq!(errors[1].1, 11);
+    }
 
-    Ok(())
+    #[test]
+    fn errors_at_end_of_file() {
+        let missing_end = errors("func main()\n\tsprite.say(1)\n");
+        assert_eq!(missing_end.len(), 1);
+        assert_eq!(missing_end[0].1, 3);
+
+        let unterminated = errors("func main()\n\tsprite.say(\"hi)\nend");
+        assert_eq!(unterminated[0].0, "unterminated string");
+        assert_eq!(unterminated[0].1, 2);
+    }
+
+    // the kind of the first statement of main
+    fn statement(source: &str) -> AstStatementKind {
+        let mut program = parse(&format!("func main()\n{}\nend", source));
+        program.functions.remove(0).block.statements.remove(0).kind
+    }
+
+    #[test]
+    fn repeat_with_count() {
+        for source in ["repeat 10 do sprite.move(1) end", "repeat n * 2: sprite.move(1)", "repeat\n\t10\ndo\n\tsprite.move(1)\nend"] {
+            match statement(source) {
+                AstStatementKind::Repeat(_, block) => assert_eq!(block.statements.len(), 1),
+                kind => panic!("expected a repeat loop, got {:?}", kind)
+            }
+        }
+    }
+
+    #[test]
+    fn repeat_until() {
+        for source in ["repeat\n\tx = x + 1\n\tsprite.move(1)\nuntil x > 5", "repeat x = x + 1 sprite.move(1) until x > 5"] {
+            match statement(source) {
+                AstStatementKind::RepeatUntil(block, cond) => {
+                    assert_eq!(block.statements.len(), 2);
+                    assert_eq!(show(&cond), "(x > 5)");
+                },
+                kind => panic!("expected a repeat-until loop, got {:?}", kind)
+            }
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let program = parse("# a sprite\nfunc main() # runs first\n\t# say something\n\tsprite.say(\"#1\")\nend # done");
+        let statements = &program.functions[0].block.statements;
+
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].span.line, 4);
+
+        // a # in a string isn't a comment
+        match &statements[0].kind {
+            AstStatementKind::Call(call) => assert!(matches!(&call.args[0].kind, AstExpressionKind::String(s) if s == "#1")),
+            kind => panic!("expected a call, got {:?}", kind)
+        }
+    }
+
+    #[test]
+    fn non_ascii_strings() {
+        let program = parse("func main()\n\tsprite.say(\"héllo → 世界\") sprite.say(1)\nend");
+        let statements = &program.functions[0].block.statements;
+
+        match &statements[0].kind {
+            AstStatementKind::Call(call) => assert!(matches!(&call.args[0].kind, AstExpressionKind::String(s) if s == "héllo → 世界")),
+            kind => panic!("expected a call, got {:?}", kind)
+        }
+
+        // spans are byte offsets, while columns count characters
+        let second = statements[1].span;
+        assert_eq!(second.start, "func main()\n\tsprite.say(\"héllo → 世界\") ".len());
+        assert_eq!(second.col, 27);
+    }
 }
@@ -1,7 +0,0 @@
-pub fn generate(ast: &AstProgram, output: &mut Vec<String>) {
-    // some sample program
-    output.push("const");
-    output.push("Hello, world!");
-    output.push("sys");
-    output.push("log");
-}
\ No newline at end of file
@@ -1,8 +1,8 @@
 use std::collections::VecDeque;
-use super::tokens::{Token, Keyword};
+use super::tokens::{Token, TokenKind, Keyword, Span};
 
 #[derive(Debug)]
-pub enum AstExpression {
+pub enum AstExpressionKind {
     Number(f64),
     String(String),
     Boolean(bool),
@@ -13,251 +13,1293 @@ pub enum AstExpression {
     Sub(Box<AstExpression>, Box<AstExpression>),
     Mul(Box<AstExpression>, Box<AstExpression>),
     Div(Box<AstExpression>, Box<AstExpression>),
+    Mod(Box<AstExpression>, Box<AstExpression>),
+    Pow(Box<AstExpression>, Box<AstExpression>),
     Join(Box<AstExpression>, Box<AstExpression>),
 
+    Eq(Box<AstExpression>, Box<AstExpression>),
+    Neq(Box<AstExpression>, Box<AstExpression>),
+    Lt(Box<AstExpression>, Box<AstExpression>),
+    Gt(Box<AstExpression>, Box<AstExpression>),
+    Le(Box<AstExpression>, Box<AstExpression>),
+    Ge(Box<AstExpression>, Box<AstExpression>),
+
     Not(Box<AstExpression>),
     And(Box<AstExpression>, Box<AstExpression>),
-    Or(Box<AstExpression>, Box<AstExpression>)
+    Or(Box<AstExpression>, Box<AstExpression>),
+
+    Variable(String),
+    Call(AstCall),
+
+    // <array>[<index>]
+    Index(Box<AstExpression>, Box<AstExpression>),
+
+    // new(<size>)
+    New(Box<AstExpression>),
+
+    // [<expr>, ...]
+    Array(Vec<AstExpression>)
 }
 
 #[derive(Debug)]
-pub enum AstStatement {
+pub struct AstExpression {
+    pub kind: AstExpressionKind,
+    pub span: Span
+}
+
+impl AstExpression {
+    fn new(kind: AstExpressionKind, span: Span) -> Self {
+        Self {
+            kind,
+            span
+        }
+    }
+
+    // create a binary operation spanning both operands
+    fn binary(op: fn(Box<AstExpression>, Box<AstExpression>) -> AstExpressionKind, a: AstExpression, b: AstExpression) -> Self {
+        let span = a.span.to(b.span);
+        Self::new(op(Box::new(a), Box::new(b)), span)
+    }
+}
+
+// a call to a function, optionally qualified by a module name
+// e.g. sprite.move(10)
+#[derive(Debug)]
+pub struct AstCall {
+    pub module: Option<String>,
+    pub name: String,
+    pub args: Vec<AstExpression>,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub enum AstStatementKind {
+    Call(AstCall),
     Drop(AstExpression),
-    Return(AstExpression),
+    Return(Option<AstExpression>),
+    Local(String, Option<AstExpression>),
+    Assign(AstExpression, AstExpression),
     Do(AstBlock),
-    If(AstExpression, AstBlock),
+    If(AstExpression, AstBlock, Option<AstBlock>),
     While(AstExpression, AstBlock),
-    // TODO For
-    // TODO Repeat
+    Repeat(AstExpression, AstBlock),
+    RepeatUntil(AstBlock, AstExpression),
+
+    // for <id> = <start>, <end> [, <step>] do <block>
+    For(String, AstExpression, AstExpression, Option<AstExpression>, AstBlock),
+
+    Break,
+    Continue
+}
+
+// for statements containing a block, the span only covers
+// the part before the block
+#[derive(Debug)]
+pub struct AstStatement {
+    pub kind: AstStatementKind,
+    pub span: Span
 }
 
 #[derive(Debug)]
 pub struct AstBlock {
-    statements: Vec<AstStatement>
+    pub statements: Vec<AstStatement>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AstType {
+    Any,
+    Number,
+    String,
+    Boolean
+}
+
+// <id> [: <type>]
+#[derive(Debug)]
+pub struct AstParam {
+    pub name: String,
+    pub param_type: AstType,
+    pub span: Span
 }
 
 #[derive(Debug)]
 pub struct AstFunc {
-    id: String,
-    block: AstBlock,    
+    pub id: String,
+    pub params: Vec<AstParam>,
+
+    // run without screen refresh
+    pub warp: bool,
+    pub block: AstBlock,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub enum AstEventKind {
+    Start,
+    Clicked,
+    Clone,
+    Key(String),
+    Broadcast(String),
+    Backdrop(String, Span), // the span of the name
+
+    // when the value goes above the threshold
+    Loudness(AstExpression),
+    Timer(AstExpression)
+}
+
+// on <event> do ... end
+#[derive(Debug)]
+pub struct AstEvent {
+    pub kind: AstEventKind,
+    pub block: AstBlock,
+    pub span: Span
+}
+
+// value of a property in a sprite or stage declaration
+#[derive(Debug)]
+pub enum AstValue {
+    Expr(AstExpression),
+    Table(Vec<AstProperty>, Span)
+}
+
+impl AstValue {
+    pub fn span(&self) -> Span {
+        match self {
+            AstValue::Expr(expr) => expr.span,
+            AstValue::Table(_, span) => *span
+        }
+    }
+}
+
+// <key> = <value>, or just <value> for entries in a table
+#[derive(Debug)]
+pub struct AstProperty {
+    pub key: Option<String>,
+    pub value: AstValue,
+    pub span: Span
+}
+
+#[derive(Debug)]
+pub enum AstObjectKind {
+    Sprite(String),
+    Stage
+}
+
+// sprite <id> { ... } or stage { ... }
+#[derive(Debug)]
+pub struct AstObject {
+    pub kind: AstObjectKind,
+    pub properties: Vec<AstProperty>,
+    pub functions: Vec<AstFunc>,
+    pub events: Vec<AstEvent>,
+    pub span: Span
 }
 
 #[derive(Debug)]
 pub struct AstProgram {
-    functions: Vec<AstFunc>
+    // functions and event handlers outside of any sprite declaration
+    pub functions: Vec<AstFunc>,
+    pub events: Vec<AstEvent>,
+    pub objects: Vec<AstObject>
 }
 
-// TODO line number, offset number
 pub struct ParseError {
-    pub msg: String
+    pub msg: String,
+
+    // None if the error is at the end of the file
+    pub span: Option<Span>,
+
+    // suggestion on how to fix the error
+    pub help: Option<String>
 }
 
 impl ParseError {
-    pub fn new(msg: String) -> Self {
+    pub fn new(msg: String, span: Option<Span>) -> Self {
         ParseError {
-            msg
+            msg,
+            span,
+            help: None
         }
     }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    pub fn unexpected(tok: &Token) -> Self {
+        ParseError::new(format!("unexpected {}", tok), Some(tok.span))
+    }
+
+    pub fn eof() -> Self {
+        ParseError::new("unexpected eof".to_string(), None)
+    }
 }
 
+// pop a token matching the pattern. if the token doesn't match,
+// it is left in the queue and an error is returned
 macro_rules! tokexpect {
-    ($tok:expr, $search:pat, $cmd:expr) => {
-        match $tok {
-            Some($search) => $cmd,
-            Some(tok) => return Err(ParseError::new(format!("unexpected {}", tok))),
-            None => return Err(ParseError::new("unexpected eof".to_string()))
+    ($tokens:expr, $search:pat, $cmd:expr) => {
+        match $tokens.pop_front() {
+            Some(Token { kind: $search, .. }) => $cmd,
+            Some(tok) => {
+                let err = ParseError::unexpected(&tok);
+                $tokens.push_front(tok);
+                return Err(err);
+            },
+            None => return Err(ParseError::eof())
         }
     };
+}
 
-    ($tok:expr, $search:pat, $cmd:block) => {
-        match $tok {
-            Some($search) => $block,
-            Some(tok) => return Err(ParseError::new(format!("unexpected {}", tok))),
-            None => return Err(ParseError::new("unexpected eof".to_string()))
-        }
+// get the kind of the next token without popping it
+fn peek(tokens: &VecDeque<Token>) -> Option<&TokenKind> {
+    tokens.front().map(|tok| &tok.kind)
+}
+
+// parse a call after its first identifier has been popped
+// <id> [. <id>] ( [<expr> {, <expr>}] )
+fn parse_call(first_id: String, first_span: Span, tokens: &mut VecDeque<Token>) -> Result<AstCall, ParseError> {
+    let (module, name) = match peek(tokens) {
+        Some(TokenKind::Symbol(".")) => {
+            tokens.pop_front(); // pop dot
+            let name = tokexpect!(tokens, TokenKind::Identifier(v), v);
+            (Some(first_id), name)
+        },
+
+        _ => (None, first_id)
     };
+
+    tokexpect!(tokens, TokenKind::Symbol("("), {}); // pop open paren
+
+    let mut args = Vec::new();
+    let end_span;
+
+    // if the argument list is empty
+    if let Some(TokenKind::Symbol(")")) = peek(tokens) {
+        end_span = tokens.pop_front().unwrap().span;
+    } else {
+        loop {
+            args.push(parse_expr(tokens)?);
+
+            match tokens.pop_front() {
+                Some(Token { kind: TokenKind::Symbol(","), .. }) => continue,
+                Some(Token { kind: TokenKind::Symbol(")"), span }) => {
+                    end_span = span;
+                    break;
+                },
+                Some(tok) => {
+                    let err = ParseError::unexpected(&tok);
+                    tokens.push_front(tok);
+                    return Err(err);
+                },
+                None => return Err(ParseError::eof())
+            }
+        }
+    }
+
+    Ok(AstCall {
+        module,
+        name,
+        args,
+        span: first_span.to(end_span)
+    })
 }
 
+// <primary> {[<expr>]}
 fn parse_factor(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
-    match tokens.pop_front() {
-        None => return Err(ParseError::new("unexpected eof".to_string())),
+    let mut factor = parse_primary(tokens)?;
+
+    while let Some(TokenKind::Symbol("[")) = peek(tokens) {
+        tokens.pop_front(); // pop open bracket
+        let index = parse_expr(tokens)?;
+        let end_span = tokens.front().map(|tok| tok.span);
+        tokexpect!(tokens, TokenKind::Symbol("]"), {});
+
+        let span = factor.span.to(end_span.unwrap());
+        factor = AstExpression::new(AstExpressionKind::Index(Box::new(factor), Box::new(index)), span);
+    }
 
+    Ok(factor)
+}
+
+fn parse_primary(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
+    let tok = match tokens.pop_front() {
+        Some(v) => v,
+        None => return Err(ParseError::eof())
+    };
+
+    let span = tok.span;
+
+    match tok.kind {
         // if token is the open paren
-        Some(Token::Symbol('(')) => {
-            let exp = parse_expr(tokens); // parse expression inside parens
-            tokexpect!(tokens.pop_front(), Token::Symbol(')'), {});
-            return exp;
-        },
+        TokenKind::Symbol("(") => {
+            let mut exp = parse_expr(tokens)?; // parse expression inside parens
+            let end_span = tokens.front().map(|tok| tok.span);
+            tokexpect!(tokens, TokenKind::Symbol(")"), {});
 
-        // if token is the unary negation operator
-        Some(Token::Symbol('-')) => {
-            return Ok(AstExpression::Neg(Box::new(parse_factor(tokens)?)));
+            // include the parens in the span
+            exp.span = span.to(end_span.unwrap());
+            Ok(exp)
         },
 
-        // if token is the not operator
-        Some(Token::Keyword(Keyword::Not)) => return Ok(AstExpression::Not(Box::new(parse_factor(tokens)?))),
-        
         // if token is a number
-        Some(Token::Number(num)) => return Ok(AstExpression::Number(num)),
+        TokenKind::Number(num) => Ok(AstExpression::new(AstExpressionKind::Number(num), span)),
 
         // if token is a string
-        Some(Token::String(string)) => return Ok(AstExpression::String(string)),
+        TokenKind::String(string) => Ok(AstExpression::new(AstExpressionKind::String(string), span)),
 
         // if token is a boolean
-        Some(Token::Keyword(Keyword::True)) => return Ok(AstExpression::Boolean(true)),
-        Some(Token::Keyword(Keyword::False)) => return Ok(AstExpression::Boolean(false)),
+        TokenKind::Keyword(Keyword::True) => Ok(AstExpression::new(AstExpressionKind::Boolean(true), span)),
+        TokenKind::Keyword(Keyword::False) => Ok(AstExpression::new(AstExpressionKind::Boolean(false), span)),
 
         // if token is null
-        Some(Token::Keyword(Keyword::Null)) => return Ok(AstExpression::Null),
+        TokenKind::Keyword(Keyword::Null) => Ok(AstExpression::new(AstExpressionKind::Null, span)),
+
+        // new(<size>)
+        TokenKind::Keyword(Keyword::New) => {
+            tokexpect!(tokens, TokenKind::Symbol("("), {});
+            let size = parse_expr(tokens)?;
+            let end_span = tokens.front().map(|tok| tok.span);
+            tokexpect!(tokens, TokenKind::Symbol(")"), {});
+
+            Ok(AstExpression::new(AstExpressionKind::New(Box::new(size)), span.to(end_span.unwrap())))
+        },
+
+        // [ [<expr> {, <expr>}] ]
+        TokenKind::Symbol("[") => {
+            let mut items = Vec::new();
+
+            let end_span = loop {
+                if let Some(Token { kind: TokenKind::Symbol("]"), span }) = tokens.front() {
+                    let span = *span;
+                    tokens.pop_front();
+                    break span;
+                }
+
+                items.push(parse_expr(tokens)?);
+
+                match tokens.front() {
+                    Some(Token { kind: TokenKind::Symbol(","), .. }) => {
+                        tokens.pop_front();
+                    },
+                    Some(Token { kind: TokenKind::Symbol("]"), .. }) => (),
+                    Some(tok) => return Err(ParseError::unexpected(tok)),
+                    None => return Err(ParseError::eof())
+                }
+            };
+
+            Ok(AstExpression::new(AstExpressionKind::Array(items), span.to(end_span)))
+        },
+
+        // if token is an identifier, it is either a variable or the start of a call
+        TokenKind::Identifier(id) => match peek(tokens) {
+            Some(TokenKind::Symbol("(" | ".")) => {
+                let call = parse_call(id, span, tokens)?;
+                let span = call.span;
+                Ok(AstExpression::new(AstExpressionKind::Call(call), span))
+            },
+
+            _ => Ok(AstExpression::new(AstExpressionKind::Variable(id), span))
+        },
 
         // unexpected token
-        Some(tok) => return Err(ParseError::new(format!("unexpected {}", tok)))
+        kind => {
+            let tok = Token { kind, span };
+            let err = ParseError::unexpected(&tok);
+            tokens.push_front(tok);
+            Err(err)
+        }
     }
 }
 
+// -<unary>
+// not <unary>
+// <power>
+fn parse_unary(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
+    let kind: fn(Box<AstExpression>) -> AstExpressionKind = match tokens.front() {
+        Some(Token { kind: TokenKind::Symbol("-"), .. }) => AstExpressionKind::Neg,
+        Some(Token { kind: TokenKind::Keyword(Keyword::Not), .. }) => AstExpressionKind::Not,
+        _ => return parse_power(tokens)
+    };
+
+    let span = tokens.pop_front().unwrap().span;
+    let operand = parse_unary(tokens)?;
+    let span = span.to(operand.span);
+    Ok(AstExpression::new(kind(Box::new(operand)), span))
+}
+
+// <factor> [^ <unary>]
+// exponents bind tighter than a unary operator on their left, and group from the right
+fn parse_power(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
+    let base = parse_factor(tokens)?;
+
+    if let Some(TokenKind::Symbol("^")) = peek(tokens) {
+        tokens.pop_front(); // pop caret
+        return Ok(AstExpression::binary(AstExpressionKind::Pow, base, parse_unary(tokens)?));
+    }
+
+    Ok(base)
+}
+
 fn parse_term(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
-    let mut factor = parse_factor(tokens)?;
+    let mut factor = parse_unary(tokens)?;
 
     // check next token, but don't pop it off yet
-    'outer: loop {
-        match tokens.get(0) {
-            Some(Token::Symbol('*')) => {
-                tokens.pop_front(); // pop multiply symbol
-                factor = AstExpression::Mul(Box::new(factor), Box::new(parse_factor(tokens)?));
-            },
-
-            Some(Token::Symbol('/')) => {
-                tokens.pop_front(); // pop division symbol
-                factor = AstExpression::Div(Box::new(factor), Box::new(parse_factor(tokens)?));
-            }
+    loop {
+        let op = match peek(tokens) {
+            Some(TokenKind::Symbol("*")) => AstExpressionKind::Mul,
+            Some(TokenKind::Symbol("/")) => AstExpressionKind::Div,
+            Some(TokenKind::Symbol("%")) => AstExpressionKind::Mod,
+            _ => break
+        };
 
-            _ => break 'outer
-        }
+        tokens.pop_front(); // pop operator
+        factor = AstExpression::binary(op, factor, parse_unary(tokens)?);
     }
 
     Ok(factor)
 }
 
-fn parse_expr(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
+fn parse_sum(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
     let mut term = parse_term(tokens)?;
 
-    // check next token, but don't pop it off yet
-    'outer: loop {
-        match tokens.get(0) {
-            Some(Token::Symbol('+')) => {
-                tokens.pop_front(); // pop plus symbol
-                term = AstExpression::Add(Box::new(term), Box::new(parse_term(tokens)?));
+    loop {
+        let op = match peek(tokens) {
+            Some(TokenKind::Symbol("+")) => AstExpressionKind::Add,
+            Some(TokenKind::Symbol("-")) => AstExpressionKind::Sub,
+            _ => break
+        };
+
+        tokens.pop_front(); // pop operator
+        term = AstExpression::binary(op, term, parse_term(tokens)?);
+    }
+
+    Ok(term)
+}
+
+// <sum> [.. <concat>]
+fn parse_concat(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
+    let sum = parse_sum(tokens)?;
+
+    if let Some(TokenKind::Symbol("..")) = peek(tokens) {
+        tokens.pop_front(); // pop join operator
+        return Ok(AstExpression::binary(AstExpressionKind::Join, sum, parse_concat(tokens)?));
+    }
+
+    Ok(sum)
+}
+
+fn parse_comparison(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
+    let mut operand = parse_concat(tokens)?;
+
+    loop {
+        let op = match peek(tokens) {
+            Some(TokenKind::Symbol("==")) => AstExpressionKind::Eq,
+            Some(TokenKind::Symbol("!=")) => AstExpressionKind::Neq,
+            Some(TokenKind::Symbol("<")) => AstExpressionKind::Lt,
+            Some(TokenKind::Symbol(">")) => AstExpressionKind::Gt,
+            Some(TokenKind::Symbol("<=")) => AstExpressionKind::Le,
+            Some(TokenKind::Symbol(">=")) => AstExpressionKind::Ge,
+            _ => break
+        };
+
+        tokens.pop_front(); // pop operator
+        operand = AstExpression::binary(op, operand, parse_concat(tokens)?);
+    }
+
+    Ok(operand)
+}
+
+fn parse_and(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
+    let mut operand = parse_comparison(tokens)?;
+
+    while let Some(TokenKind::Keyword(Keyword::And)) = peek(tokens) {
+        tokens.pop_front(); // pop and
+        operand = AstExpression::binary(AstExpressionKind::And, operand, parse_comparison(tokens)?);
+    }
+
+    Ok(operand)
+}
+
+// operators from lowest to highest precedence:
+//   or
+//   and
+//   == != < > <= >=
+//   ..
+//   + -
+//   * / %
+//   unary - and not
+//   ^
+//   [] indexing
+fn parse_expr(tokens: &mut VecDeque<Token>) -> Result<AstExpression, ParseError> {
+    let mut operand = parse_and(tokens)?;
+
+    while let Some(TokenKind::Keyword(Keyword::Or)) = peek(tokens) {
+        tokens.pop_front(); // pop or
+        operand = AstExpression::binary(AstExpressionKind::Or, operand, parse_and(tokens)?);
+    }
+
+    Ok(operand)
+}
+
+// can this token begin a statement?
+fn starts_statement(kind: &TokenKind) -> bool {
+    matches!(kind,
+        TokenKind::Identifier(_) |
+        TokenKind::Keyword(Keyword::Return) |
+        TokenKind::Keyword(Keyword::Drop) |
+        TokenKind::Keyword(Keyword::Local) |
+        TokenKind::Keyword(Keyword::Do) |
+        TokenKind::Keyword(Keyword::If) |
+        TokenKind::Keyword(Keyword::While) |
+        TokenKind::Keyword(Keyword::Repeat) |
+        TokenKind::Keyword(Keyword::For) |
+        TokenKind::Keyword(Keyword::Break) |
+        TokenKind::Keyword(Keyword::Continue)
+    )
+}
+
+// does a count follow a repeat keyword? a count is an expression followed by "do" or ":",
+// so anything else is the body of a repeat-until loop
+fn repeat_count_follows(tokens: &VecDeque<Token>) -> bool {
+    // the count can't go past the first token which could end it
+    let mut ahead: VecDeque<Token> = VecDeque::new();
+
+    for tok in tokens.iter() {
+        ahead.push_back(tok.clone());
+
+        if matches!(tok.kind, TokenKind::Keyword(Keyword::Do | Keyword::Until | Keyword::End) | TokenKind::Symbol(":")) {
+            break;
+        }
+    }
+
+    parse_expr(&mut ahead).is_ok() && matches!(peek(&ahead), Some(TokenKind::Keyword(Keyword::Do) | TokenKind::Symbol(":")))
+}
+
+// after a syntax error in a statement on the given line, skip tokens until
+// the next statement that begins on a later line, or the end of the block.
+// blocks opened while skipping are skipped as a whole.
+//
+// returns false if parsing can't resume in this block, because the
+// end of the file or the next function was reached
+fn synchronize(tokens: &mut VecDeque<Token>, mut line: usize) -> bool {
+    let mut depth = 0;
+
+    while let Some(tok) = tokens.front() {
+        match tok.kind {
+            TokenKind::Keyword(Keyword::Func | Keyword::On) | TokenKind::Symbol("}") => return false,
+            TokenKind::Keyword(Keyword::End | Keyword::Until | Keyword::Else | Keyword::Elseif) if depth == 0 => return true,
+
+            // the then of an elseif doesn't start another block
+            TokenKind::Keyword(Keyword::Elseif) => depth -= 1,
+            TokenKind::Keyword(Keyword::End) => depth -= 1,
+            TokenKind::Keyword(Keyword::Then) | TokenKind::Keyword(Keyword::Do) => depth += 1,
+
+            // the condition after until is on the same line, and isn't a statement
+            TokenKind::Keyword(Keyword::Until) => {
+                depth -= 1;
+                line = tok.span.line;
             },
 
-            Some(Token::Symbol('-')) => {
-                tokens.pop_front(); // pop minus sign
-                term = AstExpression::Sub(Box::new(term), Box::new(parse_term(tokens)?));
+            ref kind if depth == 0 && tok.span.line > line && starts_statement(kind) => return true,
+
+            // a repeat with a count opens its block with do, and one without is closed by until
+            TokenKind::Keyword(Keyword::Repeat) => {
+                tokens.pop_front();
+
+                if !repeat_count_follows(tokens) {
+                    depth += 1;
+                }
+
+                continue;
             },
 
-            // TODO join
+            _ => ()
+        }
+
+        tokens.pop_front();
+    }
+
+    false
+}
+
+fn parse_statement(tokens: &mut VecDeque<Token>, errors: &mut Vec<ParseError>) -> Result<AstStatement, ParseError> {
+    // pop token
+    let tok = match tokens.pop_front() {
+        Some(v) => v,
+        None => return Err(ParseError::eof())
+    };
+
+    let span = tok.span;
+
+    match tok.kind {
+        // return [<expr>]
+        TokenKind::Keyword(Keyword::Return) => {
+            // the value must be on the same line, and return must be the last statement of a block
+            let expr = match tokens.front() {
+                Some(Token { kind: TokenKind::Keyword(Keyword::End | Keyword::Else | Keyword::Elseif | Keyword::Until), .. }) => None,
+                Some(tok) if tok.span.line == span.line => Some(parse_expr(tokens)?),
+                _ => None
+            };
+
+            Ok(AstStatement {
+                span: expr.as_ref().map_or(span, |expr| span.to(expr.span)),
+                kind: AstStatementKind::Return(expr)
+            })
+        },
+
+        // drop <expr>
+        TokenKind::Keyword(Keyword::Drop) => {
+            let expr = parse_expr(tokens)?;
+            let span = span.to(expr.span);
+
+            Ok(AstStatement {
+                kind: AstStatementKind::Drop(expr),
+                span
+            })
+        },
+
+        // if <expr> then <block>
+        // if <expr>: <statement>
+        TokenKind::Keyword(Keyword::If) => parse_if(span, tokens, errors),
+
+        // while <expr> do <block>
+        TokenKind::Keyword(Keyword::While) => {
+            let cond = parse_expr(tokens)?;
+            let span = span.to(cond.span);
+            let block = parse_body(tokens, errors, Keyword::Do)?;
 
-            _ => break 'outer
+            Ok(AstStatement {
+                kind: AstStatementKind::While(cond, block),
+                span
+            })
+        },
+
+        // repeat <expr> do <block>
+        // repeat <statements> until <expr>
+        TokenKind::Keyword(Keyword::Repeat) if repeat_count_follows(tokens) => {
+            let count = parse_expr(tokens)?;
+            let span = span.to(count.span);
+            let block = parse_body(tokens, errors, Keyword::Do)?;
+
+            Ok(AstStatement {
+                kind: AstStatementKind::Repeat(count, block),
+                span
+            })
+        },
+
+        TokenKind::Keyword(Keyword::Repeat) => {
+            let (block, _) = parse_statements(tokens, errors, &[Keyword::Until])?;
+            let cond = parse_expr(tokens)?;
+
+            Ok(AstStatement {
+                kind: AstStatementKind::RepeatUntil(block, cond),
+                span
+            })
+        },
+
+        // for <id> = <expr>, <expr> [, <expr>] do <block>
+        TokenKind::Keyword(Keyword::For) => {
+            let id = tokexpect!(tokens, TokenKind::Identifier(v), v);
+            tokexpect!(tokens, TokenKind::Symbol("="), {});
+            let start = parse_expr(tokens)?;
+            tokexpect!(tokens, TokenKind::Symbol(","), {});
+            let end = parse_expr(tokens)?;
+            let mut span = span.to(end.span);
+
+            let step = match peek(tokens) {
+                Some(TokenKind::Symbol(",")) => {
+                    tokens.pop_front(); // pop comma
+                    let expr = parse_expr(tokens)?;
+                    span = span.to(expr.span);
+                    Some(expr)
+                },
+                _ => None
+            };
+
+            let block = parse_body(tokens, errors, Keyword::Do)?;
+
+            Ok(AstStatement {
+                kind: AstStatementKind::For(id, start, end, step, block),
+                span
+            })
+        },
+
+        TokenKind::Keyword(Keyword::Break) => Ok(AstStatement { kind: AstStatementKind::Break, span }),
+        TokenKind::Keyword(Keyword::Continue) => Ok(AstStatement { kind: AstStatementKind::Continue, span }),
+
+        // local <id> [= <expr>]
+        TokenKind::Keyword(Keyword::Local) => {
+            let id_span = tokens.front().map(|tok| tok.span);
+            let id = tokexpect!(tokens, TokenKind::Identifier(v), v);
+            let mut span = span.to(id_span.unwrap());
+
+            let value = match peek(tokens) {
+                Some(TokenKind::Symbol("=")) => {
+                    tokens.pop_front(); // pop equals sign
+                    let expr = parse_expr(tokens)?;
+                    span = span.to(expr.span);
+                    Some(expr)
+                },
+                _ => None
+            };
+
+            Ok(AstStatement {
+                kind: AstStatementKind::Local(id, value),
+                span
+            })
+        },
+
+        // do <block>
+        TokenKind::Keyword(Keyword::Do) => {
+            let block = parse_block(tokens, errors)?;
+
+            Ok(AstStatement {
+                kind: AstStatementKind::Do(block),
+                span
+            })
+        },
+
+        // <id> = <expr>
+        // <id>[<expr>]... = <expr>
+        TokenKind::Identifier(id) if matches!(peek(tokens), Some(TokenKind::Symbol("=" | "["))) => {
+            tokens.push_front(Token { kind: TokenKind::Identifier(id), span });
+            let target = parse_factor(tokens)?;
+            tokexpect!(tokens, TokenKind::Symbol("="), {});
+            let expr = parse_expr(tokens)?;
+            let span = span.to(expr.span);
+
+            Ok(AstStatement {
+                kind: AstStatementKind::Assign(target, expr),
+                span
+            })
+        },
+
+        // <call>
+        TokenKind::Identifier(id) => {
+            let call = parse_call(id, span, tokens)?;
+            let span = call.span;
+
+            Ok(AstStatement {
+                kind: AstStatementKind::Call(call),
+                span
+            })
         }
+
+        kind => Err(ParseError::new(format!("expected statement, got \"{}\"", kind), Some(span))),
+    }
+}
+
+// if <expr> then <block> {elseif <expr> then <block>} [else <block>] end
+// if <expr>: <statement>
+fn parse_if(span: Span, tokens: &mut VecDeque<Token>, errors: &mut Vec<ParseError>) -> Result<AstStatement, ParseError> {
+    let cond = parse_expr(tokens)?;
+    let span = span.to(cond.span);
+
+    if let Some(TokenKind::Symbol(":")) = peek(tokens) {
+        let block = parse_block(tokens, errors)?;
+
+        return Ok(AstStatement {
+            kind: AstStatementKind::If(cond, block, None),
+            span
+        });
+    }
+
+    tokexpect!(tokens, TokenKind::Keyword(Keyword::Then), {});
+    let (block, end) = parse_statements(tokens, errors, &[Keyword::End, Keyword::Else, Keyword::Elseif])?;
+
+    // an elseif is an if statement by itself in the else block
+    let else_block = match end {
+        Keyword::Elseif => {
+            let span = tokens.front().map_or(span, |tok| tok.span);
+
+            Some(AstBlock {
+                statements: vec![parse_if(span, tokens, errors)?]
+            })
+        },
+
+        Keyword::Else => Some(parse_block(tokens, errors)?),
+        _ => None
+    };
+
+    Ok(AstStatement {
+        kind: AstStatementKind::If(cond, block, else_block),
+        span
+    })
+}
+
+// : <statement>
+// <keyword> <block>
+fn parse_body(tokens: &mut VecDeque<Token>, errors: &mut Vec<ParseError>, keyword: Keyword) -> Result<AstBlock, ParseError> {
+    if let Some(TokenKind::Symbol(":")) = peek(tokens) {
+        return parse_block(tokens, errors);
+    }
+
+    match tokens.front() {
+        Some(Token { kind: TokenKind::Keyword(kw), .. }) if *kw == keyword => {
+            tokens.pop_front();
+        },
+        Some(tok) => return Err(ParseError::unexpected(tok)),
+        None => return Err(ParseError::eof())
+    }
+
+    parse_block(tokens, errors)
+}
+
+// parse statements until the "end" keyword.
+// if the first token is a ":", the block only contains the statement after it.
+// syntax errors in statements are added to `errors` and skipped over;
+// an Err is only returned if the rest of the block can't be parsed
+fn parse_block(tokens: &mut VecDeque<Token>, errors: &mut Vec<ParseError>) -> Result<AstBlock, ParseError> {
+    if let Some(TokenKind::Symbol(":")) = peek(tokens) {
+        tokens.pop_front(); // pop colon
+
+        return Ok(AstBlock {
+            statements: vec![parse_statement(tokens, errors)?]
+        });
     }
 
-    return Ok(term);
+    parse_statements(tokens, errors, &[Keyword::End]).map(|(block, _)| block)
 }
 
-fn parse_block(tokens: &mut VecDeque<Token>) -> Result<AstBlock, ParseError> {
+// parse statements until one of the given keywords, returning the one that ended the block
+fn parse_statements(tokens: &mut VecDeque<Token>, errors: &mut Vec<ParseError>, ends: &[Keyword]) -> Result<(AstBlock, Keyword), ParseError> {
     let mut statements: Vec<AstStatement> = Vec::new();
+    let end = ends[0];
+
+    let last = loop {
+        match tokens.front() {
+            // "end" keyword marks end of block
+            Some(Token { kind: TokenKind::Keyword(kw), .. }) if ends.contains(kw) => {
+                let kw = *kw;
+                tokens.pop_front();
+                break kw;
+            },
+
+            // functions can't be nested, so the block must be missing its end
+            Some(Token { kind: TokenKind::Keyword(kw @ (Keyword::Func | Keyword::On)), span }) => {
+                return Err(ParseError::new(format!("unexpected {}", kw), Some(*span))
+                    .with_help(&format!("add \"{}\" to close the previous block", end)));
+            },
+
+            // blocks can't contain braces, so this must be the end of a declaration
+            Some(Token { kind: TokenKind::Symbol("}"), span }) => {
+                return Err(ParseError::new("unexpected }".to_string(), Some(*span))
+                    .with_help(&format!("add \"{}\" to close the previous block", end)));
+            },
+
+            // if reached eof
+            None => return Err(ParseError::eof().with_help(&format!("add \"{}\" to close the block", end))),
+
+            _ => match parse_statement(tokens, errors) {
+                Ok(statement) => statements.push(statement),
+                Err(err) => {
+                    // errors at the end of the file can't be recovered from
+                    let line = match err.span {
+                        Some(span) => span.line,
+                        None => return Err(err)
+                    };
+
+                    if !synchronize(tokens, line) {
+                        return Err(err);
+                    }
+
+                    errors.push(err);
+                }
+            }
+        }
+    };
+
+    Ok((AstBlock { statements }, last))
+}
+
+// func <id>() <block>
+fn parse_func(tokens: &mut VecDeque<Token>, errors: &mut Vec<ParseError>) -> Result<AstFunc, ParseError> {
+    let start_span = tokens.front().map(|tok| tok.span);
+
+    // warp func <id>(...)
+    let warp = match peek(tokens) {
+        Some(TokenKind::Identifier(v)) if v == "warp" => {
+            tokens.pop_front();
+            true
+        },
+        _ => false
+    };
+
+    tokexpect!(tokens, TokenKind::Keyword(Keyword::Func), {});
+
+    // pop an identifier
+    let id_span = tokens.front().map(|tok| tok.span);
+    let func_id = tokexpect!(tokens, TokenKind::Identifier(v), v);
+
+    tokexpect!(tokens, TokenKind::Symbol("("), {}); // pop open paren
+    let params = parse_params(tokens)?;
+
+    let block = parse_block(tokens, errors)?;
+
+    Ok(AstFunc {
+        id: func_id,
+        params,
+        warp,
+        block,
+        span: start_span.unwrap().to(id_span.unwrap())
+    })
+}
+
+// is the next token the start of a function?
+fn at_func(tokens: &VecDeque<Token>) -> bool {
+    match (tokens.front().map(|tok| &tok.kind), tokens.get(1).map(|tok| &tok.kind)) {
+        (Some(TokenKind::Keyword(Keyword::Func)), _) => true,
+        (Some(TokenKind::Identifier(v)), Some(TokenKind::Keyword(Keyword::Func))) => v == "warp",
+        _ => false
+    }
+}
+
+// parse parameters after the open paren, up to and including the closed paren
+// [<id> [: <type>] {, <id> [: <type>]}] )
+fn parse_params(tokens: &mut VecDeque<Token>) -> Result<Vec<AstParam>, ParseError> {
+    let mut params = Vec::new();
+
+    if let Some(TokenKind::Symbol(")")) = peek(tokens) {
+        tokens.pop_front();
+        return Ok(params);
+    }
 
-    // TODO
-    // if first token is a ":", then block only contains one statement
-    // otherwise, continue until "end" keyword is reached
     loop {
-        // pop token
+        let name_span = tokens.front().map(|tok| tok.span);
+        let name = tokexpect!(tokens, TokenKind::Identifier(v), v);
+        let mut span = name_span.unwrap();
+        let mut param_type = AstType::Any;
+
+        if let Some(TokenKind::Symbol(":")) = peek(tokens) {
+            tokens.pop_front(); // pop colon
+
+            let type_span = tokens.front().map(|tok| tok.span);
+            let type_name = tokexpect!(tokens, TokenKind::Identifier(v), v);
+            span = span.to(type_span.unwrap());
+
+            param_type = match type_name.as_str() {
+                "any" => AstType::Any,
+                "num" => AstType::Number,
+                "str" => AstType::String,
+                "bool" => AstType::Boolean,
+                _ => return Err(ParseError::new(format!("unknown type \"{}\"", type_name), type_span)
+                    .with_help("expected any, num, str or bool"))
+            };
+        }
+
+        params.push(AstParam {
+            name,
+            param_type,
+            span
+        });
+
         match tokens.pop_front() {
-            // if token exists
+            Some(Token { kind: TokenKind::Symbol(","), .. }) => continue,
+            Some(Token { kind: TokenKind::Symbol(")"), .. }) => break,
             Some(tok) => {
-                match tok {
-                    // "end" keyword marks end of block
-                    Token::Keyword(Keyword::End) => {
-                        break;
-                    },
+                let err = ParseError::unexpected(&tok);
+                tokens.push_front(tok);
+                return Err(err);
+            },
+            None => return Err(ParseError::eof())
+        }
+    }
 
-                    // return <expr>
-                    Token::Keyword(Keyword::Return) => {
-                        let statement = AstStatement::Return(match parse_expr(tokens) {
-                            Ok(v) => v,
-                            Err(e) => return Err(e),
-                        });
+    Ok(params)
+}
 
-                        statements.push(statement);
-                    },
+// on <event> do <block>
+fn parse_event(tokens: &mut VecDeque<Token>, errors: &mut Vec<ParseError>) -> Result<AstEvent, ParseError> {
+    let on_span = tokens.front().map(|tok| tok.span);
+    tokexpect!(tokens, TokenKind::Keyword(Keyword::On), {});
 
-                    // drop <expr>
-                    Token::Keyword(Keyword::Drop) => {
-                        let statement = AstStatement::Drop(match parse_expr(tokens) {
-                            Ok(v) => v,
-                            Err(e) => return Err(e),
-                        });
+    let event_span = tokens.front().map(|tok| tok.span);
+    let event = tokexpect!(tokens, TokenKind::Identifier(v), v);
+    let mut span = on_span.unwrap().to(event_span.unwrap());
 
-                        statements.push(statement);
-                    },
+    let kind = match event.as_str() {
+        "start" => AstEventKind::Start,
+        "clicked" => AstEventKind::Clicked,
+        "clone" => AstEventKind::Clone,
 
-                    // if <expr> then <block>
-                    // TODO if <expr>: <statement>
-                    Token::Keyword(Keyword::If) => {
-                        let cond = parse_expr(tokens)?;
-                        tokexpect!(tokens.pop_front(), Token::Keyword(Keyword::Then), {});
-                        let block = parse_block(tokens)?;
+        // on key <key>
+        "key" => {
+            let tok = match tokens.pop_front() {
+                Some(v) => v,
+                None => return Err(ParseError::eof())
+            };
 
-                        statements.push(AstStatement::If(cond, block));
-                    }
+            span = span.to(tok.span);
 
-                    tok => return Err(ParseError::new(format!("expected statement, got \"{}\"", tok))),
+            match tok.kind {
+                TokenKind::Identifier(v) | TokenKind::String(v) => AstEventKind::Key(v),
+                TokenKind::Number(v) => AstEventKind::Key(v.to_string()),
+                _ => {
+                    let err = ParseError::unexpected(&tok).with_help("expected a key such as space or a");
+                    tokens.push_front(tok);
+                    return Err(err);
                 }
+            }
+        },
+
+        // on broadcast <string>
+        // on backdrop <string>
+        "broadcast" | "backdrop" => {
+            let name_span = tokens.front().map(|tok| tok.span);
+            let name = tokexpect!(tokens, TokenKind::String(v), v);
+            span = span.to(name_span.unwrap());
+
+            if event == "broadcast" {
+                AstEventKind::Broadcast(name)
+            } else {
+                AstEventKind::Backdrop(name, name_span.unwrap())
+            }
+        },
+
+        // on loudness > <expr>
+        // on timer > <expr>
+        "loudness" | "timer" => {
+            tokexpect!(tokens, TokenKind::Symbol(">"), {});
+            let value = parse_expr(tokens)?;
+            span = span.to(value.span);
+
+            if event == "loudness" {
+                AstEventKind::Loudness(value)
+            } else {
+                AstEventKind::Timer(value)
+            }
+        },
+
+        _ => return Err(ParseError::new(format!("unknown event \"{}\"", event), event_span)
+            .with_help("expected start, clicked, clone, key, broadcast, backdrop, loudness or timer"))
+    };
+
+    let block = parse_body(tokens, errors, Keyword::Do)?;
+
+    Ok(AstEvent {
+        kind,
+        block,
+        span
+    })
+}
+
+// is the next token a property key followed by "="?
+fn at_property(tokens: &VecDeque<Token>) -> bool {
+    matches!(
+        (tokens.front().map(|tok| &tok.kind), tokens.get(1).map(|tok| &tok.kind)),
+        (Some(TokenKind::Identifier(_) | TokenKind::String(_)), Some(TokenKind::Symbol("=")))
+    )
+}
+
+// { [<key> =] <value> {, [<key> =] <value>} [,] }
+fn parse_table(tokens: &mut VecDeque<Token>) -> Result<AstValue, ParseError> {
+    let start_span = tokens.front().map(|tok| tok.span);
+    tokexpect!(tokens, TokenKind::Symbol("{"), {});
+
+    let mut entries = Vec::new();
+
+    loop {
+        if let Some(Token { kind: TokenKind::Symbol("}"), span }) = tokens.front() {
+            let span = start_span.unwrap().to(*span);
+            tokens.pop_front();
+            return Ok(AstValue::Table(entries, span));
+        }
+
+        entries.push(parse_property(tokens, false)?);
+
+        // entries are separated by commas
+        match tokens.front() {
+            Some(Token { kind: TokenKind::Symbol(","), .. }) => {
+                tokens.pop_front();
             },
 
-            // if reached eof
-            None => return Err(ParseError::new("unexpected eof".to_string())),
+            Some(Token { kind: TokenKind::Symbol("}"), .. }) => (),
+            Some(tok) => return Err(ParseError::unexpected(tok).with_help("table entries are separated by \",\"")),
+            None => return Err(ParseError::eof())
         }
     }
+}
+
+// <key> = <value>. if key_required is false, the key may be left out
+fn parse_property(tokens: &mut VecDeque<Token>, key_required: bool) -> Result<AstProperty, ParseError> {
+    let mut key = None;
+    let mut span = None;
+
+    if at_property(tokens) {
+        let tok = tokens.pop_front().unwrap();
+        tokens.pop_front(); // pop equals sign
+
+        span = Some(tok.span);
+        key = match tok.kind {
+            TokenKind::Identifier(v) | TokenKind::String(v) => Some(v),
+            _ => None
+        };
+    } else if key_required {
+        return match tokens.front() {
+            Some(tok) => Err(ParseError::unexpected(tok).with_help("expected a property such as x = 0")),
+            None => Err(ParseError::eof())
+        };
+    }
 
-    Ok(AstBlock {
-        statements
+    let value = match tokens.front() {
+        Some(Token { kind: TokenKind::Symbol("{"), .. }) => parse_table(tokens)?,
+        _ => AstValue::Expr(parse_expr(tokens)?)
+    };
+
+    Ok(AstProperty {
+        key,
+        span: span.unwrap_or(value.span()),
+        value
     })
 }
 
-// parse program
-pub fn parse_ast(tokens: &mut VecDeque<Token>) -> Result<AstProgram, ParseError> {
-    let mut functions = Vec::<AstFunc>::new();
+// after an error in a declaration on the given line, skip tokens until the next
+// property or function, or the end of the declaration.
+// returns false if the end of the file was reached
+fn synchronize_object(tokens: &mut VecDeque<Token>, line: usize) -> bool {
+    while let Some(tok) = tokens.front() {
+        match tok.kind {
+            TokenKind::Keyword(Keyword::Func | Keyword::On) | TokenKind::Symbol("}") => return true,
+            TokenKind::Identifier(_) if tok.span.line > line && at_property(tokens) => return true,
+            _ => ()
+        }
+
+        tokens.pop_front();
+    }
+
+    false
+}
+
+// sprite <id> { ... }
+// stage { ... }
+fn parse_object(tokens: &mut VecDeque<Token>, errors: &mut Vec<ParseError>) -> Result<AstObject, ParseError> {
+    let tok = tokens.pop_front().unwrap();
+    let mut span = tok.span;
+
+    let kind = match tok.kind {
+        TokenKind::Identifier(v) if v == "sprite" => {
+            let id_span = tokens.front().map(|tok| tok.span);
+            let id = tokexpect!(tokens, TokenKind::Identifier(v), v);
+            span = span.to(id_span.unwrap());
+            AstObjectKind::Sprite(id)
+        },
+
+        TokenKind::Identifier(v) if v == "stage" => AstObjectKind::Stage,
+        kind => return Err(ParseError::new(format!("unexpected {}", kind), Some(tok.span)))
+    };
+
+    tokexpect!(tokens, TokenKind::Symbol("{"), {});
+
+    let mut properties = Vec::new();
+    let mut functions = Vec::new();
+    let mut events = Vec::new();
 
     loop {
-        // if there is a token
-        if let Some(tok) = tokens.pop_front() {
-            match tok {
-                Token::Keyword(Keyword::Func) => {
-                    // pop an identifier
-                    let func_id = tokexpect!(tokens.pop_front(), Token::Identifier(v), v);
- 
-                    tokexpect!(tokens.pop_front(), Token::Symbol('('), {}); // pop open paren
-                    tokexpect!(tokens.pop_front(), Token::Symbol(')'), {}); // pop closed paren
-                    
-                    let block = match parse_block(tokens) {
-                        Ok(v) => v,
-                        Err(v) => return Err(v),
-                    };
+        let res = match tokens.front() {
+            Some(Token { kind: TokenKind::Symbol("}"), .. }) => {
+                tokens.pop_front();
+                break;
+            },
 
-                    functions.push(AstFunc {
-                        id: func_id,
-                        block: block
-                    });
-                },
+            None => return Err(ParseError::eof().with_help("add \"}\" to close the declaration")),
+
+            _ if at_func(tokens) => {
+                parse_func(tokens, errors).map(|func| functions.push(func))
+            },
+
+            Some(Token { kind: TokenKind::Keyword(Keyword::On), .. }) => {
+                parse_event(tokens, errors).map(|event| events.push(event))
+            },
+
+            _ => parse_property(tokens, true).map(|prop| {
+                properties.push(prop);
 
-                tok => return Err(ParseError::new(format!("unexpected {} \"{}\"", tok.typestr(), tok)))
+                // properties may be separated by commas
+                if let Some(TokenKind::Symbol(",")) = peek(tokens) {
+                    tokens.pop_front();
+                }
+            })
+        };
+
+        if let Err(err) = res {
+            let line = match err.span {
+                Some(span) => span.line,
+                None => return Err(err)
+            };
+
+            if !synchronize_object(tokens, line) {
+                return Err(err);
             }
-        } else {
-            break;
-        } 
+
+            errors.push(err);
+        }
     }
 
-    Ok(AstProgram {
-        functions
+    Ok(AstObject {
+        kind,
+        properties,
+        functions,
+        events,
+        span
     })
-}
\ No newline at end of file
+}
+
+// is the next token the start of a sprite or stage declaration?
+fn at_object(tokens: &VecDeque<Token>) -> bool {
+    match (tokens.front().map(|tok| &tok.kind), tokens.get(1).map(|tok| &tok.kind)) {
+        (Some(TokenKind::Identifier(v)), Some(TokenKind::Identifier(_))) => v == "sprite",
+        (Some(TokenKind::Identifier(v)), Some(TokenKind::Symbol("{"))) => v == "stage",
+        _ => false
+    }
+}
+
+/// Parses a program, returning every syntax error found in it.
+///
+/// After an error in a statement, parsing resumes at the next statement.
+/// After an error in a declaration, parsing resumes at the next property, function or event handler.
+/// After any other error, parsing resumes at the next function, event handler or declaration.
+pub fn parse_ast(tokens: &mut VecDeque<Token>) -> Result<AstProgram, Vec<ParseError>> {
+    let mut functions = Vec::<AstFunc>::new();
+    let mut events = Vec::<AstEvent>::new();
+    let mut objects = Vec::<AstObject>::new();
+    let mut errors = Vec::<ParseError>::new();
+
+    // while there is a token
+    while !tokens.is_empty() {
+        let res = if at_object(tokens) {
+            parse_object(tokens, &mut errors).map(|obj| objects.push(obj))
+        } else if let Some(TokenKind::Keyword(Keyword::On)) = peek(tokens) {
+            parse_event(tokens, &mut errors).map(|event| events.push(event))
+        } else {
+            parse_func(tokens, &mut errors).map(|func| functions.push(func))
+        };
+
+        if let Err(err) = res {
+            errors.push(err);
+
+            // skip to the next function, event handler or declaration
+            while let Some(tok) = tokens.front() {
+                if let TokenKind::Keyword(Keyword::Func | Keyword::On) = tok.kind {
+                    break;
+                }
+
+                if at_object(tokens) {
+                    break;
+                }
+
+                tokens.pop_front();
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(AstProgram {
+            functions,
+            events,
+            objects
+        })
+    } else {
+        Err(errors)
+    }
+}
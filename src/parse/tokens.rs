@@ -1,20 +1,28 @@
 use std::str::FromStr;
 use std::fmt;
+use super::parser::ParseError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Keyword {
     Func,
     Return,
     Drop,
+    Local,
+    New,
     End,
     Do,
     Then,
+    On,
 
     If,
+    Else,
+    Elseif,
     While,
     For,
     Repeat,
     Until,
+    Break,
+    Continue,
 
     // bool operators
     Not,
@@ -35,14 +43,21 @@ impl fmt::Display for Keyword {
             Keyword::Func => "func",
             Keyword::Return => "return",
             Keyword::Drop => "drop",
+            Keyword::Local => "local",
+            Keyword::New => "new",
             Keyword::End => "end",
             Keyword::Do => "do",
             Keyword::Then => "then",
+            Keyword::On => "on",
             Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::Elseif => "elseif",
             Keyword::While => "while",
             Keyword::For => "for",
             Keyword::Repeat => "repeat",
             Keyword::Until => "until",
+            Keyword::Break => "break",
+            Keyword::Continue => "continue",
             Keyword::Not => "not",
             Keyword::And => "and",
             Keyword::Or => "or",
@@ -62,14 +77,21 @@ impl std::str::FromStr for Keyword {
             "func" => Ok(Keyword::Func),
             "return" => Ok(Keyword::Return),
             "drop" => Ok(Keyword::Drop),
+            "local" => Ok(Keyword::Local),
+            "new" => Ok(Keyword::New),
             "end" => Ok(Keyword::End),
             "do" => Ok(Keyword::Do),
             "then" => Ok(Keyword::Then),
+            "on" => Ok(Keyword::On),
             "if" => Ok(Keyword::If),
+            "else" => Ok(Keyword::Else),
+            "elseif" => Ok(Keyword::Elseif),
             "while" => Ok(Keyword::While),
             "for" => Ok(Keyword::For),
             "repeat" => Ok(Keyword::Repeat),
             "until" => Ok(Keyword::Until),
+            "break" => Ok(Keyword::Break),
+            "continue" => Ok(Keyword::Continue),
             "not" => Ok(Keyword::Not),
             "and" => Ok(Keyword::And),
             "or" => Ok(Keyword::Or),
@@ -81,65 +103,195 @@ impl std::str::FromStr for Keyword {
     }
 }
 
-#[derive(Debug)]
-pub enum Token {
-    Symbol(char),
+/// A location in a source file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    // byte offsets of the first character and one past the last character
+    pub start: usize,
+    pub end: usize,
+
+    // line and column of the first character, both starting at 1.
+    // the column counts characters, not bytes
+    pub line: usize,
+    pub col: usize
+}
+
+impl Span {
+    /// Creates a span covering both this span and `other`, which must come after it
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end,
+            line: self.line,
+            col: self.col
+        }
+    }
+
+    /// Returns an empty span at the end of the given source
+    pub fn eof(source: &str) -> Span {
+        let line = source.matches('\n').count() + 1;
+        let col = source[source.rfind('\n').map_or(0, |i| i + 1)..].chars().count() + 1;
+
+        Span {
+            start: source.len(),
+            end: source.len(),
+            line,
+            col
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TokenKind {
+    Symbol(&'static str),
     Identifier(String),
     Keyword(Keyword),
     Number(f64),
     String(String),
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Token::Symbol(ch) => write!(f, "{}", ch),
-            Token::Identifier(id) => write!(f, "{}", id),
-            Token::Keyword(kw) => write!(f, "{}", kw),
-            Token::Number(num) => write!(f, "{}", num),
-            Token::String(str) => write!(f, "{}", str)
+            TokenKind::Symbol(sym) => write!(f, "{}", sym),
+            TokenKind::Identifier(id) => write!(f, "{}", id),
+            TokenKind::Keyword(kw) => write!(f, "{}", kw),
+            TokenKind::Number(num) => write!(f, "{}", num),
+            TokenKind::String(str) => write!(f, "{}", str)
         }
     }
 }
 
-impl Token {
+impl TokenKind {
     pub fn typestr(&self) -> &str {
         match self {
-            Token::Symbol(_) => "symbol",
-            Token::Identifier(_) => "identifier",
-            Token::Keyword(_) => "keyword",
-            Token::Number(_) => "number",
-            Token::String(_) => "string"
+            TokenKind::Symbol(_) => "symbol",
+            TokenKind::Identifier(_) => "identifier",
+            TokenKind::Keyword(_) => "keyword",
+            TokenKind::Number(_) => "number",
+            TokenKind::String(_) => "string"
         }
     }
 }
 
-pub fn read_tokens(file_contents: &String, output: &mut Vec<Token>) {
-    let file_bytes = file_contents.as_bytes();
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+// get the symbol starting with a character, which may be followed by a second character
+fn symbol(ch: char, next: Option<char>) -> Option<&'static str> {
+    Some(match (ch, next) {
+        ('=', Some('=')) => "==",
+        ('!', Some('=')) => "!=",
+        ('<', Some('=')) => "<=",
+        ('>', Some('=')) => ">=",
+        ('.', Some('.')) => "..",
+
+        ('(', _) => "(",
+        (')', _) => ")",
+        ('{', _) => "{",
+        ('}', _) => "}",
+        ('[', _) => "[",
+        (']', _) => "]",
+        (',', _) => ",",
+        ('+', _) => "+",
+        ('-', _) => "-",
+        ('*', _) => "*",
+        ('/', _) => "/",
+        ('%', _) => "%",
+        ('^', _) => "^",
+        (':', _) => ":",
+        ('.', _) => ".",
+        ('=', _) => "=",
+        ('<', _) => "<",
+        ('>', _) => ">",
+        ('!', _) => "!",
+        _ => return None
+    })
+}
+
+/// Splits a source file into tokens, returning every invalid token found.
+///
+/// Even if there were errors, `output` holds a token stream that can be parsed.
+pub fn read_tokens(file_contents: &str, output: &mut Vec<Token>) -> Result<(), Vec<ParseError>> {
+    let mut chars = file_contents.char_indices().peekable();
+    let mut errors = Vec::new();
 
     let mut buffer = String::new();
     let mut flush = false;
-    let mut flush_ch: Option<char> = None;
+    let mut flush_sym: Option<&'static str> = None;
     let mut read_str = false;
 
-    for i in 0..(file_bytes.len() + 1) {
-        if i < file_bytes.len() {
-            let ch = file_bytes[i] as char;
+    // a comment lasts until the end of the line
+    let mut comment = false;
 
-            if read_str {
+    // the second character of a symbol was already read
+    let mut skip = false;
+
+    // line and column of the current character, which is at a byte offset in the file
+    let mut line = 1;
+    let mut col = 1;
+
+    // where the token in the buffer started
+    let mut buffer_start = Span { start: 0, end: 0, line, col };
+
+    // one more iteration past the last character flushes the last token
+    loop {
+        let current = chars.next();
+        let i = current.map_or(file_contents.len(), |(i, _)| i);
+        let here = Span { start: i, end: i + current.map_or(1, |(_, ch)| ch.len_utf8()), line, col };
+
+        if skip {
+            skip = false;
+            col += 1;
+            continue;
+        }
+
+        if let Some((_, ch)) = current {
+
+            if comment {
+                comment = ch != '\n';
+            } else if read_str {
                 if ch == '"' {
-                    output.push(Token::String(buffer.clone()));
+                    output.push(Token {
+                        kind: TokenKind::String(buffer.clone()),
+                        span: buffer_start.to(here)
+                    });
+
                     buffer.clear();
                     read_str = false;
                 } else { // TODO escape characters
                     buffer.push(ch);
                 }
             } else {
+                let next = chars.peek().map(|(_, ch)| *ch);
+
                 match ch {
+                    // a dot inside a number literal is a decimal point
+                    '.' if next != Some('.') && buffer.as_bytes().first().is_some_and(u8::is_ascii_digit) => {
+                        buffer.push(ch);
+                    }
+
                     // if reached a symbol
-                    '(' | ')' | ',' | '+' | '-' | '/' | '*' | ':' => {
+                    ch if symbol(ch, next).is_some() => {
+                        let sym = symbol(ch, next).unwrap();
                         flush = true;
-                        flush_ch = Some(ch);
+                        flush_sym = Some(sym);
+                        skip = sym.len() > 1;
                     }
 
                     // if reached a quotation mark
@@ -148,29 +300,60 @@ pub fn read_tokens(file_contents: &String, output: &mut Vec<Token>) {
                         read_str = true;
                     }
 
+                    '#' => {
+                        flush = true;
+                        comment = true;
+                    }
+
                     _ => {
                         if ch.is_whitespace() {
                             flush = true;
                         } else {
+                            if buffer.is_empty() {
+                                buffer_start = here;
+                            }
+
                             buffer.push(ch);
                         }
                     }
                 }
             }
+
+            if ch == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
         } else {
+            if read_str {
+                errors.push(ParseError::new("unterminated string".to_string(), Some(buffer_start)));
+                break;
+            }
+
             flush = true;
         }
 
         if flush {
             // flush identifier/keyword
-            if buffer.len() > 0 {
+            if !buffer.is_empty() {
+                let span = Span { end: i, ..buffer_start };
+
                 // if first character is a digit
-                if (buffer.as_bytes()[0] as char).is_digit(10) {
-                    output.push(Token::Number(buffer.parse::<f64>().unwrap())); // TODO error checking
+                if (buffer.as_bytes()[0] as char).is_ascii_digit() {
+                    match buffer.parse::<f64>() {
+                        Ok(num) => output.push(Token { kind: TokenKind::Number(num), span }),
+                        Err(_) => {
+                            errors.push(ParseError::new(format!("invalid number \"{}\"", buffer), Some(span)));
+
+                            // keep a placeholder so the parser can still check the rest of the file
+                            output.push(Token { kind: TokenKind::Number(0.0), span });
+                        }
+                    }
                 } else {
                     match Keyword::from_str(buffer.as_str()) {
-                        Ok(kw) => output.push(Token::Keyword(kw)),
-                        Err(_) => output.push(Token::Identifier(buffer.clone()))
+                        Ok(kw) => output.push(Token { kind: TokenKind::Keyword(kw), span }),
+                        Err(_) => output.push(Token { kind: TokenKind::Identifier(buffer.clone()), span })
                     }
                 }
 
@@ -178,12 +361,30 @@ pub fn read_tokens(file_contents: &String, output: &mut Vec<Token>) {
             }
 
             // flush symbol
-            if let Some(sym) = flush_ch {
-                output.push(Token::Symbol(sym));
+            if let Some(sym) = flush_sym {
+                output.push(Token {
+                    kind: TokenKind::Symbol(sym),
+                    span: Span { end: i + sym.len(), ..here }
+                });
+            }
+
+            // the opening quote of a string starts its span
+            if read_str {
+                buffer_start = here;
             }
 
             flush = false;
-            flush_ch = None;
+            flush_sym = None;
+        }
+
+        if current.is_none() {
+            break;
         }
     }
-}
\ No newline at end of file
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
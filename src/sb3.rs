@@ -7,8 +7,12 @@ use std::fs;
 use std::path::Path;
 use json::{JsonValue};
 pub use opcode::*;
+pub use audio::{SoundInfo, SampleFormat};
+pub use image::{ImageInfo, ImageFormat, Anchor};
 
 mod opcode;
+mod audio;
+mod image;
 
 #[derive(Debug)]
 pub enum SerializeError {
@@ -17,7 +21,26 @@ pub enum SerializeError {
     ZipError(zip::result::ZipError),
     NoHash,
     NoCostume,
-    Unknown
+    BadCostume(String),
+    BadSound(String),
+    EmptyScript,
+
+    // a block or input which can't be written to a project yet
+    UnsupportedOpcode(&'static str),
+    UnsupportedInput(InputType),
+    InvalidColor(String),
+
+    // a block which only sprites have, used on the stage
+    NotOnStage(&'static str),
+
+    // a name which isn't declared on the object or the stage
+    UnknownVariable(String),
+    UnknownList(String),
+    UnknownBroadcast(String),
+    UnknownSound(String),
+    UnknownCostume(String),
+    UnknownBackdrop(String),
+    UnknownProperty(String)
 }
 
 // compatibility between json::Error and SerializeError
@@ -47,8 +70,21 @@ impl std::fmt::Display for SerializeError {
             SerializeError::JsonError(e) => write!(f, "{}", e),
             SerializeError::IoError(e) => write!(f, "{}", e),
             SerializeError::NoHash => write!(f, "hash not generated"),
-            SerializeError::Unknown => write!(f, "unknown error"),
             SerializeError::NoCostume => write!(f, "no costume"),
+            SerializeError::BadCostume(path) => write!(f, "could not read costume {}, expected an svg, png or jpeg file", path),
+            SerializeError::BadSound(path) => write!(f, "could not read sound {}, expected a wav or mp3 file", path),
+            SerializeError::EmptyScript => write!(f, "script has no blocks"),
+            SerializeError::UnsupportedOpcode(opcode) => write!(f, "unsupported block {}", opcode),
+            SerializeError::UnsupportedInput(input_type) => write!(f, "unsupported input type {:?}", input_type),
+            SerializeError::NotOnStage(opcode) => write!(f, "block {} can't be used on the stage", opcode),
+            SerializeError::InvalidColor(color) => write!(f, "invalid color \"{}\", expected #rrggbb", color),
+            SerializeError::UnknownVariable(name) => write!(f, "unknown variable \"{}\"", name),
+            SerializeError::UnknownList(name) => write!(f, "unknown list \"{}\"", name),
+            SerializeError::UnknownBroadcast(name) => write!(f, "unknown broadcast \"{}\"", name),
+            SerializeError::UnknownSound(name) => write!(f, "unknown sound \"{}\"", name),
+            SerializeError::UnknownCostume(name) => write!(f, "unknown costume \"{}\"", name),
+            SerializeError::UnknownBackdrop(name) => write!(f, "unknown backdrop \"{}\"", name),
+            SerializeError::UnknownProperty(name) => write!(f, "property \"{}\" doesn't exist on that object", name),
             SerializeError::ZipError(e) => write!(f, "{}", e)
         }
     }
@@ -65,7 +101,7 @@ pub enum Value {
     Boolean(bool),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum InputType {
     Number, // 4
     PositiveNumber, // 5
@@ -73,8 +109,8 @@ pub enum InputType {
     Integer, // 7
     Angle, // 8
     Color, // 9 #rrggbb
-    String,
-    Broadcast
+    String, // 10
+    Broadcast // 11
     // Variable
     // List
 }
@@ -95,7 +131,27 @@ pub enum RotationStyle {
     AllAround,
 }
 
-#[derive(Debug)]
+impl RotationStyle {
+    /// Finds a rotation style by its name in Scratch: "all around", "left-right" or "don't rotate"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "all around" => Some(RotationStyle::AllAround),
+            "left-right" => Some(RotationStyle::LeftRight),
+            "don't rotate" => Some(RotationStyle::None),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RotationStyle::None => "don't rotate",
+            RotationStyle::LeftRight => "left-right",
+            RotationStyle::AllAround => "all around"
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Script {
     blocks: Vec<Block>
 }
@@ -111,14 +167,23 @@ impl Script {
         self.blocks.push(block);
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Moves all blocks of `other` to the end of this script
+    pub fn append(&mut self, mut other: Script) {
+        self.blocks.append(&mut other.blocks);
+    }
+
     /// Parses all the block in this script to JSON, adding it to the `block_list` JsonValue::Array.
     /// 
     /// On OK, it returns the UUID of the first block.
-    pub fn serialize(&self, block_list: &mut JsonValue, root: Option<&String>) -> Result<&String, SerializeError> {
+    pub fn serialize(&self, block_list: &mut JsonValue, obj: &Object, root: Option<&String>) -> Result<&String, SerializeError> {
         let mut parent_uuid: Option<&String> = root;
 
-        for (_, block) in self.blocks.iter().enumerate() {
-            let mut res = block.serialize(block_list, parent_uuid)?;
+        for block in self.blocks.iter() {
+            let mut res = block.serialize(block_list, obj, parent_uuid)?;
 
             // set parent's "next" property to my uuid
             // and set my "parent" property to parent's uuid
@@ -138,7 +203,7 @@ impl Script {
 
         match self.blocks.first() {
             Some(v) => Ok(&v.uuid),
-            None => Err(SerializeError::Unknown)
+            None => Err(SerializeError::EmptyScript)
         }
     }
 }
@@ -158,21 +223,65 @@ impl UserInput {
         }
     }
 
-    pub fn serialize(&self, input_type: InputType) -> Result<JsonValue, SerializeError> {
+    /// Serializes this input. If a reporter block is plugged into the slot, it is added to
+    /// `block_list` with `parent_uuid` as its parent.
+    pub fn serialize(&self, block_list: &mut JsonValue/*::Array*/, obj: &Object, parent_uuid: &String, input_type: InputType) -> Result<JsonValue, SerializeError> {
         let value = match &self.value {
             Value::Number(num) => JsonValue::String((*num).to_string().clone()),
             Value::String(s) => JsonValue::String(s.clone()),
-            Value::Boolean(b) => JsonValue::Boolean(*b)
+            // primitives always hold text
+            Value::Boolean(b) => JsonValue::String(b.to_string())
         };
     
         let input = match input_type {
             InputType::Number => json::array![4, value],
+            InputType::PositiveNumber => json::array![5, value],
+            InputType::WholeNumber => json::array![6, value],
+            InputType::Integer => json::array![7, value],
+            InputType::Angle => json::array![8, value],
+
+            // the color picker only understands #rrggbb. the picker under a reporter is hidden,
+            // so it only needs to hold some color
+            InputType::Color => match value.as_str() {
+                Some(hex) if Color::from_hex(hex).is_some() => json::array![9, value],
+                _ if self.block.is_some() => json::array![9, "#000000"],
+                _ => return Err(SerializeError::InvalidColor(value.to_string()))
+            },
+
             InputType::String => json::array![10, value],
-            _ => panic!("unimplemented input type")
+
+            // the value of a broadcast input is the name of the message
+            InputType::Broadcast => match obj.broadcast_id(&value.to_string()) {
+                Some(id) => json::array![11, value, id],
+                None => return Err(SerializeError::UnknownBroadcast(value.to_string()))
+            },
+
         };
     
         Ok(match &self.block {
-            Some(v) => json::array! [3, v.uuid.clone(), input],
+            // variable and list reporters are written into the input itself as [12, name, id] or [13, name, id]
+            Some(v) => match &v.opcode {
+                Opcode::Variable(name) => match obj.variable_id(name) {
+                    Some(id) => {
+                        let reference = json::array![12, name.clone(), id];
+                        json::array![3, reference, input]
+                    },
+                    None => return Err(SerializeError::UnknownVariable(name.clone()))
+                },
+
+                Opcode::ListContents(name) => match obj.list_id(name) {
+                    Some(id) => {
+                        let reference = json::array![13, name.clone(), id];
+                        json::array![3, reference, input]
+                    },
+                    None => return Err(SerializeError::UnknownList(name.clone()))
+                },
+
+                _ => {
+                    block_list[v.uuid.clone()] = v.serialize(block_list, obj, Some(parent_uuid))?;
+                    json::array! [3, v.uuid.clone(), input]
+                }
+            },
             None => json::array![1, input]
         })
     } 
@@ -184,11 +293,11 @@ pub struct ScriptInput {
 }
 
 impl ScriptInput {
-    pub fn serialize(&self, block_list: &mut JsonValue/*::Array*/, parent_uuid: &String) -> Result<JsonValue, SerializeError> {
+    pub fn serialize(&self, block_list: &mut JsonValue/*::Array*/, obj: &Object, parent_uuid: &String) -> Result<JsonValue, SerializeError> {
         match &self.script {
             Some(script) => {
                 // this is the uuid of the first block
-                let uuid = script.serialize(block_list, Some(parent_uuid))?;
+                let uuid = script.serialize(block_list, obj, Some(parent_uuid))?;
                 Ok(json::array![2, uuid.clone()])
             },
 
@@ -203,10 +312,10 @@ pub struct ReporterInput {
 }
 
 impl ReporterInput {
-    pub fn serialize(&self, block_list: &mut JsonValue/*::Array*/, parent_uuid: &String) -> Result<JsonValue, SerializeError> {
+    pub fn serialize(&self, block_list: &mut JsonValue/*::Array*/, obj: &Object, parent_uuid: &String) -> Result<JsonValue, SerializeError> {
         match &self.block {
             Some(block) => {
-                block_list[block.uuid.clone()] = block.serialize(block_list, Some(parent_uuid))?;
+                block_list[block.uuid.clone()] = block.serialize(block_list, obj, Some(parent_uuid))?;
                 Ok(json::array![2, block.uuid.clone()])
             }
 
@@ -215,6 +324,37 @@ impl ReporterInput {
     }
 }
 
+#[derive(Debug)]
+// Input slot holding a menu, which a reporter can be placed over
+pub struct MenuInput {
+    pub menu: Box<Block>,
+    pub block: Option<Box<Block>>
+}
+
+impl MenuInput {
+    pub fn new(menu: Opcode, block: Option<Box<Block>>) -> Self {
+        Self {
+            menu: Box::new(Block::new(menu)),
+            block
+        }
+    }
+
+    /// Serializes this input. The menu is always added to `block_list` as a shadow block,
+    /// even when a reporter covers it.
+    pub fn serialize(&self, block_list: &mut JsonValue/*::Array*/, obj: &Object, parent_uuid: &String) -> Result<JsonValue, SerializeError> {
+        block_list[self.menu.uuid.clone()] = self.menu.serialize(block_list, obj, Some(parent_uuid))?;
+
+        match &self.block {
+            Some(block) => {
+                block_list[block.uuid.clone()] = block.serialize(block_list, obj, Some(parent_uuid))?;
+                Ok(json::array![3, block.uuid.clone(), self.menu.uuid.clone()])
+            },
+
+            None => Ok(json::array![1, self.menu.uuid.clone()])
+        }
+    }
+}
+
 // generate a UUID
 fn uuid() -> String {
     let mut rng = rand::thread_rng();
@@ -359,9 +499,80 @@ pub struct List {
     // monitor properties
     pub x: f64,
     pub y: f64,
+
+    // a size of 0 lets scratch pick one
+    pub width: f64,
+    pub height: f64,
     pub visible: bool
 }
 
+impl List {
+    pub fn new(name: &str, value: Vec<Value>) -> Self {
+        List {
+            id: uuid() + name,
+            name: String::from(name),
+            value,
+            sprite_name: None,
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            visible: false
+        }
+    }
+
+    pub fn serialize_monitor(&self) -> Result<JsonValue, SerializeError> {
+        let sprite_name: JsonValue = match &self.sprite_name {
+            Some(v) => JsonValue::String(v.clone()),
+            None => JsonValue::Null,
+        };
+
+        let mut items = JsonValue::new_array();
+
+        for item in self.value.iter() {
+            items.push(match item {
+                Value::String(s) => JsonValue::String(s.clone()),
+                Value::Number(v) => JsonValue::Number((*v).into()),
+                Value::Boolean(v) => JsonValue::Boolean(*v)
+            })?;
+        }
+
+        Ok(json::object! {
+            "id": self.id.clone(),
+            "mode": "list",
+            "opcode": "data_listcontents",
+            "params": {
+                "LIST": self.name.clone()
+            },
+            "spriteName": sprite_name,
+            "value": items,
+            "width": self.width,
+            "height": self.height,
+            "x": self.x,
+            "y": self.y,
+            "visible": self.visible
+        })
+    }
+}
+
+impl JsonSerialize for List {
+    fn serialize(&self) -> Result<JsonValue, SerializeError> {
+        let mut items = JsonValue::new_array();
+
+        for item in self.value.iter() {
+            items.push(match item {
+                Value::String(s) => JsonValue::String(s.clone()),
+                Value::Number(v) => JsonValue::Number((*v).into()),
+                Value::Boolean(v) => JsonValue::Boolean(*v)
+            })?;
+        }
+
+        Ok(json::array! [
+            self.name.clone(), items
+        ])
+    }
+}
+
 #[derive(Debug)]
 pub struct Costume {
     // id is the MD5 checksum of the file contents
@@ -370,9 +581,23 @@ pub struct Costume {
     pub rot_cx: f64,
     pub rot_cy: f64,
 
-    pub md5: Option<String>
+    // put the rotation center at a point of the image, moved by an offset, once its size is known
+    pub anchor: Option<(Anchor, f64, f64)>,
+
+    // how many pixels of a bitmap make up one pixel on the stage
+    pub bitmap_resolution: u32,
+
+    // file contents built into the binary, used instead of reading from path
+    pub data: Option<&'static [u8]>,
+
+    // read from the file when the project is saved
+    pub md5: Option<String>,
+    pub info: Option<ImageInfo>
 }
 
+// a blank svg costume, for objects that don't declare any costumes
+const EMPTY_COSTUME: &[u8] = include_bytes!("../assets/empty_costume.svg");
+
 impl Costume {
     pub fn new(name: &str, path: &str, rot_cx: f64, rot_cy: f64) -> Self {
         Self {
@@ -380,39 +605,69 @@ impl Costume {
             path: path.to_string(),
             rot_cx,
             rot_cy,
-            md5: None
+            anchor: None,
+            bitmap_resolution: 2,
+            data: None,
+            md5: None,
+            info: None
+        }
+    }
+
+    /// Creates a costume which rotates around the middle of its image
+    pub fn centered(name: &str, path: &str) -> Self {
+        Self::anchored(name, path, Anchor::Center, (0.0, 0.0))
+    }
+
+    /// Creates a costume which rotates around a point of its image, moved by an offset in pixels
+    pub fn anchored(name: &str, path: &str, anchor: Anchor, offset: (f64, f64)) -> Self {
+        Self {
+            anchor: Some((anchor, offset.0, offset.1)),
+            ..Self::new(name, path, 0.0, 0.0)
+        }
+    }
+
+    /// Creates a blank costume which doesn't need any file on disk
+    pub fn empty(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: "empty_costume.svg".to_string(),
+            rot_cx: 1.0,
+            rot_cy: 1.0,
+            anchor: None,
+            bitmap_resolution: 1,
+            data: Some(EMPTY_COSTUME),
+            md5: None,
+            info: None
         }
     }
 
     pub fn read_file(&self) -> Result<Vec<u8>, std::io::Error> {
-        fs::read(&self.path)
+        match self.data {
+            Some(data) => Ok(data.to_vec()),
+            None => fs::read(&self.path)
+        }
     }
 }
 
 impl JsonSerialize for Costume {
     fn serialize(&self) -> Result<JsonValue, SerializeError> {
         // the file id will be the md5 checksum of the file contents
-        match &self.md5 {
-            Some(hash) => {
-                match Path::new(&self.path).extension().and_then(std::ffi::OsStr::to_str) {
-                    Some(ext) => {
-                        Ok(json::object! {
-                            "name": self.name.clone(),
-                            //"bitmapResolution": 1, // TODO i think it's 2
-                            "dataFormat": ext.clone(),
-                            "assetId": hash.clone(),
-                            "md5ext": format!("{}.{}", hash, &ext),
-                            "rotationCenterX": self.rot_cx,
-                            "rotationCenterY": self.rot_cy
-                        })
-                    },
-                    None => {
-                        Err(SerializeError::Unknown)
-                    }
-                }
-            },
-            None => Err(SerializeError::NoHash) 
-        }
+        let (hash, info) = match (&self.md5, &self.info) {
+            (Some(hash), Some(info)) => (hash, info),
+            _ => return Err(SerializeError::NoHash)
+        };
+
+        let ext = info.format.extension();
+
+        Ok(json::object! {
+            "name": self.name.clone(),
+            "bitmapResolution": if info.format.is_bitmap() { self.bitmap_resolution } else { 1 },
+            "dataFormat": ext,
+            "assetId": hash.clone(),
+            "md5ext": format!("{}.{}", hash, ext),
+            "rotationCenterX": self.rot_cx,
+            "rotationCenterY": self.rot_cy
+        })
     }
 }
 
@@ -421,19 +676,83 @@ pub struct Sound {
     // id is the MD5 checksum of the file contents
     pub name: String,
     pub path: String,
+
+    // read from the file when the project is saved
+    pub md5: Option<String>,
+    pub info: Option<SoundInfo>
+}
+
+impl Sound {
+    pub fn new(name: &str, path: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            md5: None,
+            info: None
+        }
+    }
+
+    pub fn read_file(&self) -> Result<Vec<u8>, std::io::Error> {
+        fs::read(&self.path)
+    }
+}
+
+impl JsonSerialize for Sound {
+    fn serialize(&self) -> Result<JsonValue, SerializeError> {
+        let (hash, info) = match (&self.md5, &self.info) {
+            (Some(hash), Some(info)) => (hash, info),
+            _ => return Err(SerializeError::NoHash)
+        };
+
+        let ext = info.format.extension();
+
+        Ok(json::object! {
+            "name": self.name.clone(),
+            "assetId": hash.clone(),
+            "dataFormat": ext,
+            "format": if info.format == SampleFormat::Adpcm { "adpcm" } else { "" },
+            "rate": info.rate,
+            "sampleCount": info.sample_count,
+            "md5ext": format!("{}.{}", hash, ext)
+        })
+    }
 }
 
+// a message which can be broadcast. broadcasts are always global, and their id is
+// made from the name like in projects scratch converts from scratch 2, so that
+// building the same source twice gives the same project
 #[derive(Debug)]
+pub struct Broadcast {
+    pub id: String,
+    pub name: String
+}
+
+impl Broadcast {
+    pub fn new(name: &str) -> Self {
+        Broadcast {
+            id: format!("broadcastMsgId-{}", name),
+            name: String::from(name)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Data {
     pub vars: Vec<Variable>,
     pub lists: Vec<List>,
+    pub broadcasts: Vec<Broadcast>,
+
+    // names of the costumes of the stage, so that sprites can switch to them
+    pub backdrops: Vec<String>
 }
 
 impl Data {
     pub fn new() -> Self {
         Self {
             vars: Vec::new(),
-            lists: Vec::new()
+            lists: Vec::new(),
+            broadcasts: Vec::new(),
+            backdrops: Vec::new()
         }
     }
 }
@@ -474,57 +793,135 @@ impl Object {
     }
 
     pub fn add_costume(&mut self, costume: Costume) {
+        if self.is_stage {
+            self.global_data.borrow_mut().backdrops.push(costume.name.clone());
+        }
+
         self.costumes.push(costume);
     }
+
+    /// Adds a variable to this object, or to the global data if this is the stage
+    pub fn add_variable(&mut self, mut var: Variable) {
+        if self.is_stage {
+            self.global_data.borrow_mut().vars.push(var);
+        } else {
+            // the monitor of a local variable is labeled with its sprite
+            var.sprite_name = Some(self.name.clone());
+            self.data.vars.push(var);
+        }
+    }
+
+    /// Adds a list to this object, or to the global data if this is the stage
+    pub fn add_list(&mut self, mut list: List) {
+        if self.is_stage {
+            self.global_data.borrow_mut().lists.push(list);
+        } else {
+            list.sprite_name = Some(self.name.clone());
+            self.data.lists.push(list);
+        }
+    }
+
+    /// Finds the id of a list by name, looking at local lists before global ones
+    pub fn list_id(&self, name: &str) -> Option<String> {
+        match self.data.lists.iter().find(|list| list.name == name) {
+            Some(list) => Some(list.id.clone()),
+            None => self.global_data.borrow().lists.iter()
+                .find(|list| list.name == name)
+                .map(|list| list.id.clone())
+        }
+    }
+
+    /// Adds a broadcast to the project if there isn't one with the same name
+    pub fn add_broadcast(&mut self, name: &str) {
+        if self.broadcast_id(name).is_none() {
+            self.global_data.borrow_mut().broadcasts.push(Broadcast::new(name));
+        }
+    }
+
+    /// Finds the id of a broadcast by name
+    pub fn broadcast_id(&self, name: &str) -> Option<String> {
+        self.global_data.borrow().broadcasts.iter()
+            .find(|broadcast| broadcast.name == name)
+            .map(|broadcast| broadcast.id.clone())
+    }
+
+    /// Finds the id of a variable by name, looking at local variables before global ones
+    pub fn variable_id(&self, name: &str) -> Option<String> {
+        match self.data.vars.iter().find(|var| var.name == name) {
+            Some(var) => Some(var.id.clone()),
+            None => self.global_data.borrow().vars.iter()
+                .find(|var| var.name == name)
+                .map(|var| var.id.clone())
+        }
+    }
 }
 
 impl JsonSerialize for Object {
     fn serialize(&self) -> Result<JsonValue, SerializeError> {
         let mut vars = JsonValue::new_object();
+        let mut lists = JsonValue::new_object();
+        let mut broadcasts = JsonValue::new_object();
         let mut costumes = JsonValue::new_array();
+        let mut sounds = JsonValue::new_array();
 
         // serialize data
         // if is stage, store the global data in this
         if self.is_stage {
             let data = self.global_data.borrow();
 
-            for (_i, var) in data.vars.iter().enumerate() {
+            for var in data.vars.iter() {
                 vars[var.id.clone()] = var.serialize()?;
             }
+
+            for list in data.lists.iter() {
+                lists[list.id.clone()] = list.serialize()?;
+            }
+
+            for broadcast in data.broadcasts.iter() {
+                broadcasts[broadcast.id.clone()] = broadcast.name.clone().into();
+            }
         } else {
-            for (_i, var) in self.data.vars.iter().enumerate() {
+            for var in self.data.vars.iter() {
                 vars[var.id.clone()] = var.serialize()?;
             }
+
+            for list in self.data.lists.iter() {
+                lists[list.id.clone()] = list.serialize()?;
+            }
         }
 
         // serialize costumes
-        if self.costumes.len() == 0 {
+        if self.costumes.is_empty() {
             return Err(SerializeError::NoCostume);
         }
 
-        for (_, costume) in self.costumes.iter().enumerate() {
+        for costume in self.costumes.iter() {
             let json = costume.serialize()?;
             costumes.push(json)?;
         }
 
+        for sound in self.sounds.iter() {
+            sounds.push(sound.serialize()?)?;
+        }
+
         let mut block_list = JsonValue::new_object();
 
         // serialize scripts
-        for (_, script) in self.scripts.iter().enumerate() {
-            script.serialize(&mut block_list, None)?;
+        for script in self.scripts.iter() {
+            script.serialize(&mut block_list, self, None)?;
         }
 
         Ok(json::object! {
             "isStage": self.is_stage,
             "name": self.name.clone(),
             "variables": vars,
-            "lists": {}, // TODO
-            "broadcasts": {}, // TODO
+            "lists": lists,
+            "broadcasts": broadcasts,
             "blocks": block_list,
             "comments": {},
             "currentCostume": self.costume_index,
-            "costumes": costumes, // TODO
-            "sounds": [], // TODO
+            "costumes": costumes,
+            "sounds": sounds,
             "volume": self.volume * 100.0,
             "layerOrder": self.layer
         })
@@ -569,11 +966,7 @@ impl JsonSerialize for Sprite {
         res["size"] = (self.size * 100.0).into();
         res["direction"] = self.dir.into();
         res["draggable"] = JsonValue::Boolean(self.draggable);
-        res["rotationStyle"] = match self.rotation_style {
-            RotationStyle::None => "don't rotate".into(),
-            RotationStyle::LeftRight => "left-right".into(),
-            RotationStyle::AllAround => "all around".into()
-        };
+        res["rotationStyle"] = self.rotation_style.name().into();
 
         Ok(res)
     }
@@ -594,14 +987,15 @@ pub struct Project {
     pub data: Rc<RefCell<Data>>,
 }
 
+impl Default for Project {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Project {
     pub fn new() -> Self {
-        let data = Data {
-            vars: Vec::new(),
-            lists: Vec::new()
-        };
-
-        let cell = Rc::new(RefCell::new(data));
+        let cell = Rc::new(RefCell::new(Data::new()));
         let mut stage = Object::new("Stage", Rc::clone(&cell));
         stage.is_stage = true;
 
@@ -635,40 +1029,53 @@ impl Project {
 
         let mut assets_visited: HashSet<String> = HashSet::new();
 
-        let mut write_costume = |costume: &mut Costume| -> Result<(), SerializeError> {
-            // get file contents and calculate md5 hash
-            let file_contents = costume.read_file()?;
-            let md5 = format!("{:x}", md5::compute(&file_contents));
+        // write a file to the zip once, named after the md5 hash of its contents
+        let mut write_asset = |ext: &str, file_contents: &[u8]| -> Result<String, SerializeError> {
+            let md5 = format!("{:x}", md5::compute(file_contents));
 
             if !assets_visited.contains(&md5) {
                 assets_visited.insert(md5.clone());
 
-                let path = Path::new(&costume.path);
-
-                // extract file extension
-                let ext = match path.extension().and_then(std::ffi::OsStr::to_str) {
-                    Some(v) => String::from(".") + v,
-                    None => String::from(".")
-                };
-
-                
                 // begin writing file to zip
-                zip.start_file(md5.clone() + &ext, options)?;
-                zip.write_all(&file_contents)?;
+                zip.start_file(format!("{}.{}", md5, ext), options)?;
+                zip.write_all(file_contents)?;
             }
-            
-            costume.md5 = Some(md5);
-            Ok(())
+
+            Ok(md5)
         };
 
         // begin writing asset files
-        for (_, costume) in self.stage.costumes.iter_mut().enumerate() {
-            write_costume(costume)?;
-        }
+        for obj in std::iter::once(&mut self.stage).chain(self.sprites.iter_mut().map(|sprite| &mut sprite.obj)) {
+            for costume in obj.costumes.iter_mut() {
+                let file_contents = costume.read_file()?;
+
+                let info = match image::read_info(&file_contents) {
+                    Some(info) => info,
+                    None => return Err(SerializeError::BadCostume(costume.path.clone()))
+                };
 
-        for (_, sprite) in self.sprites.iter_mut().enumerate() {
-            for (_, costume) in sprite.obj.costumes.iter_mut().enumerate() {
-                write_costume(costume)?;
+                if let Some((anchor, dx, dy)) = costume.anchor {
+                    if let Some((x, y)) = anchor.position(&info) {
+                        costume.rot_cx = x + dx;
+                        costume.rot_cy = y + dy;
+                    }
+                }
+
+                costume.info = Some(info);
+                costume.md5 = Some(write_asset(info.format.extension(), &file_contents)?);
+            }
+
+            for sound in obj.sounds.iter_mut() {
+                let file_contents = sound.read_file()?;
+                let ext = Path::new(&sound.path).extension().and_then(std::ffi::OsStr::to_str).unwrap_or("");
+
+                let info = match audio::read_info(ext, &file_contents) {
+                    Some(info) => info,
+                    None => return Err(SerializeError::BadSound(sound.path.clone()))
+                };
+
+                sound.info = Some(info);
+                sound.md5 = Some(write_asset(info.format.extension(), &file_contents)?);
             }
         }
 
@@ -684,6 +1091,29 @@ impl Project {
     }
 }
 
+// extensions which are loaded when the project uses any of their blocks, whose opcodes
+// start with the name of the extension
+const EXTENSIONS: &[&str] = &["pen", "music"];
+
+fn used_extensions(targets: &JsonValue) -> Result<JsonValue, SerializeError> {
+    let mut res = JsonValue::new_array();
+
+    for ext in EXTENSIONS {
+        let prefix = format!("{}_", ext);
+        let used = targets.members().any(|target| {
+            target["blocks"].entries().any(|(_, block)| {
+                block["opcode"].as_str().is_some_and(|opcode| opcode.starts_with(&prefix))
+            })
+        });
+
+        if used {
+            res.push(*ext)?;
+        }
+    }
+
+    Ok(res)
+}
+
 impl JsonSerialize for Project {
     fn serialize(&self) -> Result<JsonValue, SerializeError> {
         let mut targets = JsonValue::new_array();
@@ -700,33 +1130,44 @@ impl JsonSerialize for Project {
 
         targets.push(stage)?;
 
-        for (_, sprite) in self.sprites.iter().enumerate() {
+        for sprite in self.sprites.iter() {
             targets.push(sprite.serialize()?)?;
             
             // serialize variable monitors
-            for (_, var) in sprite.obj.data.vars.iter().enumerate() {
+            for var in sprite.obj.data.vars.iter() {
                 if var.visible {
                     monitors.push(var.serialize_monitor()?)?;
                 }
             }
 
-            // TODO serialize list monitors
-            // for (_, list) in sprite.obj.data.lists.iter().enumerate() {
-            //     monitors.push(list.serialize_monitor()?)?;
-            // }
+            // serialize list monitors
+            for list in sprite.obj.data.lists.iter() {
+                if list.visible {
+                    monitors.push(list.serialize_monitor()?)?;
+                }
+            }
         }
 
         // serialize global variable monitors
-        for (_i, var) in self.data.borrow().vars.iter().enumerate() {
+        for var in self.data.borrow().vars.iter() {
             if var.visible {
                 monitors.push(var.serialize_monitor()?)?;
             }
         }
 
+        // serialize global list monitors
+        for list in self.data.borrow().lists.iter() {
+            if list.visible {
+                monitors.push(list.serialize_monitor()?)?;
+            }
+        }
+
+        let extensions = used_extensions(&targets)?;
+
         Ok(json::object! {
             "targets": targets,
             "monitors": monitors,
-            "extensions": [],
+            "extensions": extensions,
             "meta": {
                 "semver": "3.0.0",
                 "vm": "1.3.18",
@@ -0,0 +1,309 @@
+// reads what scratch needs to know about a sound from the headers of its file,
+// without decoding any audio
+
+/// Format of the samples of a sound
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleFormat {
+    Pcm,
+    Adpcm,
+    Mp3
+}
+
+impl SampleFormat {
+    /// The dataFormat of a sound in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SampleFormat::Mp3 => "mp3",
+            _ => "wav"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SoundInfo {
+    pub format: SampleFormat,
+    pub rate: u32,
+    pub sample_count: u32
+}
+
+fn u16_at(data: &[u8], i: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(i..i + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], i: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(i..i + 4)?.try_into().ok()?))
+}
+
+/// Reads the info of a sound file, given its extension
+pub fn read_info(ext: &str, data: &[u8]) -> Option<SoundInfo> {
+    match ext.to_lowercase().as_str() {
+        "wav" => wav_info(data),
+        "mp3" => mp3_info(data),
+        _ => None
+    }
+}
+
+// a RIFF file is a list of chunks, each starting with a 4 byte id and its size
+fn wav_info(data: &[u8]) -> Option<SoundInfo> {
+    if data.get(0..4)? != b"RIFF" || data.get(8..12)? != b"WAVE" {
+        return None;
+    }
+
+    let mut fmt = None;
+    let mut data_size = None;
+    let mut fact = None;
+
+    let mut i = 12;
+    while let (Some(id), Some(size)) = (data.get(i..i + 4), u32_at(data, i + 4)) {
+        let start = i + 8;
+
+        match id {
+            b"fmt " => fmt = data.get(start..start + size as usize),
+            b"data" => data_size = Some(size),
+            b"fact" => fact = u32_at(data, start),
+            _ => ()
+        }
+
+        // chunks are padded to an even size
+        i = start + size as usize + (size as usize & 1);
+    }
+
+    let fmt = fmt?;
+    let tag = u16_at(fmt, 0)?;
+    let rate = u32_at(fmt, 4)?;
+    let block_align = u16_at(fmt, 12)? as u32;
+    let data_size = data_size?;
+
+    if block_align == 0 {
+        return None;
+    }
+
+    let (format, sample_count) = match tag {
+        // integer or float pcm, or WAVE_FORMAT_EXTENSIBLE
+        1 | 3 | 0xFFFE => (SampleFormat::Pcm, data_size / block_align),
+
+        // ima adpcm, which is what scratch saves recordings as
+        0x11 => {
+            let samples_per_block = u16_at(fmt, 18)? as u32;
+            let count = fact.unwrap_or(data_size / block_align * samples_per_block);
+            (SampleFormat::Adpcm, count)
+        },
+
+        _ => return None
+    };
+
+    Some(SoundInfo { format, rate, sample_count })
+}
+
+// bitrates in kbps, by version and layer
+const BITRATES: [[u32; 15]; 5] = [
+    [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448], // mpeg 1 layer I
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384], // mpeg 1 layer II
+    [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320], // mpeg 1 layer III
+    [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256], // mpeg 2 and 2.5 layer I
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160] // mpeg 2 and 2.5 layers II and III
+];
+
+struct Frame {
+    rate: u32,
+    samples: u32,
+    length: usize
+}
+
+// parse the 4 byte header of an mpeg audio frame
+fn frame(header: &[u8]) -> Option<Frame> {
+    let header = u32::from_be_bytes(header.try_into().ok()?);
+
+    if header >> 21 != 0x7FF {
+        return None;
+    }
+
+    let version = (header >> 19) & 3; // 0 = 2.5, 2 = 2, 3 = 1
+    let layer = (header >> 17) & 3; // 1 = III, 2 = II, 3 = I
+    let bitrate_index = ((header >> 12) & 15) as usize;
+    let rate_index = ((header >> 10) & 3) as usize;
+    let padding = (header >> 9) & 1;
+
+    // free format bitrates can't be measured without decoding
+    if version == 1 || layer == 0 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version == 3;
+    let bitrate = BITRATES[match (mpeg1, layer) {
+        (true, 3) => 0,
+        (true, 2) => 1,
+        (true, _) => 2,
+        (false, 3) => 3,
+        (false, _) => 4
+    }][bitrate_index] * 1000;
+
+    let rate = [44100, 48000, 32000][rate_index] >> match version {
+        3 => 0,
+        2 => 1,
+        _ => 2
+    };
+
+    let (samples, length) = match layer {
+        3 => (384, (12 * bitrate / rate + padding) * 4),
+        2 => (1152, 144 * bitrate / rate + padding),
+        _ if mpeg1 => (1152, 144 * bitrate / rate + padding),
+        _ => (576, 72 * bitrate / rate + padding)
+    };
+
+    Some(Frame { rate, samples, length: length as usize })
+}
+
+fn mp3_info(data: &[u8]) -> Option<SoundInfo> {
+    let mut i = 0;
+
+    // skip an ID3v2 tag, whose size is stored in 7 bits of each byte
+    if data.get(0..3)? == b"ID3" {
+        let size = data.get(6..10)?.iter().fold(0, |size, b| (size << 7) | (*b as usize & 0x7F));
+        i = 10 + size;
+    }
+
+    let mut rate = None;
+    let mut sample_count = 0;
+
+    while let Some(frame) = data.get(i..i + 4).and_then(frame) {
+        let body = data.get(i + 4..(i + frame.length).min(data.len()))?;
+
+        // the first frame of a vbr file can hold a Xing or Info header instead of audio
+        let tagged = rate.is_none() && body.windows(4).take(40).any(|w| w == b"Xing" || w == b"Info");
+
+        if !tagged {
+            sample_count += frame.samples;
+        }
+
+        rate.get_or_insert(frame.rate);
+        i += frame.length;
+    }
+
+    Some(SoundInfo {
+        format: SampleFormat::Mp3,
+        rate: rate?,
+        sample_count
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a RIFF file with a fmt chunk, the given chunks and a data chunk of `data_size` bytes
+    fn wav(tag: u16, rate: u32, block_align: u16, fmt_extra: &[u8], chunks: &[(&[u8; 4], &[u8])], data_size: u32) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend(tag.to_le_bytes());
+        fmt.extend(1u16.to_le_bytes()); // channels
+        fmt.extend(rate.to_le_bytes());
+        fmt.extend((rate * block_align as u32).to_le_bytes()); // bytes per second
+        fmt.extend(block_align.to_le_bytes());
+        fmt.extend(16u16.to_le_bytes()); // bits per sample
+        fmt.extend(fmt_extra);
+
+        let mut body = b"WAVE".to_vec();
+        let mut chunk = |id: &[u8; 4], data: &[u8]| {
+            body.extend(id);
+            body.extend((data.len() as u32).to_le_bytes());
+            body.extend(data);
+
+            if data.len() % 2 == 1 {
+                body.push(0);
+            }
+        };
+
+        chunk(b"fmt ", &fmt);
+
+        for (id, data) in chunks {
+            chunk(id, data);
+        }
+
+        chunk(b"data", &vec![0; data_size as usize]);
+
+        let mut file = b"RIFF".to_vec();
+        file.extend((body.len() as u32).to_le_bytes());
+        file.extend(body);
+        file
+    }
+
+    // an mpeg 1 layer III frame at 128 kbps and 44100 Hz, which is 417 bytes long
+    fn mp3_frame(body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0xFF, 0xFB, 0x90, 0x00];
+        frame.extend(body);
+        frame.resize(417, 0);
+        frame
+    }
+
+    #[test]
+    fn pcm_wav() {
+        let info = read_info("wav", &wav(1, 22050, 2, &[], &[], 100)).unwrap();
+        assert_eq!(info.format, SampleFormat::Pcm);
+        assert_eq!(info.rate, 22050);
+        assert_eq!(info.sample_count, 50);
+    }
+
+    #[test]
+    fn odd_sized_chunks_are_padded() {
+        let data = wav(1, 8000, 2, &[], &[(b"LIST", b"abc")], 10);
+        assert_eq!(read_info("WAV", &data).unwrap().sample_count, 5);
+    }
+
+    #[test]
+    fn adpcm_wav() {
+        // cbSize, then 505 samples in each block of 256 bytes
+        let extra = [2, 0, 0xF9, 0x01];
+
+        let info = read_info("wav", &wav(0x11, 22050, 256, &extra, &[], 512)).unwrap();
+        assert_eq!(info.format, SampleFormat::Adpcm);
+        assert_eq!(info.sample_count, 1010);
+
+        // the fact chunk has the exact count, since the last block can be partly empty
+        let info = read_info("wav", &wav(0x11, 22050, 256, &extra, &[(b"fact", &1000u32.to_le_bytes())], 512)).unwrap();
+        assert_eq!(info.sample_count, 1000);
+    }
+
+    #[test]
+    fn invalid_wav() {
+        assert!(read_info("wav", b"RIFX\0\0\0\0WAVE").is_none());
+        assert!(read_info("wav", &wav(2, 8000, 2, &[], &[], 10)).is_none()); // ms adpcm
+        assert!(read_info("wav", &wav(1, 8000, 0, &[], &[], 10)).is_none());
+
+        let mut no_data = wav(1, 8000, 2, &[], &[], 0);
+        no_data.truncate(no_data.len() - 8);
+        assert!(read_info("wav", &no_data).is_none());
+    }
+
+    #[test]
+    fn mp3_frames() {
+        let data = [mp3_frame(&[]), mp3_frame(&[])].concat();
+        let info = read_info("mp3", &data).unwrap();
+        assert_eq!(info.format, SampleFormat::Mp3);
+        assert_eq!(info.rate, 44100);
+        assert_eq!(info.sample_count, 2304);
+    }
+
+    #[test]
+    fn mp3_tags_are_skipped() {
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00\x0a".to_vec();
+        data.extend([0; 10]);
+
+        // a Xing header in the first frame holds no audio
+        let mut xing = vec![0; 32];
+        xing.extend(b"Xing");
+        data.extend(mp3_frame(&xing));
+        data.extend(mp3_frame(&[]));
+
+        assert_eq!(read_info("mp3", &data).unwrap().sample_count, 1152);
+    }
+
+    #[test]
+    fn invalid_mp3() {
+        assert!(read_info("mp3", b"not an mp3 file").is_none());
+        assert!(read_info("mp3", &[0xFF, 0xFB, 0x90]).is_none());
+
+        // a free format bitrate
+        assert!(read_info("mp3", &[0xFF, 0xFB, 0x00, 0x00]).is_none());
+        assert!(read_info("ogg", &mp3_frame(&[])).is_none());
+    }
+}
@@ -0,0 +1,344 @@
+// finds the format and size of a costume from the contents of its file
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Svg,
+    Png,
+    Jpeg
+}
+
+impl ImageFormat {
+    /// The dataFormat of a costume in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "svg",
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg"
+        }
+    }
+
+    pub fn is_bitmap(&self) -> bool {
+        *self != ImageFormat::Svg
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+
+    // size in pixels, if it could be read
+    pub size: Option<(f64, f64)>,
+
+    // position of the top left corner. the viewBox of an svg can start anywhere
+    pub origin: (f64, f64)
+}
+
+/// A point on the edge or in the middle of an image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight
+}
+
+impl Anchor {
+    /// Finds an anchor by its name, such as "center", "top-left" or "bottom"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.replace('_', "-").to_lowercase().as_str() {
+            "top-left" => Some(Anchor::TopLeft),
+            "top" => Some(Anchor::Top),
+            "top-right" => Some(Anchor::TopRight),
+            "left" => Some(Anchor::Left),
+            "center" => Some(Anchor::Center),
+            "right" => Some(Anchor::Right),
+            "bottom-left" => Some(Anchor::BottomLeft),
+            "bottom" => Some(Anchor::Bottom),
+            "bottom-right" => Some(Anchor::BottomRight),
+            _ => None
+        }
+    }
+
+    /// Position of the anchor in an image, with y going down
+    pub fn position(&self, info: &ImageInfo) -> Option<(f64, f64)> {
+        let (width, height) = info.size?;
+
+        let (fx, fy) = match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0)
+        };
+
+        Some((info.origin.0 + width * fx, info.origin.1 + height * fy))
+    }
+}
+
+fn u16_at(data: &[u8], i: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(i..i + 2)?.try_into().ok()?))
+}
+
+fn u32_at(data: &[u8], i: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?))
+}
+
+/// Reads the format and size of an image. Bitmaps are recognized by their contents
+/// rather than the extension of their file.
+pub fn read_info(data: &[u8]) -> Option<ImageInfo> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(ImageInfo { format: ImageFormat::Png, size: png_size(data), origin: (0.0, 0.0) });
+    }
+
+    if data.starts_with(&[0xFF, 0xD8]) {
+        return Some(ImageInfo { format: ImageFormat::Jpeg, size: jpeg_size(data), origin: (0.0, 0.0) });
+    }
+
+    let text = String::from_utf8_lossy(data);
+    let start = text.find("<svg")?;
+    let tag = &text[start..start + text[start..].find('>')?];
+
+    // scratch measures svgs by their viewBox, and falls back to their width and height
+    let view_box = attribute(tag, "viewBox").and_then(|v| {
+        let nums: Vec<f64> = v.split(|ch: char| ch == ',' || ch.is_whitespace())
+            .filter(|num| !num.is_empty())
+            .map(|num| num.parse().ok())
+            .collect::<Option<_>>()?;
+
+        match nums.as_slice() {
+            [x, y, width, height] => Some(((*x, *y), (*width, *height))),
+            _ => None
+        }
+    });
+
+    let (origin, size) = match view_box {
+        Some((origin, size)) => (origin, Some(size)),
+        None => {
+            let width = attribute(tag, "width").and_then(length);
+            let height = attribute(tag, "height").and_then(length);
+            ((0.0, 0.0), width.zip(height))
+        }
+    };
+
+    Some(ImageInfo { format: ImageFormat::Svg, size, origin })
+}
+
+// the value of an attribute of an xml tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+
+    while let Some(i) = rest.find(name) {
+        let before = rest[..i].chars().next_back();
+        let after = rest[i + name.len()..].trim_start();
+        rest = &rest[i + name.len()..];
+
+        // skip matches inside of other names or values, like the width in stroke-width
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+
+        if let Some(value) = after.strip_prefix('=') {
+            // values must be quoted in xml
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|ch| *ch == '"' || *ch == '\'')?;
+            let value = &value[1..];
+            return Some(&value[..value.find(quote)?]);
+        }
+    }
+
+    None
+}
+
+// a length in pixels. other units can't be converted without knowing the document
+fn length(value: &str) -> Option<f64> {
+    value.trim().trim_end_matches("px").parse().ok()
+}
+
+// the IHDR chunk always comes first
+fn png_size(data: &[u8]) -> Option<(f64, f64)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+
+    Some((u32_at(data, 16)? as f64, u32_at(data, 20)? as f64))
+}
+
+// walk the segments of the file until a start of frame, which holds the size
+fn jpeg_size(data: &[u8]) -> Option<(f64, f64)> {
+    let mut i = 2;
+
+    loop {
+        if *data.get(i)? != 0xFF {
+            return None;
+        }
+
+        let marker = *data.get(i + 1)?;
+
+        match marker {
+            // padding between segments
+            0xFF => {
+                i += 1;
+                continue;
+            },
+
+            // markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                i += 2;
+                continue;
+            },
+
+            // SOF0 to SOF15, except for DHT, JPG and DAC which share the range
+            0xC0..=0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC => {
+                let height = u16_at(data, i + 5)?;
+                let width = u16_at(data, i + 7)?;
+                return Some((width as f64, height as f64));
+            },
+
+            _ => i += 2 + u16_at(data, i + 2)? as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend(width.to_be_bytes());
+        data.extend(height.to_be_bytes());
+        data.extend([8, 6, 0, 0, 0]);
+        data
+    }
+
+    // a segment with a marker and its contents, whose length counts the length itself
+    fn segment(marker: u8, contents: &[u8]) -> Vec<u8> {
+        let mut data = vec![0xFF, marker];
+        data.extend((contents.len() as u16 + 2).to_be_bytes());
+        data.extend(contents);
+        data
+    }
+
+    fn sof(marker: u8, width: u16, height: u16) -> Vec<u8> {
+        let mut contents = vec![8];
+        contents.extend(height.to_be_bytes());
+        contents.extend(width.to_be_bytes());
+        contents.extend([1, 1, 0x11, 0]);
+        segment(marker, &contents)
+    }
+
+    #[test]
+    fn png_size() {
+        let info = read_info(&png(64, 30)).unwrap();
+        assert_eq!(info.format, ImageFormat::Png);
+        assert_eq!(info.size, Some((64.0, 30.0)));
+        assert_eq!(info.origin, (0.0, 0.0));
+    }
+
+    #[test]
+    fn png_without_header() {
+        let mut data = png(64, 30);
+        data[12..16].copy_from_slice(b"IDAT");
+        assert_eq!(read_info(&data).unwrap().size, None);
+        assert_eq!(read_info(&data[..20]).unwrap().size, None);
+    }
+
+    #[test]
+    fn jpeg_size() {
+        let data = [
+            vec![0xFF, 0xD8],
+            segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"),
+            segment(0xC4, &[0; 5]), // a huffman table shares the range of the frame markers
+            vec![0xFF], // padding
+            sof(0xC2, 640, 480),
+            vec![0xFF, 0xD9]
+        ].concat();
+
+        let info = read_info(&data).unwrap();
+        assert_eq!(info.format, ImageFormat::Jpeg);
+        assert_eq!(info.size, Some((640.0, 480.0)));
+    }
+
+    #[test]
+    fn jpeg_without_frame() {
+        let data = [vec![0xFF, 0xD8], segment(0xE0, &[0; 14])].concat();
+        assert_eq!(read_info(&data).unwrap().size, None);
+
+        // a segment which doesn't start with a marker
+        let data = [vec![0xFF, 0xD8, 0x00], sof(0xC0, 1, 1)].concat();
+        assert_eq!(read_info(&data).unwrap().size, None);
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(read_info(b"GIF89a").is_none());
+        assert!(read_info(b"").is_none());
+    }
+
+    #[test]
+    fn extensions() {
+        assert_eq!(ImageFormat::Jpeg.extension(), "jpg");
+        assert!(ImageFormat::Png.is_bitmap());
+        assert!(!ImageFormat::Svg.is_bitmap());
+    }
+
+    fn svg_info(svg: &str) -> ImageInfo {
+        let info = read_info(svg.as_bytes()).unwrap();
+        assert_eq!(info.format, ImageFormat::Svg);
+        info
+    }
+
+    #[test]
+    fn svg_view_box() {
+        let info = svg_info(r#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" width="10" viewBox="-5 10,96 100.5"><g/></svg>"#);
+        assert_eq!(info.size, Some((96.0, 100.5)));
+        assert_eq!(info.origin, (-5.0, 10.0));
+    }
+
+    #[test]
+    fn svg_width_and_height() {
+        let info = svg_info("<svg stroke-width='3' width = '48px'\n\theight=\"50\">");
+        assert_eq!(info.size, Some((48.0, 50.0)));
+        assert_eq!(info.origin, (0.0, 0.0));
+
+        // other units would need the size of the document
+        assert_eq!(svg_info(r#"<svg width="1in" height="1in">"#).size, None);
+        assert_eq!(svg_info(r#"<svg viewBox="0 0 10">"#).size, None);
+    }
+
+    #[test]
+    fn svg_attributes() {
+        assert_eq!(attribute(r#"<svg data-width="1" width="2""#, "width"), Some("2"));
+        assert_eq!(attribute(r#"<svg title="width=3" width="4""#, "width"), Some("4"));
+        assert_eq!(attribute("<svg width='5'", "width"), Some("5"));
+        assert_eq!(attribute(r#"<svg height="5""#, "width"), None);
+
+        // values have to be quoted, and end with their quote
+        assert_eq!(attribute("<svg width=5", "width"), None);
+        assert_eq!(attribute("<svg width=\u{e9}5", "width"), None);
+        assert_eq!(attribute(r#"<svg width="5"#, "width"), None);
+    }
+
+    #[test]
+    fn anchors() {
+        let info = svg_info(r#"<svg viewBox="10 20 100 50">"#);
+
+        assert_eq!(Anchor::from_name("top-left").unwrap().position(&info), Some((10.0, 20.0)));
+        assert_eq!(Anchor::from_name("Center").unwrap().position(&info), Some((60.0, 45.0)));
+        assert_eq!(Anchor::from_name("bottom_right").unwrap().position(&info), Some((110.0, 70.0)));
+        assert_eq!(Anchor::from_name("bottom").unwrap().position(&info), Some((60.0, 70.0)));
+        assert!(Anchor::from_name("middle").is_none());
+
+        let unsized_svg = svg_info("<svg>");
+        assert_eq!(Anchor::Center.position(&unsized_svg), None);
+    }
+}
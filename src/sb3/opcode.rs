@@ -7,13 +7,99 @@ pub enum StopOption {
     Other
 }
 
+// the type of a custom block argument
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgumentType {
+    StringNumber,
+    Boolean
+}
+
+#[derive(Debug, Clone)]
+pub struct Argument {
+    pub id: String,
+    pub name: String,
+    pub arg_type: ArgumentType
+}
+
+#[derive(Debug, Clone)]
+/// The signature of a custom block, shared by its definition and calls
+pub struct Prototype {
+    pub name: String,
+    pub args: Vec<Argument>,
+
+    // run without screen refresh
+    pub warp: bool
+}
+
+impl Prototype {
+    pub fn new(name: &str, args: Vec<(String, ArgumentType)>, warp: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            args: args.into_iter().map(|(name, arg_type)| Argument {
+                id: uuid(),
+                name,
+                arg_type
+            }).collect(),
+            warp
+        }
+    }
+
+    // the text of the block, where each argument is a %s or %b slot
+    fn proccode(&self) -> String {
+        let mut res = self.name.clone();
+
+        for arg in self.args.iter() {
+            res += match arg.arg_type {
+                ArgumentType::StringNumber => " %s",
+                ArgumentType::Boolean => " %b"
+            };
+        }
+
+        res
+    }
+
+    // the mutation of a call, or of the prototype itself if `declaration` is true
+    fn mutation(&self, declaration: bool) -> JsonValue {
+        // lists in mutations are stored as json strings
+        let ids: Vec<String> = self.args.iter().map(|arg| arg.id.clone()).collect();
+
+        let mut res = json::object! {
+            "tagName": "mutation",
+            "children": [],
+            "proccode": self.proccode(),
+            "argumentids": json::stringify(ids),
+            "warp": self.warp.to_string()
+        };
+
+        if declaration {
+            let names: Vec<String> = self.args.iter().map(|arg| arg.name.clone()).collect();
+            let defaults: Vec<&str> = self.args.iter().map(|arg| match arg.arg_type {
+                ArgumentType::StringNumber => "",
+                ArgumentType::Boolean => "false"
+            }).collect();
+
+            res["argumentnames"] = json::stringify(names).into();
+            res["argumentdefaults"] = json::stringify(defaults).into();
+        }
+
+        res
+    }
+}
+
+// value given to an argument of a custom block
+#[derive(Debug)]
+pub enum ArgumentInput {
+    StringNumber(UserInput),
+    Boolean(ReporterInput)
+}
+
 #[derive(Debug)]
 pub enum SensingType {
     Loudness,
     Timer
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum KeyOption {
     Any,
     Space,
@@ -25,14 +111,93 @@ pub enum KeyOption {
     Zero,One,Two,Three,Four,Five,Six,Seven,Eight,Nine
 }
 
+const LETTER_KEYS: [KeyOption; 26] = [
+    KeyOption::A, KeyOption::B, KeyOption::C, KeyOption::D, KeyOption::E, KeyOption::F, KeyOption::G,
+    KeyOption::H, KeyOption::I, KeyOption::J, KeyOption::K, KeyOption::L, KeyOption::M, KeyOption::N,
+    KeyOption::O, KeyOption::P, KeyOption::Q, KeyOption::R, KeyOption::S, KeyOption::T, KeyOption::U,
+    KeyOption::V, KeyOption::W, KeyOption::X, KeyOption::Y, KeyOption::Z
+];
+
+const DIGIT_KEYS: [KeyOption; 10] = [
+    KeyOption::Zero, KeyOption::One, KeyOption::Two, KeyOption::Three, KeyOption::Four,
+    KeyOption::Five, KeyOption::Six, KeyOption::Seven, KeyOption::Eight, KeyOption::Nine
+];
+
+impl KeyOption {
+    /// Finds a key by its name in Scratch, such as "space", "up arrow" or "a".
+    /// Underscores may be used in place of spaces.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.replace('_', " ").to_lowercase().as_str() {
+            "any" => Some(KeyOption::Any),
+            "space" => Some(KeyOption::Space),
+            "up arrow" => Some(KeyOption::UpArrow),
+            "down arrow" => Some(KeyOption::DownArrow),
+            "right arrow" => Some(KeyOption::RightArrow),
+            "left arrow" => Some(KeyOption::LeftArrow),
+
+            name => match name.as_bytes() {
+                [ch @ b'a'..=b'z'] => Some(LETTER_KEYS[(ch - b'a') as usize]),
+                [ch @ b'0'..=b'9'] => Some(DIGIT_KEYS[(ch - b'0') as usize]),
+                _ => None
+            }
+        }
+    }
+
+    /// The name of the key in Scratch
+    pub fn name(&self) -> String {
+        match self {
+            KeyOption::Any => "any".to_string(),
+            KeyOption::Space => "space".to_string(),
+            KeyOption::UpArrow => "up arrow".to_string(),
+            KeyOption::DownArrow => "down arrow".to_string(),
+            KeyOption::RightArrow => "right arrow".to_string(),
+            KeyOption::LeftArrow => "left arrow".to_string(),
+
+            key => match LETTER_KEYS.iter().position(|v| v == key) {
+                Some(i) => ((b'a' + i as u8) as char).to_string(),
+                None => DIGIT_KEYS.iter().position(|v| v == key).unwrap().to_string()
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum GraphicEffect {
     Color,
     Fisheye,
     Whirl,
     Pixelate,
-    Moasic,
-    Brightness
+    Mosaic,
+    Brightness,
+    Ghost
+}
+
+impl GraphicEffect {
+    /// Finds a graphic effect by its name in Scratch, such as "color" or "ghost"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "color" => Some(GraphicEffect::Color),
+            "fisheye" => Some(GraphicEffect::Fisheye),
+            "whirl" => Some(GraphicEffect::Whirl),
+            "pixelate" => Some(GraphicEffect::Pixelate),
+            "mosaic" => Some(GraphicEffect::Mosaic),
+            "brightness" => Some(GraphicEffect::Brightness),
+            "ghost" => Some(GraphicEffect::Ghost),
+            _ => None
+        }
+    }
+
+    fn field(&self) -> JsonValue {
+        json::array![match self {
+            GraphicEffect::Color => "COLOR",
+            GraphicEffect::Fisheye => "FISHEYE",
+            GraphicEffect::Whirl => "WHIRL",
+            GraphicEffect::Pixelate => "PIXELATE",
+            GraphicEffect::Mosaic => "MOSAIC",
+            GraphicEffect::Brightness => "BRIGHTNESS",
+            GraphicEffect::Ghost => "GHOST"
+        }, json::Null]
+    }
 }
 
 #[derive(Debug)]
@@ -53,7 +218,16 @@ pub enum ForwardBack {
 
 #[derive(Debug)]
 pub enum NumberName {
-    Number, Nmae
+    Number, Name
+}
+
+impl NumberName {
+    fn field(&self) -> JsonValue {
+        json::array![match self {
+            NumberName::Number => "number",
+            NumberName::Name => "name"
+        }, json::Null]
+    }
 }
 
 #[derive(Debug)]
@@ -69,6 +243,60 @@ pub enum PositionTarget {
     Sprite(String),
 }
 
+impl PointTarget {
+    /// Finds a target by its name in a menu, where "_mouse_" is the mouse pointer
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "_mouse_" => PointTarget::MousePointer,
+            name => PointTarget::Sprite(name.to_string())
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            PointTarget::MousePointer => "_mouse_",
+            PointTarget::Sprite(name) => name
+        }
+    }
+}
+
+impl SpriteOption {
+    /// Finds a sprite by its name in a menu, where "_myself_" is the sprite itself
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "_myself_" => SpriteOption::Myself,
+            name => SpriteOption::Other(name.to_string())
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            SpriteOption::Myself => "_myself_",
+            SpriteOption::Other(name) => name
+        }
+    }
+}
+
+impl PositionTarget {
+    /// Finds a target by its name in a menu, where "_random_" is a random position
+    /// and "_mouse_" is the mouse pointer
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "_random_" => PositionTarget::RandomPosition,
+            "_mouse_" => PositionTarget::MousePointer,
+            name => PositionTarget::Sprite(name.to_string())
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            PositionTarget::RandomPosition => "_random_",
+            PositionTarget::MousePointer => "_mouse_",
+            PositionTarget::Sprite(name) => name
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum TouchingOption {
     MousePointer,
@@ -76,11 +304,57 @@ pub enum TouchingOption {
     Sprite(String)
 }
 
+impl TouchingOption {
+    /// Finds a target by its name in a menu, where "_mouse_" is the mouse pointer
+    /// and "_edge_" is the edge of the stage
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "_mouse_" => TouchingOption::MousePointer,
+            "_edge_" => TouchingOption::Edge,
+            name => TouchingOption::Sprite(name.to_string())
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            TouchingOption::MousePointer => "_mouse_",
+            TouchingOption::Edge => "_edge_",
+            TouchingOption::Sprite(name) => name
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Color {
-    r: f64,
-    g: f64,
-    b: f64
+    pub r: f64,
+    pub g: f64,
+    pub b: f64
+}
+
+impl Color {
+    /// Parses a color in the form #rrggbb
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#')?;
+
+        // from_str_radix would also take a sign
+        if hex.len() != 6 || !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok().map(f64::from);
+        Some(Color { r: channel(0)?, g: channel(2)?, b: channel(4)? })
+    }
+
+    /// The color in the form #rrggbb, with each channel rounded and clamped to 0-255
+    pub fn hex(&self) -> String {
+        let channel = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+        format!("#{:02x}{:02x}{:02x}", channel(self.r), channel(self.g), channel(self.b))
+    }
+
+    // a color picker input holding this color
+    fn input(&self) -> UserInput {
+        UserInput::new(Value::String(self.hex()), None)
+    }
 }
 
 #[derive(Debug)]
@@ -89,6 +363,23 @@ pub enum ObjectMenu {
     Sprite(String)
 }
 
+impl ObjectMenu {
+    /// Finds an object by its name in a menu, where "_stage_" is the stage
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "_stage_" => ObjectMenu::Stage,
+            name => ObjectMenu::Sprite(name.to_string())
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            ObjectMenu::Stage => "_stage_",
+            ObjectMenu::Sprite(name) => name
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ObjectProperty {
     XPosition,
@@ -103,6 +394,43 @@ pub enum ObjectProperty {
     Data(String)
 }
 
+impl ObjectProperty {
+    /// Finds a property by its name in Scratch, such as "x position" or "costume #".
+    /// Any other name is a variable of the object
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "x position" => ObjectProperty::XPosition,
+            "y position" => ObjectProperty::YPosition,
+            "direction" => ObjectProperty::Direction,
+            "costume #" => ObjectProperty::CostumeNumber,
+            "costume name" => ObjectProperty::CostumeName,
+            "size" => ObjectProperty::Size,
+            "volume" => ObjectProperty::Volume,
+            "backdrop #" => ObjectProperty::BackdropNumber,
+            "backdrop name" => ObjectProperty::BackdropName,
+            name => ObjectProperty::Data(name.to_string())
+        }
+    }
+
+    /// The name of the property in the menu for the stage or a sprite. The costumes of the
+    /// stage are its backdrops, and it has no position, direction or size
+    pub fn name(&self, stage: bool) -> Option<&str> {
+        Some(match (self, stage) {
+            (ObjectProperty::XPosition, false) => "x position",
+            (ObjectProperty::YPosition, false) => "y position",
+            (ObjectProperty::Direction, false) => "direction",
+            (ObjectProperty::CostumeNumber, false) => "costume #",
+            (ObjectProperty::CostumeName, false) => "costume name",
+            (ObjectProperty::Size, false) => "size",
+            (ObjectProperty::CostumeNumber | ObjectProperty::BackdropNumber, true) => "backdrop #",
+            (ObjectProperty::CostumeName | ObjectProperty::BackdropName, true) => "backdrop name",
+            (ObjectProperty::Volume, _) => "volume",
+            (ObjectProperty::Data(name), _) => name,
+            _ => return None
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum TimeOption {
     Year,
@@ -114,12 +442,231 @@ pub enum TimeOption {
     Second
 }
 
+impl TimeOption {
+    /// Finds a unit of time by its name in Scratch, such as "year" or "day of week".
+    /// Underscores may be used in place of spaces.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.replace('_', " ").to_lowercase().as_str() {
+            "year" => Some(TimeOption::Year),
+            "month" => Some(TimeOption::Month),
+            "date" => Some(TimeOption::Date),
+            "day of week" => Some(TimeOption::DayOfWeek),
+            "hour" => Some(TimeOption::Hour),
+            "minute" => Some(TimeOption::Minute),
+            "second" => Some(TimeOption::Second),
+            _ => None
+        }
+    }
+
+    fn field(&self) -> JsonValue {
+        json::array![match self {
+            TimeOption::Year => "YEAR",
+            TimeOption::Month => "MONTH",
+            TimeOption::Date => "DATE",
+            TimeOption::DayOfWeek => "DAYOFWEEK",
+            TimeOption::Hour => "HOUR",
+            TimeOption::Minute => "MINUTE",
+            TimeOption::Second => "SECOND"
+        }, json::Null]
+    }
+}
+
 #[derive(Debug)]
 pub enum SoundEffect {
     Pan,
     Pitch
 }
 
+impl SoundEffect {
+    /// Finds a sound effect by its name, "pan" or "pitch"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "pan" => Some(SoundEffect::Pan),
+            "pitch" => Some(SoundEffect::Pitch),
+            _ => None
+        }
+    }
+
+    fn field(&self) -> JsonValue {
+        json::array![match self {
+            SoundEffect::Pan => "PAN",
+            SoundEffect::Pitch => "PITCH"
+        }, json::Null]
+    }
+}
+
+/// A part of the pen's color which can be set or changed on its own
+#[derive(Debug)]
+pub enum PenColorParam {
+    Color,
+    Saturation,
+    Brightness,
+    Transparency
+}
+
+impl PenColorParam {
+    /// Finds a color parameter by its name, such as "color" or "transparency"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "color" => Some(PenColorParam::Color),
+            "saturation" => Some(PenColorParam::Saturation),
+            "brightness" => Some(PenColorParam::Brightness),
+            "transparency" => Some(PenColorParam::Transparency),
+            _ => None
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            PenColorParam::Color => "color",
+            PenColorParam::Saturation => "saturation",
+            PenColorParam::Brightness => "brightness",
+            PenColorParam::Transparency => "transparency"
+        }
+    }
+}
+
+/// A drum of the music extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Drum {
+    SnareDrum = 1,
+    BassDrum,
+    SideStick,
+    CrashCymbal,
+    OpenHiHat,
+    ClosedHiHat,
+    Tambourine,
+    HandClap,
+    Claves,
+    WoodBlock,
+    Cowbell,
+    Triangle,
+    Bongo,
+    Conga,
+    Cabasa,
+    Guiro,
+    Vibraslap,
+    Cuica
+}
+
+const DRUMS: [Drum; 18] = [
+    Drum::SnareDrum, Drum::BassDrum, Drum::SideStick, Drum::CrashCymbal, Drum::OpenHiHat, Drum::ClosedHiHat,
+    Drum::Tambourine, Drum::HandClap, Drum::Claves, Drum::WoodBlock, Drum::Cowbell, Drum::Triangle,
+    Drum::Bongo, Drum::Conga, Drum::Cabasa, Drum::Guiro, Drum::Vibraslap, Drum::Cuica
+];
+
+impl Drum {
+    /// Finds a drum by its name in Scratch, such as "snare drum" or "open hi-hat".
+    /// Underscores may be used in place of spaces.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.replace('_', " ").to_lowercase();
+        DRUMS.iter().find(|drum| drum.name() == name).copied()
+    }
+
+    /// Finds a drum by its number in the menu, starting from 1
+    pub fn from_number(num: usize) -> Option<Self> {
+        DRUMS.get(num.checked_sub(1)?).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Drum::SnareDrum => "snare drum",
+            Drum::BassDrum => "bass drum",
+            Drum::SideStick => "side stick",
+            Drum::CrashCymbal => "crash cymbal",
+            Drum::OpenHiHat => "open hi-hat",
+            Drum::ClosedHiHat => "closed hi-hat",
+            Drum::Tambourine => "tambourine",
+            Drum::HandClap => "hand clap",
+            Drum::Claves => "claves",
+            Drum::WoodBlock => "wood block",
+            Drum::Cowbell => "cowbell",
+            Drum::Triangle => "triangle",
+            Drum::Bongo => "bongo",
+            Drum::Conga => "conga",
+            Drum::Cabasa => "cabasa",
+            Drum::Guiro => "guiro",
+            Drum::Vibraslap => "vibraslap",
+            Drum::Cuica => "cuica"
+        }
+    }
+}
+
+/// An instrument of the music extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instrument {
+    Piano = 1,
+    ElectricPiano,
+    Organ,
+    Guitar,
+    ElectricGuitar,
+    Bass,
+    Pizzicato,
+    Cello,
+    Trombone,
+    Clarinet,
+    Saxophone,
+    Flute,
+    WoodenFlute,
+    Bassoon,
+    Choir,
+    Vibraphone,
+    MusicBox,
+    SteelDrum,
+    Marimba,
+    SynthLead,
+    SynthPad
+}
+
+const INSTRUMENTS: [Instrument; 21] = [
+    Instrument::Piano, Instrument::ElectricPiano, Instrument::Organ, Instrument::Guitar,
+    Instrument::ElectricGuitar, Instrument::Bass, Instrument::Pizzicato, Instrument::Cello,
+    Instrument::Trombone, Instrument::Clarinet, Instrument::Saxophone, Instrument::Flute,
+    Instrument::WoodenFlute, Instrument::Bassoon, Instrument::Choir, Instrument::Vibraphone,
+    Instrument::MusicBox, Instrument::SteelDrum, Instrument::Marimba, Instrument::SynthLead,
+    Instrument::SynthPad
+];
+
+impl Instrument {
+    /// Finds an instrument by its name in Scratch, such as "piano" or "electric guitar".
+    /// Underscores may be used in place of spaces.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.replace('_', " ").to_lowercase();
+        INSTRUMENTS.iter().find(|instrument| instrument.name() == name).copied()
+    }
+
+    /// Finds an instrument by its number in the menu, starting from 1
+    pub fn from_number(num: usize) -> Option<Self> {
+        INSTRUMENTS.get(num.checked_sub(1)?).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instrument::Piano => "piano",
+            Instrument::ElectricPiano => "electric piano",
+            Instrument::Organ => "organ",
+            Instrument::Guitar => "guitar",
+            Instrument::ElectricGuitar => "electric guitar",
+            Instrument::Bass => "bass",
+            Instrument::Pizzicato => "pizzicato",
+            Instrument::Cello => "cello",
+            Instrument::Trombone => "trombone",
+            Instrument::Clarinet => "clarinet",
+            Instrument::Saxophone => "saxophone",
+            Instrument::Flute => "flute",
+            Instrument::WoodenFlute => "wooden flute",
+            Instrument::Bassoon => "bassoon",
+            Instrument::Choir => "choir",
+            Instrument::Vibraphone => "vibraphone",
+            Instrument::MusicBox => "music box",
+            Instrument::SteelDrum => "steel drum",
+            Instrument::Marimba => "marimba",
+            Instrument::SynthLead => "synth lead",
+            Instrument::SynthPad => "synth pad"
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum MathOp {
     Abs,
@@ -152,7 +699,7 @@ pub enum Opcode {
     // obsolete: control_while
     // obsolete: control_for_each
     StartAsClone(),
-    CreateCloneOf(Box<Block>), CreateCloneOfMenu(SpriteOption),
+    CreateCloneOf(MenuInput), CreateCloneOfMenu(SpriteOption),
     DeleteThisClone(),
     // obsolete: control_get_counter
     // obsolete: control_incr_counter
@@ -167,7 +714,7 @@ pub enum Opcode {
     ChangeVariableBy(String, UserInput),
     ShowVariable(String),
     HideVariable(String),
-    // ListContents(), // idk what this does
+    ListContents(String),
     // ListIndexAll(), // idk what this does
     // ListIndexRandom(), // idk what this does
     AddToList(UserInput, String),
@@ -189,8 +736,8 @@ pub enum Opcode {
     WhenBroadcastReceived(String),
     WhenBackdropSwitchesTo(String),
     WhenGreaterThan(SensingType, UserInput),
-    Broadcast(Box<Block>), // holds an event_broadcast_menu block by default
-    BroadcastAndWait(Box<Block>), // holds an evente_broadcast_menu_block by default
+    Broadcast(UserInput), // the value is the name of the message, or of the menu under a reporter
+    BroadcastAndWait(UserInput),
     BroadcastMenu(String),
     WhenKeyPressed(KeyOption),
 
@@ -212,14 +759,14 @@ pub enum Opcode {
     Size(),
     // obsolete: ChangeStretchBy
     // obsolete: SetStretchTo
-    SwitchCostumeTo(Box<Block>), Costume(String),
+    SwitchCostumeTo(MenuInput), Costume(String),
     NextCostume(),
-    SwitchBackdropTo(Box<Block>), Backdrops(String),
+    SwitchBackdropTo(MenuInput), Backdrops(String),
     GoToFrontBack(FrontBack),
-    GoForwardBackwardLayers(ForwardBack),
+    GoForwardBackwardLayers(ForwardBack, UserInput),
     BackdropNumberName(NumberName),
     CostumeNumberName(NumberName),
-    SwitchBackdropToAndWait(Box<Block>),
+    SwitchBackdropToAndWait(MenuInput),
     NextBackdrop(),
     
     // Motion
@@ -227,12 +774,12 @@ pub enum Opcode {
     TurnRight(UserInput),
     TurnLeft(UserInput),
     PointInDirection(UserInput),
-    PointTowards(Box<Block>), PointTowardsMenu(PointTarget),
+    PointTowards(MenuInput), PointTowardsMenu(PointTarget),
     GoToXY(UserInput, UserInput),
-    GoTo(Box<Block>), GoToMenu(PositionTarget),
+    GoTo(MenuInput), GoToMenu(PositionTarget),
     GlideSecsToXY(UserInput, UserInput, UserInput),
     GlideToMenu(PositionTarget),
-    GlideTo(UserInput, Box<Block>),
+    GlideTo(UserInput, MenuInput),
     ChangeXBy(UserInput),
     SetX(UserInput),
     ChangeYBy(UserInput),
@@ -257,9 +804,9 @@ pub enum Opcode {
     Lt(UserInput, UserInput),
     Equals(UserInput, UserInput),
     Gt(UserInput, UserInput),
-    And(Box<Block>, Box<Block>),
-    Or(Box<Block>, Box<Block>),
-    Not(Box<Block>),
+    And(ReporterInput, ReporterInput),
+    Or(ReporterInput, ReporterInput),
+    Not(ReporterInput),
     Join(UserInput, UserInput),
     LetterOf(UserInput, UserInput),
     Length(UserInput),
@@ -269,26 +816,25 @@ pub enum Opcode {
     MathOp(MathOp, UserInput),
     
     // Procedures
-    // TODO how do custom procedures work?
-    ProceduresDefinition(),
-    ProceduresCall(),
-    ProceduresPrototype(),
+    ProceduresDefinition(Box<Block>), // holds a procedures_prototype block
+    ProceduresCall(Prototype, Vec<ArgumentInput>),
+    ProceduresPrototype(Prototype),
     ProceduresDeclaration(),
-    ArgumentReporterBoolean(),
-    ArgumentReporterStringNumber(),
+    ArgumentReporterBoolean(String),
+    ArgumentReporterStringNumber(String),
     ArgumentEditorBoolean(),
     ArgumentEditorStringNumber(),
 
     // Sensing
-    TouchingObject(Box<Block>),
+    TouchingObject(MenuInput),
     TouchingObjectMenu(TouchingOption),
     TouchingColor(Color),
     ColorIsTouchingColor(Color, Color),
-    DistanceTo(Box<Block>),
+    DistanceTo(MenuInput),
     DistanceToMenu(String),
     AskAndWait(UserInput),
     Answer(),
-    KeyPressed(Box<Block>),
+    KeyPressed(MenuInput),
     KeyOptions(KeyOption),
     MouseDown(),
     MouseX(),
@@ -299,7 +845,7 @@ pub enum Opcode {
     Timer(),
     ResetTimer(),
     OfObjectMenu(ObjectMenu),
-    Of(ObjectProperty, Box<Block>),
+    Of(ObjectProperty, MenuInput),
     Current(TimeOption),
     DaysSince2000(),
     Username(),
@@ -307,8 +853,8 @@ pub enum Opcode {
 
     // Sound
     SoundsMenu(String),
-    Play(Box<Block>),
-    PlayUntilDone(Box<Block>),
+    Play(MenuInput),
+    PlayUntilDone(MenuInput),
     StopAllSounds(),
     SetSoundEffectTo(SoundEffect, UserInput),
     ChangeSoundEffectBy(SoundEffect, UserInput),
@@ -318,71 +864,141 @@ pub enum Opcode {
     Volume(),
 
     // Pen
+    PenClear(),
+    PenStamp(),
     PenDown(),
     PenUp(),
-    // TODO more pen blocks (where are they?)
+    SetPenColorToColor(UserInput),
+    ChangePenColorParamBy(MenuInput, UserInput),
+    SetPenColorParamTo(MenuInput, UserInput),
+    PenColorParamMenu(PenColorParam),
+    ChangePenSizeBy(UserInput),
+    SetPenSizeTo(UserInput),
 
     // Music
-    MusicDrum(),
-    MusicPlayNote()
-    // TODO more music blocks (where are they?)
+    PlayDrumForBeats(MenuInput, UserInput),
+    DrumMenu(Drum),
+    RestForBeats(UserInput),
+    PlayNoteForBeats(MenuInput, UserInput),
+    Note(f64), // the note picker, which holds a MIDI note number
+    SetInstrument(MenuInput),
+    InstrumentMenu(Instrument),
+    SetTempo(UserInput),
+    ChangeTempo(UserInput),
+    GetTempo()
 }
 
-impl Block {
-    pub fn serialize(&self, block_list: &mut JsonValue, parent_uuid: Option<&String>) -> Result<JsonValue, SerializeError> {
-        // this function is a big todo, so disable warnings on unused variables for now
-        #![allow(unused_variables)]
+impl Opcode {
+    // the opcode of a block which only sprites have. the stage has no position or direction,
+    // so it has none of the motion blocks, and it can't draw, so erasing is the only pen block it has
+    fn sprite_only(&self) -> Option<&'static str> {
+        Some(match self {
+            Opcode::MoveSteps(_) => "motion_movesteps",
+            Opcode::TurnRight(_) => "motion_turnright",
+            Opcode::TurnLeft(_) => "motion_turnleft",
+            Opcode::PointInDirection(_) => "motion_pointindirection",
+            Opcode::PointTowardsMenu(_) => "motion_pointtowards_menu",
+            Opcode::PointTowards(_) => "motion_pointtowards",
+            Opcode::GoToMenu(_) => "motion_goto_menu",
+            Opcode::GoToXY(..) => "motion_gotoxy",
+            Opcode::GoTo(_) => "motion_goto",
+            Opcode::GlideSecsToXY(..) => "motion_glidesecstoxy",
+            Opcode::GlideToMenu(_) => "motion_glideto_menu",
+            Opcode::GlideTo(..) => "motion_glideto",
+            Opcode::ChangeXBy(_) => "motion_changexby",
+            Opcode::SetX(_) => "motion_setx",
+            Opcode::ChangeYBy(_) => "motion_changeyby",
+            Opcode::SetY(_) => "motion_sety",
+            Opcode::IfOnEdgeBounce() => "motion_ifonedgebounce",
+            Opcode::SetRotationStyle(_) => "motion_setrotationstyle",
+            Opcode::XPosition() => "motion_xposition",
+            Opcode::YPosition() => "motion_yposition",
+            Opcode::Direction() => "motion_direction",
+            Opcode::PenStamp() => "pen_stamp",
+            Opcode::PenDown() => "pen_penDown",
+            Opcode::PenUp() => "pen_penUp",
+            Opcode::SetPenColorToColor(_) => "pen_setPenColorToColor",
+            Opcode::ChangePenColorParamBy(..) => "pen_changePenColorParamBy",
+            Opcode::SetPenColorParamTo(..) => "pen_setPenColorParamTo",
+            Opcode::PenColorParamMenu(_) => "pen_menu_colorParam",
+            Opcode::ChangePenSizeBy(_) => "pen_changePenSizeBy",
+            Opcode::SetPenSizeTo(_) => "pen_setPenSizeTo",
+            _ => return None
+        })
+    }
+}
 
+impl Block {
+    pub fn serialize(&self, block_list: &mut JsonValue, obj: &Object, parent_uuid: Option<&String>) -> Result<JsonValue, SerializeError> {
         let mut res = JsonValue::new_object();
 
         let opcode_str: &str;
         let mut inputs = JsonValue::new_object();
-        let fields = JsonValue::new_object();
+        let mut fields = JsonValue::new_object();
+        let mut mutation: Option<JsonValue> = None;
+        let mut shadow = false;
+
+        // checked before the inputs are, so that a block which is rejected doesn't add any to the list
+        if let (true, Some(opcode)) = (obj.is_stage, self.opcode.sprite_only()) {
+            return Err(SerializeError::NotOnStage(opcode));
+        }
 
         match &self.opcode {
             // CONTROL //
             Opcode::Forever(substack) => {
                 opcode_str = "control_forever";
-                inputs["SUBSTACK"] = substack.serialize(block_list, &self.uuid)?;
+                inputs["SUBSTACK"] = substack.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::Repeat(times, substack) => {
                 opcode_str = "control_repeat";
-                inputs["TIMES"] = times.serialize(InputType::WholeNumber)?;
-                inputs["SUBSTACK"] = substack.serialize(block_list, &self.uuid)?;
+                inputs["TIMES"] = times.serialize(block_list, obj, &self.uuid, InputType::WholeNumber)?;
+                inputs["SUBSTACK"] = substack.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::If(condition, substack) => {
                 opcode_str = "control_if";
-                inputs["CONDITION"] = condition.serialize(block_list, &self.uuid)?;
-                inputs["SUBSTACK"] = substack.serialize(block_list, &self.uuid)?;
+                inputs["CONDITION"] = condition.serialize(block_list, obj, &self.uuid)?;
+                inputs["SUBSTACK"] = substack.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::IfElse(condition, substack, substack2) => {
                 opcode_str = "control_if_else";
-                inputs["CONDITION"] = condition.serialize(block_list, &self.uuid)?;
-                inputs["SUBSTACK"] = substack.serialize(block_list, &self.uuid)?;
-                inputs["SUBSTACK2"] = substack2.serialize(block_list, &self.uuid)?;
+                inputs["CONDITION"] = condition.serialize(block_list, obj, &self.uuid)?;
+                inputs["SUBSTACK"] = substack.serialize(block_list, obj, &self.uuid)?;
+                inputs["SUBSTACK2"] = substack2.serialize(block_list, obj, &self.uuid)?;
             },
 
-            Opcode::Stop(option) => { // TODO
-                panic!("control_stop unimplemented");
+            Opcode::Stop(option) => {
+                opcode_str = "control_stop";
+                fields["STOP_OPTION"] = json::array![match option {
+                    StopOption::All => "all",
+                    StopOption::Myself => "this script",
+                    StopOption::Other => "other scripts in sprite"
+                }, json::Null];
+
+                // only "other scripts" can have blocks placed after it
+                mutation = Some(json::object! {
+                    "tagName": "mutation",
+                    "children": [],
+                    "hasnext": matches!(option, StopOption::Other).to_string()
+                });
             },
 
             Opcode::Wait(duration) => {
                 opcode_str = "control_wait";
-                inputs["DURATION"] = duration.serialize(InputType::PositiveNumber)?;
+                inputs["DURATION"] = duration.serialize(block_list, obj, &self.uuid, InputType::PositiveNumber)?;
             },
 
             Opcode::WaitUntil(condition) => {
                 opcode_str = "control_wait_until";
-                inputs["CONDITION"] = condition.serialize(block_list, &self.uuid)?;
+                inputs["CONDITION"] = condition.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::RepeatUntil(condition, substack) => {
                 opcode_str = "control_repeat_until";
-                inputs["CONDITION"] = condition.serialize(block_list, &self.uuid)?;
-                inputs["SUBSTACK"] = substack.serialize(block_list, &self.uuid)?;
+                inputs["CONDITION"] = condition.serialize(block_list, obj, &self.uuid)?;
+                inputs["SUBSTACK"] = substack.serialize(block_list, obj, &self.uuid)?;
             },
 
             // obsolete: control_while
@@ -392,12 +1008,15 @@ impl Block {
                 opcode_str = "control_start_as_clone";
             },
 
-            Opcode::CreateCloneOfMenu(clone_option) => { // TODO
-                todo!("control_createcloneof_menu");
+            Opcode::CreateCloneOfMenu(option) => {
+                opcode_str = "control_create_clone_of_menu";
+                fields["CLONE_OPTION"] = json::array![option.name(), json::Null];
+                shadow = true;
             },
 
-            Opcode::CreateCloneOf(clone_option) => { // TODO
-                todo!("control_createcloneof");
+            Opcode::CreateCloneOf(option) => {
+                opcode_str = "control_create_clone_of";
+                inputs["CLONE_OPTION"] = option.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::DeleteThisClone() => {
@@ -413,68 +1032,100 @@ impl Block {
 
 
             // DATA //
-            Opcode::Variable(variable) => { // TODO
-                todo!("data_variable");
+            Opcode::Variable(variable) => {
+                opcode_str = "data_variable";
+                fields["VARIABLE"] = variable_field(obj, variable)?;
             },
 
-            Opcode::SetVariableTo(variable, value) => { // TODO
-                todo!("data_setvariableto");
+            Opcode::SetVariableTo(variable, value) => {
+                opcode_str = "data_setvariableto";
+                inputs["VALUE"] = value.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                fields["VARIABLE"] = variable_field(obj, variable)?;
             },
 
-            Opcode::ChangeVariableBy(variable, value) => { // TODO
-                todo!("data_changevariableby");
+            Opcode::ChangeVariableBy(variable, value) => {
+                opcode_str = "data_changevariableby";
+                inputs["VALUE"] = value.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                fields["VARIABLE"] = variable_field(obj, variable)?;
             },
 
-            Opcode::ShowVariable(variable) => { // TODO
-                todo!("data_showvariable");
+            Opcode::ShowVariable(variable) => {
+                opcode_str = "data_showvariable";
+                fields["VARIABLE"] = variable_field(obj, variable)?;
             },
 
-            Opcode::HideVariable(variable) => { // TODO
-                todo!("data_hidevariable");
+            Opcode::HideVariable(variable) => {
+                opcode_str = "data_hidevariable";
+                fields["VARIABLE"] = variable_field(obj, variable)?;
             },
 
-            Opcode::AddToList(item, list) => { // TODO
-                todo!("data_addtolist");
+            Opcode::ListContents(list) => {
+                opcode_str = "data_listcontents";
+                fields["LIST"] = list_field(obj, list)?;
             },
 
-            Opcode::DeleteOfList(index, list) => { // TODO
-                todo!("data_deleteoflist");
+            Opcode::AddToList(item, list) => {
+                opcode_str = "data_addtolist";
+                inputs["ITEM"] = item.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                fields["LIST"] = list_field(obj, list)?;
             },
 
-            Opcode::DeleteAllOfList(list) => { // TODO
-                todo!("data_deletealloflist");
+            Opcode::DeleteOfList(index, list) => {
+                opcode_str = "data_deleteoflist";
+                inputs["INDEX"] = index.serialize(block_list, obj, &self.uuid, InputType::Integer)?;
+                fields["LIST"] = list_field(obj, list)?;
             },
 
-            Opcode::InsertAtList(item, index, list) => { // TODO
-                todo!("data_insertatlist");
+            Opcode::DeleteAllOfList(list) => {
+                opcode_str = "data_deletealloflist";
+                fields["LIST"] = list_field(obj, list)?;
+            },
+
+            Opcode::InsertAtList(item, index, list) => {
+                opcode_str = "data_insertatlist";
+                inputs["ITEM"] = item.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                inputs["INDEX"] = index.serialize(block_list, obj, &self.uuid, InputType::Integer)?;
+                fields["LIST"] = list_field(obj, list)?;
             },
 
             Opcode::ReplaceItemOfList(index, list, item) => {
-                todo!("data_replaceitemoflist");
+                opcode_str = "data_replaceitemoflist";
+                inputs["INDEX"] = index.serialize(block_list, obj, &self.uuid, InputType::Integer)?;
+                inputs["ITEM"] = item.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                fields["LIST"] = list_field(obj, list)?;
             },
 
             Opcode::ItemOfList(index, list) => {
-                todo!("data_itemoflist");
+                opcode_str = "data_itemoflist";
+                inputs["INDEX"] = index.serialize(block_list, obj, &self.uuid, InputType::Integer)?;
+                fields["LIST"] = list_field(obj, list)?;
             },
 
             Opcode::ItemNumOfList(item, list) => {
-                todo!("data_itemnumoflist");
+                opcode_str = "data_itemnumoflist";
+                inputs["ITEM"] = item.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                fields["LIST"] = list_field(obj, list)?;
             },
 
             Opcode::LengthOfList(list) => {
-                todo!("data_lengthoflist");
+                opcode_str = "data_lengthoflist";
+                fields["LIST"] = list_field(obj, list)?;
             },
 
             Opcode::ListContainsItem(list, item) => {
-                todo!("data_listcontainsitem");
+                opcode_str = "data_listcontainsitem";
+                inputs["ITEM"] = item.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                fields["LIST"] = list_field(obj, list)?;
             },
 
             Opcode::ShowList(list) => {
-                todo!("data_showlist");
+                opcode_str = "data_showlist";
+                fields["LIST"] = list_field(obj, list)?;
             },
 
             Opcode::HideList(list) => {
-                todo!("data_hidelist");
+                opcode_str = "data_hidelist";
+                fields["LIST"] = list_field(obj, list)?;
             },
 
             // EVENT //
@@ -493,55 +1144,68 @@ impl Block {
             },
 
             Opcode::WhenBroadcastReceived(broadcast_option) => {
-                todo!("event_whenbroadcastreceived");
+                opcode_str = "event_whenbroadcastreceived";
+                fields["BROADCAST_OPTION"] = broadcast_field(obj, broadcast_option)?;
             },
 
             Opcode::WhenBackdropSwitchesTo(backdrop) => {
-                todo!("event_whenbackdropswitchesto");
+                opcode_str = "event_whenbackdropswitchesto";
+                fields["BACKDROP"] = json::array![backdrop.clone(), json::Null];
             },
 
             Opcode::WhenGreaterThan(when_greater_than_menu, value) => {
-                todo!("event_whengreaterthan");
+                opcode_str = "event_whengreaterthan";
+                inputs["VALUE"] = value.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                fields["WHENGREATERTHANMENU"] = json::array![match when_greater_than_menu {
+                    SensingType::Loudness => "LOUDNESS",
+                    SensingType::Timer => "TIMER"
+                }, json::Null];
             },
 
+            // inputs hold the menu as [11, name, id], but it can also be written out as a shadow block
             Opcode::BroadcastMenu(broadcast_option) => {
-                todo!("event_broadcast_menu");
+                opcode_str = "event_broadcast_menu";
+                fields["BROADCAST_OPTION"] = broadcast_field(obj, broadcast_option)?;
+                shadow = true;
             },
 
             Opcode::Broadcast(broadcast_input) => {
-                todo!("event_broadcast");
+                opcode_str = "event_broadcast";
+                inputs["BROADCAST_INPUT"] = broadcast_input.serialize(block_list, obj, &self.uuid, InputType::Broadcast)?;
             },
 
             Opcode::BroadcastAndWait(broadcast_input) => {
-                todo!("event_broadcastandwait");
+                opcode_str = "event_broadcastandwait";
+                inputs["BROADCAST_INPUT"] = broadcast_input.serialize(block_list, obj, &self.uuid, InputType::Broadcast)?;
             },
 
             Opcode::WhenKeyPressed(key_option) => {
-                todo!("event_whenkeypressed");
+                opcode_str = "event_whenkeypressed";
+                fields["KEY_OPTION"] = json::array![key_option.name(), json::Null];
             },
 
 
             // LOOKS //
             Opcode::SayForSecs(message, secs) => {
                 opcode_str = "looks_sayforsecs";
-                inputs["MESSAGE"] = message.serialize(InputType::String)?;
-                inputs["SECS"] = message.serialize(InputType::PositiveNumber)?;
+                inputs["MESSAGE"] = message.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                inputs["SECS"] = secs.serialize(block_list, obj, &self.uuid, InputType::PositiveNumber)?;
             },
 
             Opcode::Say(message) => {
                 opcode_str = "looks_say";
-                inputs["MESSAGE"] = message.serialize(InputType::String)?;
+                inputs["MESSAGE"] = message.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::ThinkForSecs(message, secs) => {
                 opcode_str = "looks_thinkforsecs";
-                inputs["MESSAGE"] = message.serialize(InputType::String)?;
-                inputs["SECS"] = secs.serialize(InputType::PositiveNumber)?;
+                inputs["MESSAGE"] = message.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                inputs["SECS"] = secs.serialize(block_list, obj, &self.uuid, InputType::PositiveNumber)?;
             },
 
             Opcode::Think(message) => {
                 opcode_str = "looks_think";
-                inputs["THINK"] = message.serialize(InputType::String)?;
+                inputs["MESSAGE"] = message.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::Show() => {
@@ -555,11 +1219,15 @@ impl Block {
             // obsolete: looks_hideallsprites
             
             Opcode::ChangeGraphicEffectBy(effect, change) => {
-                todo!("looks_changeeffectby");
+                opcode_str = "looks_changeeffectby";
+                inputs["CHANGE"] = change.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                fields["EFFECT"] = effect.field();
             },
 
-            Opcode::SetGraphicEffectTo(effect, change) => {
-                todo!("looks_seteffectto");
+            Opcode::SetGraphicEffectTo(effect, value) => {
+                opcode_str = "looks_seteffectto";
+                inputs["VALUE"] = value.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                fields["EFFECT"] = effect.field();
             },
 
             Opcode::ClearGraphicEffects() => {
@@ -568,12 +1236,12 @@ impl Block {
 
             Opcode::ChangeSizeBy(change) => {
                 opcode_str = "looks_changesizeby";
-                inputs["CHANGE"] = change.serialize(InputType::Number)?;
+                inputs["CHANGE"] = change.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::SetSizeTo(size) => {
                 opcode_str = "looks_setsizeto";
-                inputs["SIZE"] = size.serialize(InputType::Number)?;
+                inputs["SIZE"] = size.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::Size() => {
@@ -583,12 +1251,20 @@ impl Block {
             // obsolete: looks_changestretchby
             // obsolete: looks_setstretchto
 
+            // costume and backdrop menus can also hold a position, like sound menus
             Opcode::Costume(costume) => {
-                todo!("looks_costume");
+                if !obj.costumes.iter().any(|v| &v.name == costume) && costume.parse::<f64>().is_err() {
+                    return Err(SerializeError::UnknownCostume(costume.clone()));
+                }
+
+                opcode_str = "looks_costume";
+                fields["COSTUME"] = json::array![costume.clone(), json::Null];
+                shadow = true;
             },
 
             Opcode::SwitchCostumeTo(costume) => {
-                todo!("looks_switchcostumeto");
+                opcode_str = "looks_switchcostumeto";
+                inputs["COSTUME"] = costume.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::NextCostume() => {
@@ -596,31 +1272,53 @@ impl Block {
             },
 
             Opcode::SwitchBackdropTo(backdrop) => {
-                todo!("looks_switchbackdropto");
+                opcode_str = "looks_switchbackdropto";
+                inputs["BACKDROP"] = backdrop.serialize(block_list, obj, &self.uuid)?;
             },
 
+            // the menu can also pick the next, previous or a random backdrop
             Opcode::Backdrops(backdrop) => {
-                todo!("looks_backdrops");
+                let special = ["next backdrop", "previous backdrop", "random backdrop"].contains(&backdrop.as_str());
+
+                if !special && !obj.global_data.borrow().backdrops.contains(backdrop) && backdrop.parse::<f64>().is_err() {
+                    return Err(SerializeError::UnknownBackdrop(backdrop.clone()));
+                }
+
+                opcode_str = "looks_backdrops";
+                fields["BACKDROP"] = json::array![backdrop.clone(), json::Null];
+                shadow = true;
             },
 
             Opcode::GoToFrontBack(front_back) => {
-                todo!("looks_gotofrontback");
+                opcode_str = "looks_gotofrontback";
+                fields["FRONT_BACK"] = json::array![match front_back {
+                    FrontBack::Front => "front",
+                    FrontBack::Back => "back"
+                }, json::Null];
             },
 
-            Opcode::GoForwardBackwardLayers(forward_backward) => {
-                todo!("looks_goforwardbackwardlayers");
+            Opcode::GoForwardBackwardLayers(forward_backward, num) => {
+                opcode_str = "looks_goforwardbackwardlayers";
+                inputs["NUM"] = num.serialize(block_list, obj, &self.uuid, InputType::Integer)?;
+                fields["FORWARD_BACKWARD"] = json::array![match forward_backward {
+                    ForwardBack::Forward => "forward",
+                    ForwardBack::Back => "backward"
+                }, json::Null];
             },
 
             Opcode::BackdropNumberName(number_name) => {
-                todo!("looks_backdropnumbername");
+                opcode_str = "looks_backdropnumbername";
+                fields["NUMBER_NAME"] = number_name.field();
             },
 
             Opcode::CostumeNumberName(number_name) => {
-                todo!("looks_costumenumbername");
+                opcode_str = "looks_costumenumbername";
+                fields["NUMBER_NAME"] = number_name.field();
             },
 
             Opcode::SwitchBackdropToAndWait(backdrop) => {
-                todo!("looks_switchbackdroptoandwait");
+                opcode_str = "looks_switchbackdroptoandwait";
+                inputs["BACKDROP"] = backdrop.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::NextBackdrop() => {
@@ -632,79 +1330,89 @@ impl Block {
 
             Opcode::MoveSteps(steps) => {
                 opcode_str = "motion_movesteps";
-                inputs["STEPS"] = steps.serialize(InputType::Number)?;
+                inputs["STEPS"] = steps.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
             
             Opcode::TurnRight(degrees) => {
                 opcode_str = "motion_turnright";
-                inputs["DEGREES"] = degrees.serialize(InputType::Number)?;
+                inputs["DEGREES"] = degrees.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::TurnLeft(degrees) => {
                 opcode_str = "motion_turnleft";
-                inputs["DEGREES"] = degrees.serialize(InputType::Number)?;
+                inputs["DEGREES"] = degrees.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::PointInDirection(direction) => {
                 opcode_str = "motion_pointindirection";
-                inputs["DIRECTION"] = direction.serialize(InputType::Angle)?;
+                inputs["DIRECTION"] = direction.serialize(block_list, obj, &self.uuid, InputType::Angle)?;
             },
 
             Opcode::PointTowardsMenu(towards) => {
-                todo!("motion_pointtowards_menu");
+                opcode_str = "motion_pointtowards_menu";
+                fields["TOWARDS"] = json::array![towards.name(), json::Null];
+                shadow = true;
             },
 
             Opcode::PointTowards(towards) => {
-                todo!("motion_pointtowards");
+                opcode_str = "motion_pointtowards";
+                inputs["TOWARDS"] = towards.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::GoToMenu(to) => {
-                todo!("motion_goto_menu");
+                opcode_str = "motion_goto_menu";
+                fields["TO"] = json::array![to.name(), json::Null];
+                shadow = true;
             },
 
             Opcode::GoToXY(x, y) => {
                 opcode_str = "motion_gotoxy";
-                inputs["X"] = x.serialize(InputType::Number)?;
-                inputs["Y"] = y.serialize(InputType::Number)?;
+                inputs["X"] = x.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                inputs["Y"] = y.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::GoTo(to) => {
-                todo!("motion_goto");
+                opcode_str = "motion_goto";
+                inputs["TO"] = to.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::GlideSecsToXY(secs, x, y) => {
                 opcode_str = "motion_glidesecstoxy";
-                inputs["SECS"] = secs.serialize(InputType::PositiveNumber)?;
-                inputs["X"] = x.serialize(InputType::Number)?;
-                inputs["Y"] = y.serialize(InputType::Number)?;
+                inputs["SECS"] = secs.serialize(block_list, obj, &self.uuid, InputType::PositiveNumber)?;
+                inputs["X"] = x.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                inputs["Y"] = y.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::GlideToMenu(to) => {
-                todo!("motion_glidetomenu");
+                opcode_str = "motion_glideto_menu";
+                fields["TO"] = json::array![to.name(), json::Null];
+                shadow = true;
             },
 
             Opcode::GlideTo(secs, to) => {
-                todo!("motion_glideto");
+                opcode_str = "motion_glideto";
+                inputs["SECS"] = secs.serialize(block_list, obj, &self.uuid, InputType::PositiveNumber)?;
+                inputs["TO"] = to.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::ChangeXBy(dx) => {
                 opcode_str = "motion_changexby";
-                inputs["DX"] = dx.serialize(InputType::Number)?;
+                inputs["DX"] = dx.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::SetX(x) => {
                 opcode_str = "motion_setx";
-                inputs["X"] = x.serialize(InputType::Number)?;
+                inputs["X"] = x.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::ChangeYBy(dy) => {
                 opcode_str = "motion_changeyby";
-                inputs["DY"] = dy.serialize(InputType::Number)?;
+                inputs["DY"] = dy.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::SetY(y) => {
                 opcode_str = "motion_sety";
-                inputs["Y"] = y.serialize(InputType::Number)?;
+                inputs["Y"] = y.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::IfOnEdgeBounce() => {
@@ -712,7 +1420,8 @@ impl Block {
             },
 
             Opcode::SetRotationStyle(style) => {
-                todo!("looks_setrotationstyle");
+                opcode_str = "motion_setrotationstyle";
+                fields["STYLE"] = json::array![style.name(), json::Null];
             },
 
             Opcode::XPosition() => {
@@ -731,132 +1440,162 @@ impl Block {
             // Operators //
             Opcode::Add(num1, num2) => {
                 opcode_str = "operator_add";
-                inputs["NUM1"] = num1.serialize(InputType::Number)?;
-                inputs["NUM2"] = num2.serialize(InputType::Number)?;
+                inputs["NUM1"] = num1.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                inputs["NUM2"] = num2.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::Subtract(num1, num2) => {
-                opcode_str = "operator_add";
-                inputs["NUM1"] = num1.serialize(InputType::Number)?;
-                inputs["NUM2"] = num2.serialize(InputType::Number)?;
+                opcode_str = "operator_subtract";
+                inputs["NUM1"] = num1.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                inputs["NUM2"] = num2.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::Multiply(num1, num2) => {
                 opcode_str = "operator_multiply";
-                inputs["NUM1"] = num1.serialize(InputType::Number)?;
-                inputs["NUM2"] = num2.serialize(InputType::Number)?;
+                inputs["NUM1"] = num1.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                inputs["NUM2"] = num2.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::Divide(num1, num2) => {
                 opcode_str = "operator_divide";
-                inputs["NUM1"] = num1.serialize(InputType::Number)?;
-                inputs["NUM2"] = num2.serialize(InputType::Number)?;
+                inputs["NUM1"] = num1.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                inputs["NUM2"] = num2.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::Random(from, to) => {
                 opcode_str = "operator_random";
-                inputs["FROM"] = from.serialize(InputType::Number)?;
-                inputs["TO"] = to.serialize(InputType::Number)?;
+                inputs["FROM"] = from.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                inputs["TO"] = to.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::Lt(operand1, operand2) => {
                 opcode_str = "operator_lt";
-                inputs["OPERAND1"] = operand1.serialize(InputType::Number)?;
-                inputs["OPERAND2"] = operand2.serialize(InputType::Number)?;
+                inputs["OPERAND1"] = operand1.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                inputs["OPERAND2"] = operand2.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::Equals(operand1, operand2) => {
                 opcode_str = "operator_equals";
-                inputs["OPERAND1"] = operand1.serialize(InputType::Number)?;
-                inputs["OPERAND2"] = operand2.serialize(InputType::Number)?;
+                inputs["OPERAND1"] = operand1.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                inputs["OPERAND2"] = operand2.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::Gt(operand1, operand2) => {
                 opcode_str = "operator_gt";
-                inputs["OPERAND1"] = operand1.serialize(InputType::Number)?;
-                inputs["OPERAND2"] = operand2.serialize(InputType::Number)?;
+                inputs["OPERAND1"] = operand1.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                inputs["OPERAND2"] = operand2.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::And(operand1, operand2) => {
-                todo!("operator_and");
+                opcode_str = "operator_and";
+                inputs["OPERAND1"] = operand1.serialize(block_list, obj, &self.uuid)?;
+                inputs["OPERAND2"] = operand2.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::Or(operand1, operand2) => {
-                todo!("operator_or");
+                opcode_str = "operator_or";
+                inputs["OPERAND1"] = operand1.serialize(block_list, obj, &self.uuid)?;
+                inputs["OPERAND2"] = operand2.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::Not(operand) => {
-                todo!("operator_not");
+                opcode_str = "operator_not";
+                inputs["OPERAND"] = operand.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::Join(string1, string2) => {
                 opcode_str = "operator_join";
-                inputs["STRING1"] = string1.serialize(InputType::Number)?;
-                inputs["STRING2"] = string2.serialize(InputType::Number)?;
+                inputs["STRING1"] = string1.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                inputs["STRING2"] = string2.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::LetterOf(letter, string) => {
                 opcode_str = "operator_letter_of";
-                inputs["LETTER"] = letter.serialize(InputType::WholeNumber)?;
-                inputs["STRING"] = string.serialize(InputType::String)?;
+                inputs["LETTER"] = letter.serialize(block_list, obj, &self.uuid, InputType::WholeNumber)?;
+                inputs["STRING"] = string.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::Length(string) => {
                 opcode_str = "operator_length";
-                inputs["STRING"] = string.serialize(InputType::String)?;
+                inputs["STRING"] = string.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::Contains(string1, string2) => {
                 opcode_str = "operator_contains";
-                inputs["STRING1"] = string1.serialize(InputType::String)?;
-                inputs["STRING2"] = string2.serialize(InputType::String)?;
+                inputs["STRING1"] = string1.serialize(block_list, obj, &self.uuid, InputType::String)?;
+                inputs["STRING2"] = string2.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::Mod(num1, num2) => {
                 opcode_str = "operator_mod";
-                inputs["NUM1"] = num1.serialize(InputType::Number)?;
-                inputs["NUM2"] = num2.serialize(InputType::Number)?;
+                inputs["NUM1"] = num1.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                inputs["NUM2"] = num2.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::Round(num) => {
                 opcode_str = "operator_round";
-                inputs["NUM"] = num.serialize(InputType::Number)?;
+                inputs["NUM"] = num.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::MathOp(operator, num) => {
-                todo!("operator_mathop");
+                opcode_str = "operator_mathop";
+                inputs["NUM"] = num.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                fields["OPERATOR"] = json::array![match operator {
+                    MathOp::Abs => "abs",
+                    MathOp::Floor => "floor",
+                    MathOp::Ceiling => "ceiling",
+                    MathOp::Sqrt => "sqrt",
+                    MathOp::Sin => "sin",
+                    MathOp::Cos => "cos",
+                    MathOp::Tan => "tan",
+                    MathOp::Asin => "asin",
+                    MathOp::Acos => "acos",
+                    MathOp::Atan => "atan",
+                    MathOp::Ln => "ln",
+                    MathOp::Log => "log",
+                    MathOp::NaturalExponent => "e ^",
+                    MathOp::Base10Exponent => "10 ^"
+                }, json::Null];
             },
 
-            // TODO how do custom procedures work?
-
             // Sensing //
             Opcode::TouchingObject(touchingobjectmenu) => {
-                todo!("sensing_touchingobject");
+                opcode_str = "sensing_touchingobject";
+                inputs["TOUCHINGOBJECTMENU"] = touchingobjectmenu.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::TouchingObjectMenu(touchingobjectmenu) => {
-                todo!("sensing_touchingobjectmenu");
+                opcode_str = "sensing_touchingobjectmenu";
+                fields["TOUCHINGOBJECTMENU"] = json::array![touchingobjectmenu.name(), json::Null];
+                shadow = true;
             },
 
             Opcode::TouchingColor(color) => {
-                todo!("sensing_touchingcolor");
+                opcode_str = "sensing_touchingcolor";
+                inputs["COLOR"] = color.input().serialize(block_list, obj, &self.uuid, InputType::Color)?;
             },
 
             Opcode::ColorIsTouchingColor(color, color2) => {
-                todo!("sensing_coloristouchingcolor");
+                opcode_str = "sensing_coloristouchingcolor";
+                inputs["COLOR"] = color.input().serialize(block_list, obj, &self.uuid, InputType::Color)?;
+                inputs["COLOR2"] = color2.input().serialize(block_list, obj, &self.uuid, InputType::Color)?;
             },
 
             Opcode::DistanceTo(distancetomenu) => {
-                todo!("sensing_distanceto");
+                opcode_str = "sensing_distanceto";
+                inputs["DISTANCETOMENU"] = distancetomenu.serialize(block_list, obj, &self.uuid)?;
             },
 
+            // either "_mouse_" or the name of a sprite
             Opcode::DistanceToMenu(distancetomenu) => {
-                todo!("sensing_distancetomenu");
+                opcode_str = "sensing_distancetomenu";
+                fields["DISTANCETOMENU"] = json::array![distancetomenu.clone(), json::Null];
+                shadow = true;
             },
 
             Opcode::AskAndWait(question) => {
                 opcode_str = "sensing_askandwait";
-                inputs["QUESTION"] = question.serialize(InputType::String)?;
+                inputs["QUESTION"] = question.serialize(block_list, obj, &self.uuid, InputType::String)?;
             },
 
             Opcode::Answer() => {
@@ -864,11 +1603,14 @@ impl Block {
             },
 
             Opcode::KeyPressed(key_option) => {
-                todo!("sensing_keypressed");
+                opcode_str = "sensing_keypressed";
+                inputs["KEY_OPTION"] = key_option.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::KeyOptions(key_option) => {
-                todo!("sensing_keyoptions");
+                opcode_str = "sensing_keyoptions";
+                fields["KEY_OPTION"] = json::array![key_option.name(), json::Null];
+                shadow = true;
             },
 
             Opcode::MouseDown() => {
@@ -884,7 +1626,8 @@ impl Block {
             },
 
             Opcode::SetDragMode(drag_mode) => {
-                todo!("sensing_setdragmode");
+                opcode_str = "sensing_setdragmode";
+                fields["DRAG_MODE"] = json::array![if *drag_mode { "draggable" } else { "not draggable" }, json::Null];
             },
 
             Opcode::Loudness() => {
@@ -897,16 +1640,34 @@ impl Block {
                 opcode_str = "sensing_timer";
             },
 
+            Opcode::ResetTimer() => {
+                opcode_str = "sensing_resettimer";
+            },
+
             Opcode::OfObjectMenu(object) => {
-                todo!("sensing_of_object_menu");
+                opcode_str = "sensing_of_object_menu";
+                fields["OBJECT"] = json::array![object.name(), json::Null];
+                shadow = true;
             },
 
+            // the properties in the menu depend on the object picked in the other menu
             Opcode::Of(property, object) => {
-                todo!("sensing_of");
+                let stage = matches!(object.menu.opcode, Opcode::OfObjectMenu(ObjectMenu::Stage));
+
+                opcode_str = "sensing_of";
+                inputs["OBJECT"] = object.serialize(block_list, obj, &self.uuid)?;
+                fields["PROPERTY"] = match property.name(stage) {
+                    Some(name) => json::array![name, json::Null],
+                    None => {
+                        let name = property.name(!stage).unwrap_or_default();
+                        return Err(SerializeError::UnknownProperty(name.to_string()));
+                    }
+                };
             },
 
             Opcode::Current(currentmenu) => {
-                todo!("sensing_current");
+                opcode_str = "sensing_current";
+                fields["CURRENTMENU"] = currentmenu.field();
             },
 
             Opcode::DaysSince2000() => {
@@ -920,18 +1681,26 @@ impl Block {
             // obsolete: sensing_userid
 
             // Sound //
-            // TODO what is sound_sounds_menu?
-
+            // the menu holds the name of a sound. scratch plays the sound at that
+            // position instead if there is no sound by that name and it is a number
             Opcode::SoundsMenu(sound) => {
-                todo!("sound_sounds_menu");
+                if !obj.sounds.iter().any(|v| &v.name == sound) && sound.parse::<f64>().is_err() {
+                    return Err(SerializeError::UnknownSound(sound.clone()));
+                }
+
+                opcode_str = "sound_sounds_menu";
+                fields["SOUND_MENU"] = json::array![sound.clone(), json::Null];
+                shadow = true;
             },
 
             Opcode::Play(sound_menu) => {
-                todo!("sound_play");
+                opcode_str = "sound_play";
+                inputs["SOUND_MENU"] = sound_menu.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::PlayUntilDone(sound_menu) => {
-                todo!("sound_playuntilmode");
+                opcode_str = "sound_playuntildone";
+                inputs["SOUND_MENU"] = sound_menu.serialize(block_list, obj, &self.uuid)?;
             },
 
             Opcode::StopAllSounds() => {
@@ -939,11 +1708,15 @@ impl Block {
             },
 
             Opcode::SetSoundEffectTo(effect, value) => {
-                todo!("sound_seteffectto");
+                opcode_str = "sound_seteffectto";
+                inputs["VALUE"] = value.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                fields["EFFECT"] = effect.field();
             },
 
             Opcode::ChangeSoundEffectBy(effect, value) => {
-                todo!("sound_changeeffectby");
+                opcode_str = "sound_changeeffectby";
+                inputs["VALUE"] = value.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+                fields["EFFECT"] = effect.field();
             },
 
             Opcode::ClearSoundEffects() => {
@@ -952,12 +1725,12 @@ impl Block {
 
             Opcode::ChangeVolumeBy(volume) => {
                 opcode_str = "sound_changevolumeby";
-                inputs["VOLUME"] = volume.serialize(InputType::Number)?;
+                inputs["VOLUME"] = volume.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::SetVolumeTo(volume) => {
                 opcode_str = "sound_setvolumeto";
-                inputs["VOLUME"] = volume.serialize(InputType::Number)?;
+                inputs["VOLUME"] = volume.serialize(block_list, obj, &self.uuid, InputType::Number)?;
             },
 
             Opcode::Volume() => {
@@ -966,8 +1739,161 @@ impl Block {
 
 
 
-            _ => {
-                panic!("unimplemented opcode");
+            // PROCEDURES //
+            Opcode::ProceduresDefinition(prototype) => {
+                opcode_str = "procedures_definition";
+                block_list[prototype.uuid.clone()] = prototype.serialize(block_list, obj, Some(&self.uuid))?;
+                inputs["custom_block"] = json::array![1, prototype.uuid.clone()];
+            },
+
+            Opcode::ProceduresPrototype(prototype) => {
+                opcode_str = "procedures_prototype";
+                shadow = true;
+                mutation = Some(prototype.mutation(true));
+
+                // each argument slot holds a reporter which can be dragged into the definition
+                for arg in prototype.args.iter() {
+                    let reporter = Block::new(match arg.arg_type {
+                        ArgumentType::StringNumber => Opcode::ArgumentReporterStringNumber(arg.name.clone()),
+                        ArgumentType::Boolean => Opcode::ArgumentReporterBoolean(arg.name.clone())
+                    });
+
+                    let mut json = reporter.serialize(block_list, obj, Some(&self.uuid))?;
+                    json["shadow"] = JsonValue::Boolean(true);
+                    block_list[reporter.uuid.clone()] = json;
+                    inputs[arg.id.clone()] = json::array![1, reporter.uuid.clone()];
+                }
+            },
+
+            Opcode::ProceduresCall(prototype, args) => {
+                opcode_str = "procedures_call";
+                mutation = Some(prototype.mutation(false));
+
+                for (arg, input) in prototype.args.iter().zip(args.iter()) {
+                    inputs[arg.id.clone()] = match input {
+                        ArgumentInput::StringNumber(v) => v.serialize(block_list, obj, &self.uuid, InputType::String)?,
+                        ArgumentInput::Boolean(v) => v.serialize(block_list, obj, &self.uuid)?
+                    };
+                }
+            },
+
+            Opcode::ArgumentReporterStringNumber(name) => {
+                opcode_str = "argument_reporter_string_number";
+                fields["VALUE"] = json::array![name.clone(), json::Null];
+            },
+
+            Opcode::ArgumentReporterBoolean(name) => {
+                opcode_str = "argument_reporter_boolean";
+                fields["VALUE"] = json::array![name.clone(), json::Null];
+            },
+
+            Opcode::ProceduresDeclaration() => return Err(SerializeError::UnsupportedOpcode("procedures_declaration")),
+            Opcode::ArgumentEditorBoolean() => return Err(SerializeError::UnsupportedOpcode("argument_editor_boolean")),
+            Opcode::ArgumentEditorStringNumber() => return Err(SerializeError::UnsupportedOpcode("argument_editor_string_number")),
+
+            // EXTENSIONS //
+            Opcode::PenClear() => {
+                opcode_str = "pen_clear";
+            },
+
+            Opcode::PenStamp() => {
+                opcode_str = "pen_stamp";
+            },
+
+            Opcode::PenDown() => {
+                opcode_str = "pen_penDown";
+            },
+
+            Opcode::PenUp() => {
+                opcode_str = "pen_penUp";
+            },
+
+            Opcode::SetPenColorToColor(color) => {
+                opcode_str = "pen_setPenColorToColor";
+                inputs["COLOR"] = color.serialize(block_list, obj, &self.uuid, InputType::Color)?;
+            },
+
+            Opcode::ChangePenColorParamBy(param, value) => {
+                opcode_str = "pen_changePenColorParamBy";
+                inputs["COLOR_PARAM"] = param.serialize(block_list, obj, &self.uuid)?;
+                inputs["VALUE"] = value.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            Opcode::SetPenColorParamTo(param, value) => {
+                opcode_str = "pen_setPenColorParamTo";
+                inputs["COLOR_PARAM"] = param.serialize(block_list, obj, &self.uuid)?;
+                inputs["VALUE"] = value.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            Opcode::PenColorParamMenu(param) => {
+                opcode_str = "pen_menu_colorParam";
+                fields["colorParam"] = json::array![param.name(), json::Null];
+                shadow = true;
+            },
+
+            Opcode::ChangePenSizeBy(size) => {
+                opcode_str = "pen_changePenSizeBy";
+                inputs["SIZE"] = size.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            Opcode::SetPenSizeTo(size) => {
+                opcode_str = "pen_setPenSizeTo";
+                inputs["SIZE"] = size.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            Opcode::PlayDrumForBeats(drum, beats) => {
+                opcode_str = "music_playDrumForBeats";
+                inputs["DRUM"] = drum.serialize(block_list, obj, &self.uuid)?;
+                inputs["BEATS"] = beats.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            // the menus of the music extension hold the number of the option
+            Opcode::DrumMenu(drum) => {
+                opcode_str = "music_menu_DRUM";
+                fields["DRUM"] = json::array![(*drum as u8).to_string(), json::Null];
+                shadow = true;
+            },
+
+            Opcode::RestForBeats(beats) => {
+                opcode_str = "music_restForBeats";
+                inputs["BEATS"] = beats.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            Opcode::PlayNoteForBeats(note, beats) => {
+                opcode_str = "music_playNoteForBeats";
+                inputs["NOTE"] = note.serialize(block_list, obj, &self.uuid)?;
+                inputs["BEATS"] = beats.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            Opcode::Note(note) => {
+                opcode_str = "note";
+                fields["NOTE"] = json::array![note.to_string(), json::Null];
+                shadow = true;
+            },
+
+            Opcode::SetInstrument(instrument) => {
+                opcode_str = "music_setInstrument";
+                inputs["INSTRUMENT"] = instrument.serialize(block_list, obj, &self.uuid)?;
+            },
+
+            Opcode::InstrumentMenu(instrument) => {
+                opcode_str = "music_menu_INSTRUMENT";
+                fields["INSTRUMENT"] = json::array![(*instrument as u8).to_string(), json::Null];
+                shadow = true;
+            },
+
+            Opcode::SetTempo(tempo) => {
+                opcode_str = "music_setTempo";
+                inputs["TEMPO"] = tempo.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            Opcode::ChangeTempo(tempo) => {
+                opcode_str = "music_changeTempo";
+                inputs["TEMPO"] = tempo.serialize(block_list, obj, &self.uuid, InputType::Number)?;
+            },
+
+            Opcode::GetTempo() => {
+                opcode_str = "music_getTempo";
             }
         }
 
@@ -979,9 +1905,150 @@ impl Block {
         };
         res["inputs"] = inputs;
         res["fields"] = fields;
-        res["shadow"] = JsonValue::Boolean(false);
-        res["topLevel"] = JsonValue::Boolean(true);
+        res["shadow"] = JsonValue::Boolean(shadow);
+        res["topLevel"] = JsonValue::Boolean(parent_uuid.is_none());
+
+        if let Some(mutation) = mutation {
+            res["mutation"] = mutation;
+        }
 
         Ok(res)
     }
-}
\ No newline at end of file
+}
+
+// the [name, id] field of a variable
+fn variable_field(obj: &Object, name: &str) -> Result<JsonValue, SerializeError> {
+    match obj.variable_id(name) {
+        Some(id) => Ok(json::array![name, id]),
+        None => Err(SerializeError::UnknownVariable(name.to_string()))
+    }
+}
+
+// the [name, id] field of a broadcast
+fn broadcast_field(obj: &Object, name: &str) -> Result<JsonValue, SerializeError> {
+    match obj.broadcast_id(name) {
+        Some(id) => Ok(json::array![name, id]),
+        None => Err(SerializeError::UnknownBroadcast(name.to_string()))
+    }
+}
+
+// the [name, id] field of a list
+fn list_field(obj: &Object, name: &str) -> Result<JsonValue, SerializeError> {
+    match obj.list_id(name) {
+        Some(id) => Ok(json::array![name, id]),
+        None => Err(SerializeError::UnknownList(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        let color = Color::from_hex("#FF8000").unwrap();
+        assert_eq!((color.r, color.g, color.b), (255.0, 128.0, 0.0));
+        assert_eq!(color.hex(), "#ff8000");
+
+        assert!(Color::from_hex("#000000").is_some());
+        assert!(Color::from_hex("ff8000").is_none());
+        assert!(Color::from_hex("#ff800").is_none());
+        assert!(Color::from_hex("#ff80000").is_none());
+        assert!(Color::from_hex("#gg8000").is_none());
+        assert!(Color::from_hex("#+f+f+f").is_none());
+        assert!(Color::from_hex("#\u{e9}\u{e9}\u{e9}").is_none());
+    }
+
+    #[test]
+    fn hex_channels_are_clamped() {
+        let color = Color { r: 300.0, g: -4.0, b: 15.4 };
+        assert_eq!(color.hex(), "#ff000f");
+    }
+
+    #[test]
+    fn color_inputs() {
+        let mut obj = Object::new("Sprite1", Rc::new(RefCell::new(Data::new())));
+        obj.add_variable(Variable::new("c", Value::String("#ff0000".to_string())));
+        let mut block_list = JsonValue::new_object();
+        let parent = "parent".to_string();
+
+        let input = UserInput::new(Value::String("#00ff00".to_string()), None);
+        let json = input.serialize(&mut block_list, &obj, &parent, InputType::Color).unwrap();
+        assert_eq!(json, json::array![1, json::array![9, "#00ff00"]]);
+
+        let input = UserInput::new(Value::String("green".to_string()), None);
+        let res = input.serialize(&mut block_list, &obj, &parent, InputType::Color);
+        assert!(matches!(res, Err(SerializeError::InvalidColor(color)) if color == "green"));
+
+        // a reporter covers the color picker, whatever value it was given
+        let input = UserInput::new(Value::String(String::new()), Some(Box::new(Block::new(Opcode::Variable("c".to_string())))));
+        let json = input.serialize(&mut block_list, &obj, &parent, InputType::Color).unwrap();
+        let expected = json::array![3, json::array![12, "c", obj.variable_id("c").unwrap()], json::array![9, "#000000"]];
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn create_clone_menu() {
+        let obj = Object::new("Sprite1", Rc::new(RefCell::new(Data::new())));
+        let mut block_list = JsonValue::new_object();
+
+        let block = Block::new(Opcode::CreateCloneOf(MenuInput::new(Opcode::CreateCloneOfMenu(SpriteOption::Myself), None)));
+        let json = block.serialize(&mut block_list, &obj, None).unwrap();
+        let menu = &block_list[json["inputs"]["CLONE_OPTION"][1].as_str().unwrap()];
+
+        assert_eq!(json["opcode"], "control_create_clone_of");
+        assert_eq!(json["inputs"]["CLONE_OPTION"][0], 1);
+        assert_eq!(menu["opcode"], "control_create_clone_of_menu");
+        assert_eq!(menu["fields"]["CLONE_OPTION"], json::array!["_myself_", null]);
+        assert_eq!(menu["shadow"], true);
+    }
+
+    #[test]
+    fn sprite_blocks_on_the_stage_add_no_inputs() {
+        let mut stage = Object::new("Stage", Rc::new(RefCell::new(Data::new())));
+        stage.is_stage = true;
+        let mut block_list = JsonValue::new_object();
+
+        let block = Block::new(Opcode::GoTo(MenuInput::new(Opcode::GoToMenu(PositionTarget::MousePointer), None)));
+        let res = block.serialize(&mut block_list, &stage, None);
+
+        assert!(matches!(res, Err(SerializeError::NotOnStage("motion_goto"))));
+        assert!(block_list.is_empty());
+    }
+
+    #[test]
+    fn variable_and_list_reporters() {
+        let mut obj = Object::new("Sprite1", Rc::new(RefCell::new(Data::new())));
+        obj.add_list(List::new("l", Vec::new()));
+        let mut block_list = JsonValue::new_object();
+        let parent = "parent".to_string();
+
+        let input = UserInput::new(Value::String(String::new()), Some(Box::new(Block::new(Opcode::ListContents("l".to_string())))));
+        let json = input.serialize(&mut block_list, &obj, &parent, InputType::String).unwrap();
+        assert_eq!(json, json::array![3, json::array![13, "l", obj.list_id("l").unwrap()], json::array![10, ""]]);
+        assert!(block_list.is_empty());
+
+        let block = Block::new(Opcode::SetVariableTo("v".to_string(), UserInput::new(Value::Number(1.0), None)));
+        let res = block.serialize(&mut block_list, &obj, None);
+        assert!(matches!(res, Err(SerializeError::UnknownVariable(name)) if name == "v"));
+    }
+
+    #[test]
+    fn serialize_errors() {
+        let obj = Object::new("Sprite1", Rc::new(RefCell::new(Data::new())));
+        let mut block_list = JsonValue::new_object();
+
+        let script = Script::new();
+        assert!(matches!(script.serialize(&mut block_list, &obj, None), Err(SerializeError::EmptyScript)));
+
+        let res = Block::new(Opcode::ProceduresDeclaration()).serialize(&mut block_list, &obj, None);
+        assert!(matches!(res, Err(SerializeError::UnsupportedOpcode("procedures_declaration"))));
+
+        // a number is the position of a sound, but a name has to be one of the sounds of the sprite
+        let play = |sound: &str| Block::new(Opcode::Play(MenuInput::new(Opcode::SoundsMenu(sound.to_string()), None)));
+        assert!(play("2").serialize(&mut block_list, &obj, None).is_ok());
+
+        let err = play("meow").serialize(&mut block_list, &obj, None).unwrap_err();
+        assert_eq!(err.to_string(), "unknown sound \"meow\"");
+    }
+}